@@ -0,0 +1,126 @@
+use ::cursor::{Cursor, Endianness};
+use ::error::ParseResult;
+use ::jpeg::{JPEGSegmentIterator, SegmentMarker, read_frame_header};
+
+/** the start of an APP14 segment written by Adobe applications */
+pub const ADOBE_SEGMENT_HEADER : &[u8] = b"Adobe";
+
+/** The Adobe APP14 segment, which tells how the components were transformed before encoding */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AdobeApp14 {
+  pub dct_encode_version: u16,
+  pub flags0: u16,
+  pub flags1: u16,
+  /** 0 for none (RGB or CMYK), 1 for YCbCr, 2 for YCCK */
+  pub color_transform: u8
+}
+
+/** The colour space of the encoded components */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JpegColorSpace {
+  Grayscale,
+  YCbCr,
+  Rgb,
+  Cmyk,
+  /** CMYK with the CMY components transformed to YCbCr */
+  Ycck,
+  Unknown
+}
+
+impl JpegColorSpace {
+  /** Decides the colour space by the ids of the components in the SOF segment,
+      and the transform in the Adobe segment if there is one. Without an Adobe segment,
+      three components with the ids 'R', 'G' and 'B' are RGB, as libjpeg treats them. */
+  pub fn from_components(component_ids: &[u8], adobe: Option<&AdobeApp14>) -> JpegColorSpace {
+    let transform = adobe.map(|a| a.color_transform);
+    match (component_ids.len(), transform) {
+      (1, _) => JpegColorSpace::Grayscale,
+      (3, Some(0)) => JpegColorSpace::Rgb,
+      (3, None) if component_ids == b"RGB" => JpegColorSpace::Rgb,
+      (3, _) => JpegColorSpace::YCbCr,
+      (4, Some(2)) => JpegColorSpace::Ycck,
+      (4, _) => JpegColorSpace::Cmyk,
+      _ => JpegColorSpace::Unknown
+    }
+  }
+
+  /** CMYK and YCCK are mostly written by print workflows, and not supported by every decoder */
+  pub fn is_cmyk(self) -> bool {
+    self == JpegColorSpace::Cmyk || self == JpegColorSpace::Ycck
+  }
+}
+
+/** Returns the Adobe segment of an APP14 segment, or `None` if the segment holds something else */
+pub fn read_adobe_app14(app14_cursor: Cursor) -> ParseResult<Option<AdobeApp14>> {
+  let mut cursor = app14_cursor.with_endianness(Endianness::Big);
+  if cursor.read_bytes(ADOBE_SEGMENT_HEADER.len()) != Some(ADOBE_SEGMENT_HEADER) {
+    return Ok(None);
+  }
+  Ok(Some(AdobeApp14 {
+    dct_encode_version: cursor.read_num_or_fail()?,
+    flags0: cursor.read_num_or_fail()?,
+    flags1: cursor.read_num_or_fail()?,
+    color_transform: cursor.read_num_or_fail()?
+  }))
+}
+
+/** Detects the colour space of a JPEG file from its segments, without decoding it.
+    Returns `None` if the file has no SOF segment. */
+pub fn read_color_space(jpeg: &[u8]) -> ParseResult<Option<JpegColorSpace>> {
+  let mut adobe = None;
+  let mut component_ids = None;
+  for result in JPEGSegmentIterator::new(Cursor::new(jpeg, Endianness::Big)) {
+    let (marker, segment) = result?;
+    match marker {
+      SegmentMarker::APP(14) if adobe.is_none() => adobe = read_adobe_app14(segment)?,
+      SegmentMarker::SOF(_) => {
+        let header = read_frame_header(marker, &segment)?;
+        component_ids = Some(header.components.iter().map(|c| c.id).collect::<Vec<u8>>());
+      },
+      _ => ()
+    }
+  }
+  Ok(component_ids.map(|ids| JpegColorSpace::from_components(&ids, adobe.as_ref())))
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::jpeg::SegmentMarker;
+  use ::jpeg_editor::JPEGEditor;
+  use ::test_fixtures::JPEG_SAMPLE;
+  use super::{read_adobe_app14, read_color_space, AdobeApp14, JpegColorSpace};
+
+  const ADOBE_YCCK : &[u8] = b"Adobe\x00\x64\x80\x00\x00\x00\x02";
+
+  #[test]
+  fn test_read_adobe_app14() {
+    let adobe = read_adobe_app14(Cursor::new(ADOBE_YCCK, Endianness::Little)).unwrap();
+    assert_eq!(adobe, Some(AdobeApp14 {
+      dct_encode_version: 100,
+      flags0: 0x8000,
+      flags1: 0,
+      color_transform: 2
+    }));
+    assert_eq!(read_adobe_app14(Cursor::new(b"Ducky\x00", Endianness::Big)), Ok(None));
+  }
+
+  #[test]
+  fn test_color_space() {
+    assert_eq!(read_color_space(JPEG_SAMPLE), Ok(Some(JpegColorSpace::YCbCr)));
+
+    let mut editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    editor.insert_segment(1, SegmentMarker::APP(14), b"Adobe\x00\x64\x00\x00\x00\x00\x00".to_vec());
+    let jpeg = editor.write().unwrap();
+    assert_eq!(read_color_space(&jpeg), Ok(Some(JpegColorSpace::Rgb)));
+
+    let adobe = read_adobe_app14(Cursor::new(ADOBE_YCCK, Endianness::Big)).unwrap();
+    assert_eq!(JpegColorSpace::from_components(&[1, 2, 3, 4], adobe.as_ref()), JpegColorSpace::Ycck);
+    assert_eq!(JpegColorSpace::from_components(&[1, 2, 3, 4], None), JpegColorSpace::Cmyk);
+    assert_eq!(JpegColorSpace::from_components(&[1], adobe.as_ref()), JpegColorSpace::Grayscale);
+    assert_eq!(JpegColorSpace::from_components(b"RGB", None), JpegColorSpace::Rgb);
+    assert_eq!(JpegColorSpace::from_components(&[1, 2, 3], None), JpegColorSpace::YCbCr);
+    assert!(JpegColorSpace::Ycck.is_cmyk());
+    assert!(!JpegColorSpace::YCbCr.is_cmyk());
+  }
+}
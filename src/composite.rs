@@ -0,0 +1,375 @@
+use ::Size;
+use ::tags::Section;
+use ::tag::RawExifTag;
+use ::error::ParseResult;
+use ::jfif::Jfif;
+
+/** diagonal of a 36x24mm full frame sensor */
+const FULL_FRAME_DIAGONAL : f64 = 43.266_615_305_567_87;
+
+/** A value derived from several tags, with the names of the tags it was derived from */
+#[derive(Debug, PartialEq, Clone)]
+pub struct DerivedValue {
+  pub value: f64,
+  pub inputs: Vec<&'static str>
+}
+
+impl DerivedValue {
+  fn new(value: f64, inputs: Vec<&'static str>) -> Option<DerivedValue> {
+    if value.is_finite() {
+      Some(DerivedValue { value, inputs })
+    } else {
+      None
+    }
+  }
+}
+
+/** The tags composite values are derived from, collected from all sections.
+    The image size is not stored in EXIF reliably, so it is best taken from
+    the SOF segment with `jpeg::read_image_size_from_sof`, and the resolution
+    may only be stored in the JFIF segment. */
+#[derive(Default)]
+pub struct CompositeTags {
+  pub focal_length: Option<f64>,
+  pub focal_length_35mm: Option<f64>,
+  pub focal_plane_x_resolution: Option<f64>,
+  pub focal_plane_y_resolution: Option<f64>,
+  pub focal_plane_resolution_unit: Option<u32>,
+  pub pixel_x_dimension: Option<u32>,
+  pub pixel_y_dimension: Option<u32>,
+  pub f_number: Option<f64>,
+  pub aperture_value: Option<f64>,
+  pub exposure_time: Option<f64>,
+  pub shutter_speed_value: Option<f64>,
+  pub iso: Option<u32>,
+  pub x_resolution: Option<f64>,
+  pub y_resolution: Option<f64>,
+  pub resolution_unit: Option<u32>,
+  pub image_size: Option<Size>,
+  /** the JFIF density in dots per inch, unless it is only an aspect ratio */
+  pub jfif_dpi: Option<(f64, f64)>
+}
+
+impl CompositeTags {
+  /** collects the inputs from a tag iterator, skipping tags that failed to parse */
+  pub fn from_tags<'a, I>(tags: I) -> CompositeTags
+    where I: Iterator<Item=ParseResult<(RawExifTag<'a>, Section)>>
+  {
+    let mut composite = CompositeTags::default();
+    for (tag, section) in tags.filter_map(Result::ok) {
+      composite.add_tag(&tag, section);
+    }
+    composite
+  }
+
+  pub fn add_tag(&mut self, tag: &RawExifTag, section: Section) {
+    let value = &tag.value;
+    match (section, tag.no) {
+      (Section::SubIFD, 0x920A) => self.focal_length = value.as_f64(),
+      (Section::SubIFD, 0xA405) => self.focal_length_35mm = value.as_f64(),
+      (Section::SubIFD, 0xA20E) => self.focal_plane_x_resolution = value.as_f64(),
+      (Section::SubIFD, 0xA20F) => self.focal_plane_y_resolution = value.as_f64(),
+      (Section::SubIFD, 0xA210) => self.focal_plane_resolution_unit = value.as_u32(),
+      (Section::SubIFD, 0xA002) => self.pixel_x_dimension = value.as_u32(),
+      (Section::SubIFD, 0xA003) => self.pixel_y_dimension = value.as_u32(),
+      (Section::SubIFD, 0x829D) => self.f_number = value.as_f64(),
+      (Section::SubIFD, 0x9202) => self.aperture_value = value.as_f64(),
+      (Section::SubIFD, 0x829A) => self.exposure_time = value.as_f64(),
+      (Section::SubIFD, 0x9201) => self.shutter_speed_value = value.as_f64(),
+      (Section::SubIFD, 0x8827) => self.iso = value.as_u32(),
+      (Section::IFD0, 0x011A) => self.x_resolution = value.as_f64(),
+      (Section::IFD0, 0x011B) => self.y_resolution = value.as_f64(),
+      (Section::IFD0, 0x0128) => self.resolution_unit = value.as_u32(),
+      _ => ()
+    }
+  }
+
+  pub fn with_image_size(mut self, size: Size) -> CompositeTags {
+    self.image_size = Some(size);
+    self
+  }
+
+  pub fn with_jfif(mut self, jfif: &Jfif) -> CompositeTags {
+    self.jfif_dpi = jfif.dpi();
+    self
+  }
+
+  /** image dimensions in pixels and the inputs they came from, preferring the SOF size over the EXIF tags */
+  fn dimensions(&self) -> Option<(f64, f64, Vec<&'static str>)> {
+    if let Some(ref size) = self.image_size {
+      return Some((size.width as f64, size.height as f64, vec!["SOF"]));
+    }
+    match (self.pixel_x_dimension, self.pixel_y_dimension) {
+      (Some(w), Some(h)) => Some((w as f64, h as f64, vec!["PixelXDimension", "PixelYDimension"])),
+      _ => None
+    }
+  }
+
+  fn aperture(&self) -> Option<(f64, &'static str)> {
+    self.f_number.map(|n| (n, "FNumber"))
+      .or_else(|| self.aperture_value.map(|apex| (2f64.powf(apex / 2.0), "ApertureValue")))
+  }
+
+  fn exposure(&self) -> Option<(f64, &'static str)> {
+    self.exposure_time.map(|t| (t, "ExposureTime"))
+      .or_else(|| self.shutter_speed_value.map(|apex| (2f64.powf(-apex), "ShutterSpeedValue")))
+  }
+
+  /** The crop factor relative to a 35mm full frame sensor.
+      Taken from the FocalLengthIn35mmFilm tag if present, otherwise computed
+      from the sensor size given by the focal plane resolution. */
+  pub fn scale_factor_35efl(&self) -> Option<DerivedValue> {
+    if let (Some(focal_length), Some(focal_length_35mm)) = (self.focal_length, self.focal_length_35mm) {
+      if focal_length > 0.0 && focal_length_35mm > 0.0 {
+        return DerivedValue::new(focal_length_35mm / focal_length,
+          vec!["FocalLength", "FocalLengthIn35mmFilm"]);
+      }
+    }
+
+    let x_resolution = self.focal_plane_x_resolution?;
+    let y_resolution = self.focal_plane_y_resolution.unwrap_or(x_resolution);
+    let (width, height, dimensions_inputs) = self.dimensions()?;
+    let unit_mm = match self.focal_plane_resolution_unit {
+      Some(3) => 10.0,
+      Some(4) => 1.0,
+      Some(5) => 0.001,
+      //inches, also used when the unit is missing or `None`
+      _ => 25.4
+    };
+    if x_resolution <= 0.0 || y_resolution <= 0.0 {
+      return None;
+    }
+    let sensor_width = width * unit_mm / x_resolution;
+    let sensor_height = height * unit_mm / y_resolution;
+    let diagonal = (sensor_width * sensor_width + sensor_height * sensor_height).sqrt();
+    let mut inputs = vec!["FocalPlaneXResolution"];
+    if self.focal_plane_y_resolution.is_some() {
+      inputs.push("FocalPlaneYResolution");
+    }
+    if self.focal_plane_resolution_unit.is_some() {
+      inputs.push("FocalPlaneResolutionUnit");
+    }
+    inputs.extend(dimensions_inputs);
+    DerivedValue::new(FULL_FRAME_DIAGONAL / diagonal, inputs)
+  }
+
+  pub fn focal_length_35efl(&self) -> Option<DerivedValue> {
+    let focal_length = self.focal_length?;
+    let scale = self.scale_factor_35efl()?;
+    let mut inputs = vec!["FocalLength"];
+    inputs.extend(scale.inputs.into_iter().filter(|i| *i != "FocalLength"));
+    DerivedValue::new(focal_length * scale.value, inputs)
+  }
+
+  /** horizontal angle of view in degrees */
+  pub fn field_of_view(&self) -> Option<DerivedValue> {
+    let focal_length = self.focal_length_35efl()?;
+    if focal_length.value <= 0.0 {
+      return None;
+    }
+    let radians = 2.0 * (36.0 / (2.0 * focal_length.value)).atan();
+    DerivedValue::new(radians.to_degrees(), focal_length.inputs)
+  }
+
+  /** circle of confusion in mm, using the d/1440 rule */
+  pub fn circle_of_confusion(&self) -> Option<DerivedValue> {
+    let scale = self.scale_factor_35efl()?;
+    DerivedValue::new(FULL_FRAME_DIAGONAL / (scale.value * 1440.0), scale.inputs)
+  }
+
+  /** hyperfocal distance in meters */
+  pub fn hyperfocal_distance(&self) -> Option<DerivedValue> {
+    let focal_length = self.focal_length?;
+    let (aperture, aperture_input) = self.aperture()?;
+    let coc = self.circle_of_confusion()?;
+    if aperture <= 0.0 || coc.value <= 0.0 {
+      return None;
+    }
+    let mut inputs = vec!["FocalLength", aperture_input];
+    inputs.extend(coc.inputs.into_iter().filter(|i| *i != "FocalLength"));
+    DerivedValue::new(focal_length * focal_length / (aperture * coc.value * 1000.0), inputs)
+  }
+
+  /** light value, the exposure value normalized to ISO 100 */
+  pub fn light_value(&self) -> Option<DerivedValue> {
+    let (aperture, aperture_input) = self.aperture()?;
+    let (exposure, exposure_input) = self.exposure()?;
+    let iso = self.iso? as f64;
+    if aperture <= 0.0 || exposure <= 0.0 || iso <= 0.0 {
+      return None;
+    }
+    let lv = 2.0 * aperture.log2() - exposure.log2() - (iso / 100.0).log2();
+    DerivedValue::new(lv, vec![aperture_input, exposure_input, "ISOSpeedRatings"])
+  }
+
+  /** the resolution in dots per inch, preferring XResolution/YResolution over the JFIF density.
+      A ResolutionUnit of 1 means the EXIF resolution has no unit, so the JFIF density is used. */
+  fn dpi(&self, exif_resolution: Option<f64>, exif_input: &'static str, jfif_index: usize) -> Option<DerivedValue> {
+    let factor = match self.resolution_unit {
+      //inches are the default when the unit is missing
+      None | Some(2) => Some(1.0),
+      Some(3) => Some(2.54),
+      _ => None
+    };
+    if let (Some(resolution), Some(factor)) = (exif_resolution, factor) {
+      if resolution > 0.0 {
+        let mut inputs = vec![exif_input];
+        if self.resolution_unit.is_some() {
+          inputs.push("ResolutionUnit");
+        }
+        return DerivedValue::new(resolution * factor, inputs);
+      }
+    }
+    let jfif_dpi = self.jfif_dpi?;
+    let dpi = if jfif_index == 0 { jfif_dpi.0 } else { jfif_dpi.1 };
+    if dpi > 0.0 {
+      DerivedValue::new(dpi, vec!["JFIF"])
+    } else {
+      None
+    }
+  }
+
+  /** the horizontal print resolution in dots per inch */
+  pub fn x_dpi(&self) -> Option<DerivedValue> {
+    self.dpi(self.x_resolution, "XResolution", 0)
+  }
+
+  /** the vertical print resolution in dots per inch */
+  pub fn y_dpi(&self) -> Option<DerivedValue> {
+    self.dpi(self.y_resolution, "YResolution", 1)
+  }
+
+  pub fn megapixels(&self) -> Option<DerivedValue> {
+    let (width, height, dimensions_inputs) = self.dimensions()?;
+    DerivedValue::new(width * height / 1_000_000.0, dimensions_inputs)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::Size;
+  use ::cursor::{Cursor, Endianness};
+  use ::tags::read_tags;
+  use ::jfif::{Jfif, DensityUnit};
+  use ::test_fixtures::{JPEG_SAMPLE, JPEG_SAMPLE_EXIF_OFFSET};
+  use super::CompositeTags;
+
+  fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 0.01, "{} should be close to {}", a, b);
+  }
+
+  #[test]
+  fn test_from_sample() {
+    let cursor = Cursor::new(JPEG_SAMPLE, Endianness::Little);
+    let cursor = cursor.with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).expect("EOF");
+    let composite = CompositeTags::from_tags(read_tags(cursor).unwrap())
+      .with_image_size(Size { width: 2, height: 1 });
+
+    let lv = composite.light_value().unwrap();
+    //f/2.2 at 103/32813s and ISO 100
+    assert_close(lv.value, 10.59);
+    assert_eq!(lv.inputs, vec!["FNumber", "ExposureTime", "ISOSpeedRatings"]);
+
+    let megapixels = composite.megapixels().unwrap();
+    assert_close(megapixels.value, 0.000002);
+    assert_eq!(megapixels.inputs, vec!["SOF"]);
+
+    //the sample has no focal length
+    assert!(composite.field_of_view().is_none());
+
+    let dpi = composite.x_dpi().unwrap();
+    assert_close(dpi.value, 72.0);
+    assert_eq!(dpi.inputs, vec!["XResolution", "ResolutionUnit"]);
+  }
+
+  #[test]
+  fn test_dpi_from_jfif() {
+    let jfif = Jfif {
+      version: (1, 2),
+      units: DensityUnit::DotsPerCm,
+      x_density: 118,
+      y_density: 59,
+      thumbnail: None
+    };
+    let composite = CompositeTags::default().with_jfif(&jfif);
+    let x_dpi = composite.x_dpi().unwrap();
+    assert_close(x_dpi.value, 299.72);
+    assert_eq!(x_dpi.inputs, vec!["JFIF"]);
+    assert_close(composite.y_dpi().unwrap().value, 149.86);
+
+    //EXIF wins when it has a unit
+    let composite = CompositeTags {
+      x_resolution: Some(300.0),
+      resolution_unit: Some(2),
+      ..CompositeTags::default()
+    }.with_jfif(&jfif);
+    assert_close(composite.x_dpi().unwrap().value, 300.0);
+    assert_eq!(composite.x_dpi().unwrap().inputs, vec!["XResolution", "ResolutionUnit"]);
+    //inches are assumed without a unit
+    let composite = CompositeTags { resolution_unit: None, ..composite };
+    assert_eq!(composite.x_dpi().unwrap().inputs, vec!["XResolution"]);
+    let composite = CompositeTags { resolution_unit: Some(1), ..composite };
+    assert_close(composite.x_dpi().unwrap().value, 299.72);
+  }
+
+  #[test]
+  fn test_scale_factor_from_35mm_focal_length() {
+    let composite = CompositeTags {
+      focal_length: Some(24.0),
+      focal_length_35mm: Some(36.0),
+      f_number: Some(8.0),
+      ..CompositeTags::default()
+    };
+    let scale = composite.scale_factor_35efl().unwrap();
+    assert_close(scale.value, 1.5);
+    assert_eq!(scale.inputs, vec!["FocalLength", "FocalLengthIn35mmFilm"]);
+
+    let fov = composite.field_of_view().unwrap();
+    assert_close(fov.value, 53.13);
+
+    let coc = composite.circle_of_confusion().unwrap();
+    assert_close(coc.value, 0.02);
+
+    let hyperfocal = composite.hyperfocal_distance().unwrap();
+    assert_close(hyperfocal.value, 3.59);
+    assert_eq!(hyperfocal.inputs, vec!["FocalLength", "FNumber", "FocalLengthIn35mmFilm"]);
+  }
+
+  #[test]
+  fn test_scale_factor_from_focal_plane() {
+    //a 6000x4000 sensor of 36x24mm
+    let composite = CompositeTags {
+      focal_plane_x_resolution: Some(6000.0 / 36.0),
+      focal_plane_y_resolution: Some(4000.0 / 24.0),
+      focal_plane_resolution_unit: Some(4),
+      ..CompositeTags::default()
+    }.with_image_size(Size { width: 6000, height: 4000 });
+    let scale = composite.scale_factor_35efl().unwrap();
+    assert_close(scale.value, 1.0);
+    assert_eq!(scale.inputs, vec!["FocalPlaneXResolution", "FocalPlaneYResolution", "FocalPlaneResolutionUnit", "SOF"]);
+
+    //without a unit, inches are assumed and the unit is not an input
+    let composite = CompositeTags {
+      focal_plane_x_resolution: Some(6000.0 / 36.0 * 25.4),
+      pixel_x_dimension: Some(6000),
+      pixel_y_dimension: Some(6000),
+      ..CompositeTags::default()
+    };
+    let scale = composite.scale_factor_35efl().unwrap();
+    assert_eq!(scale.inputs, vec!["FocalPlaneXResolution", "PixelXDimension", "PixelYDimension"]);
+    assert_eq!(composite.megapixels().unwrap().inputs, vec!["PixelXDimension", "PixelYDimension"]);
+  }
+
+  #[test]
+  fn test_light_value_from_apex() {
+    let composite = CompositeTags {
+      aperture_value: Some(6.0),
+      shutter_speed_value: Some(8.0),
+      iso: Some(200),
+      ..CompositeTags::default()
+    };
+    let lv = composite.light_value().unwrap();
+    assert_close(lv.value, 13.0);
+    assert_eq!(lv.inputs, vec!["ApertureValue", "ShutterSpeedValue", "ISOSpeedRatings"]);
+  }
+}
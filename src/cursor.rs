@@ -4,7 +4,7 @@ use std::str;
 use std::cmp;
 use ::error::{ParseError, ParseResult};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Endianness {
   Little,
   Big,
@@ -117,6 +117,17 @@ impl<'a> Cursor<'a> {
     self.data.as_ptr() as usize - parent.data.as_ptr() as usize
   }
 
+  /** like `offset_from`, but checks this cursor lies within `parent` */
+  pub fn offset_within(&self, parent: &Cursor<'a>) -> Option<usize> {
+    let start = self.data.as_ptr() as usize;
+    let parent_start = parent.data.as_ptr() as usize;
+    if start < parent_start || start + self.len() > parent_start + parent.len() {
+      None
+    } else {
+      Some(start - parent_start)
+    }
+  }
+
   pub fn len(&self) -> usize {
     self.data.len()
   }
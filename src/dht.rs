@@ -0,0 +1,148 @@
+use ::cursor::{Cursor, Endianness};
+use ::error::{ParseError, ParseResult};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HuffmanClass {
+  Dc,
+  Ac
+}
+
+/** A Huffman table of a DHT segment */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HuffmanTable<'a> {
+  pub class: HuffmanClass,
+  /** the destination the SOS components refer to, 0 to 3 */
+  pub id: u8,
+  /** the number of codes of each length from 1 to 16 bits */
+  pub code_lengths: [u8; 16],
+  /** the symbols in the order of their codes */
+  pub symbols: &'a [u8]
+}
+
+//the example tables of the JPEG standard (Annex K.3), which most encoders use as they are
+const DC_LUMINANCE_LENGTHS : [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const DC_CHROMINANCE_LENGTHS : [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_VALUES : [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const AC_LUMINANCE_LENGTHS : [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7D];
+const AC_CHROMINANCE_LENGTHS : [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+
+const AC_LUMINANCE_VALUES : [u8; 162] = [
+  0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+  0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xA1, 0x08, 0x23, 0x42, 0xB1, 0xC1, 0x15, 0x52, 0xD1, 0xF0,
+  0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0A, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x25, 0x26, 0x27, 0x28,
+  0x29, 0x2A, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+  0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+  0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+  0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7,
+  0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3, 0xC4, 0xC5,
+  0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA, 0xE1, 0xE2,
+  0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+  0xF9, 0xFA
+];
+
+const AC_CHROMINANCE_VALUES : [u8; 162] = [
+  0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+  0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xA1, 0xB1, 0xC1, 0x09, 0x23, 0x33, 0x52, 0xF0,
+  0x15, 0x62, 0x72, 0xD1, 0x0A, 0x16, 0x24, 0x34, 0xE1, 0x25, 0xF1, 0x17, 0x18, 0x19, 0x1A, 0x26,
+  0x27, 0x28, 0x29, 0x2A, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+  0x49, 0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+  0x69, 0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+  0x88, 0x89, 0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5,
+  0xA6, 0xA7, 0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3,
+  0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA,
+  0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+  0xF9, 0xFA
+];
+
+const STANDARD_TABLES : [(HuffmanClass, &[u8; 16], &[u8]); 4] = [
+  (HuffmanClass::Dc, &DC_LUMINANCE_LENGTHS, &DC_VALUES),
+  (HuffmanClass::Dc, &DC_CHROMINANCE_LENGTHS, &DC_VALUES),
+  (HuffmanClass::Ac, &AC_LUMINANCE_LENGTHS, &AC_LUMINANCE_VALUES),
+  (HuffmanClass::Ac, &AC_CHROMINANCE_LENGTHS, &AC_CHROMINANCE_VALUES)
+];
+
+impl<'a> HuffmanTable<'a> {
+  /** Whether this is one of the example tables of the JPEG standard.
+      Encoders that optimize their tables for the image write other ones. */
+  pub fn is_standard(&self) -> bool {
+    STANDARD_TABLES.iter().any(|&(class, lengths, symbols)| {
+      class == self.class && lengths == &self.code_lengths && symbols == self.symbols
+    })
+  }
+}
+
+/** Reads all tables of a DHT segment */
+pub fn read_huffman_tables<'a>(dht_cursor: &Cursor<'a>) -> ParseResult<Vec<HuffmanTable<'a>>> {
+  let segment = dht_cursor.with_endianness(Endianness::Big);
+  let mut cursor = segment;
+  let mut tables = Vec::new();
+  while cursor.len() != 0 {
+    let offset = cursor.offset_from(&segment);
+    let class_and_id : u8 = cursor.read_num_or_fail()?;
+    let class = match class_and_id >> 4 {
+      0 => HuffmanClass::Dc,
+      1 => HuffmanClass::Ac,
+      _ => return Err(ParseError::InvalidHuffmanTable { offset })
+    };
+    let id = class_and_id & 0xF;
+    if id > 3 {
+      return Err(ParseError::InvalidHuffmanTable { offset });
+    }
+    let mut code_lengths = [0u8; 16];
+    code_lengths.copy_from_slice(cursor.read_bytes_or_fail(16)?);
+    let symbol_count = code_lengths.iter().map(|n| *n as usize).sum::<usize>();
+    if symbol_count > 256 {
+      return Err(ParseError::InvalidHuffmanTable { offset });
+    }
+    let symbols = cursor.read_bytes_or_fail(symbol_count)?;
+    tables.push(HuffmanTable { class, id, code_lengths, symbols });
+  }
+  Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::error::ParseError;
+  use ::jpeg::{JPEGSegmentIterator, SegmentMarker};
+  use ::test_fixtures::JPEG_SAMPLE;
+  use super::{read_huffman_tables, HuffmanClass};
+
+  #[test]
+  fn test_sample_tables() {
+    let tables : Vec<_> = JPEGSegmentIterator::new(Cursor::new(JPEG_SAMPLE, Endianness::Big))
+      .map(|r| r.unwrap())
+      .filter(|&(marker, _)| marker == SegmentMarker::DHT)
+      .flat_map(|(_, segment)| read_huffman_tables(&segment).unwrap())
+      .collect();
+    assert_eq!(tables.len(), 4);
+    let classes : Vec<_> = tables.iter().map(|t| (t.class, t.id)).collect();
+    assert_eq!(classes, vec![
+      (HuffmanClass::Dc, 0), (HuffmanClass::Ac, 0),
+      (HuffmanClass::Dc, 1), (HuffmanClass::Ac, 1)
+    ]);
+    assert_eq!(tables[1].symbols.len(), 162);
+    assert!(tables.iter().all(|t| t.is_standard()));
+  }
+
+  #[test]
+  fn test_optimized_tables() {
+    //two tables in one segment, the second one with a single symbol
+    let mut segment = vec![0x01, 0, 2];
+    segment.extend_from_slice(&[0; 14]);
+    segment.extend_from_slice(&[3, 5]);
+    segment.extend_from_slice(&[0x13, 1]);
+    segment.extend_from_slice(&[0; 15]);
+    segment.push(0x00);
+    let tables = read_huffman_tables(&Cursor::new(&segment, Endianness::Little)).unwrap();
+    assert_eq!(tables.len(), 2);
+    assert_eq!((tables[0].class, tables[0].id, tables[0].symbols), (HuffmanClass::Dc, 1, &[3u8, 5][..]));
+    assert_eq!((tables[1].class, tables[1].id, tables[1].symbols), (HuffmanClass::Ac, 3, &[0u8][..]));
+    assert!(!tables[0].is_standard());
+
+    assert_eq!(read_huffman_tables(&Cursor::new(&[0x24], Endianness::Big)),
+      Err(ParseError::InvalidHuffmanTable { offset: 0 }));
+    assert_eq!(read_huffman_tables(&Cursor::new(&segment[.. 18], Endianness::Big)),
+      Err(ParseError::UnexpectedEOF));
+  }
+}
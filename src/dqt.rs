@@ -0,0 +1,207 @@
+use ::cursor::{Cursor, Endianness};
+use ::error::{ParseError, ParseResult};
+use ::jpeg::{JPEGSegmentIterator, SegmentMarker};
+
+/** the position in the 8x8 block of each coefficient in the order they are stored */
+pub const ZIGZAG : [usize; 64] = [
+  0, 1, 8, 16, 9, 2, 3, 10,
+  17, 24, 32, 25, 18, 11, 4, 5,
+  12, 19, 26, 33, 40, 48, 41, 34,
+  27, 20, 13, 6, 7, 14, 21, 28,
+  35, 42, 49, 56, 57, 50, 43, 36,
+  29, 22, 15, 23, 30, 37, 44, 51,
+  58, 59, 52, 45, 38, 31, 39, 46,
+  53, 60, 61, 54, 47, 55, 62, 63
+];
+
+/** the luminance table of the JPEG standard (Annex K), which IJG scales for quality 50 */
+const STANDARD_LUMINANCE : [u16; 64] = [
+  16, 11, 10, 16, 24, 40, 51, 61,
+  12, 12, 14, 19, 26, 58, 60, 55,
+  14, 13, 16, 24, 40, 57, 69, 56,
+  14, 17, 22, 29, 51, 87, 80, 62,
+  18, 22, 37, 56, 68, 109, 103, 77,
+  24, 35, 55, 64, 81, 104, 113, 92,
+  49, 64, 78, 87, 103, 121, 120, 101,
+  72, 92, 95, 98, 112, 100, 103, 99
+];
+
+const STANDARD_CHROMINANCE : [u16; 64] = [
+  17, 18, 24, 47, 99, 99, 99, 99,
+  18, 21, 26, 66, 99, 99, 99, 99,
+  24, 26, 56, 99, 99, 99, 99, 99,
+  47, 66, 99, 99, 99, 99, 99, 99,
+  99, 99, 99, 99, 99, 99, 99, 99,
+  99, 99, 99, 99, 99, 99, 99, 99,
+  99, 99, 99, 99, 99, 99, 99, 99,
+  99, 99, 99, 99, 99, 99, 99, 99
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantizationTable {
+  /** the destination the SOF components refer to, 0 to 3 */
+  pub id: u8,
+  /** whether the values are stored as 16 bits, which only 12 bit images need */
+  pub is_16bit: bool,
+  /** the values in row-major order, not in the zigzag order they are stored in */
+  pub values: [u16; 64]
+}
+
+/** Reads all tables of a DQT segment */
+pub fn read_quantization_tables(dqt_cursor: &Cursor) -> ParseResult<Vec<QuantizationTable>> {
+  let segment = dqt_cursor.with_endianness(Endianness::Big);
+  let mut cursor = segment;
+  let mut tables = Vec::new();
+  while cursor.len() != 0 {
+    let offset = cursor.offset_from(&segment);
+    let precision_and_id : u8 = cursor.read_num_or_fail()?;
+    let is_16bit = match precision_and_id >> 4 {
+      0 => false,
+      1 => true,
+      _ => return Err(ParseError::InvalidQuantizationTable { offset })
+    };
+    let mut values = [0u16; 64];
+    for position in ZIGZAG.iter() {
+      values[*position] = if is_16bit {
+        cursor.read_num_or_fail::<u16>()?
+      } else {
+        cursor.read_num_or_fail::<u8>()? as u16
+      };
+    }
+    tables.push(QuantizationTable { id: precision_and_id & 0xF, is_16bit, values });
+  }
+  Ok(tables)
+}
+
+/** The standard table scaled for `quality` the way IJG libjpeg does it */
+fn scaled_table(standard: &[u16; 64], quality: u32, max: u32) -> [u16; 64] {
+  let scale = if quality < 50 { 5000 / quality } else { 200 - quality * 2 };
+  let mut table = [0u16; 64];
+  for (value, standard) in table.iter_mut().zip(standard.iter()) {
+    let scaled = (*standard as u32 * scale + 50) / 100;
+    *value = scaled.clamp(1, max) as u16;
+  }
+  table
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityEstimate {
+  /** the IJG quality from 1 to 100 whose tables are closest */
+  pub quality: u8,
+  /** whether the tables are exactly the scaled IJG tables */
+  pub is_standard: bool,
+  /** the mean absolute difference of the values from those of the estimated quality */
+  pub error: f64
+}
+
+/** Estimates the IJG quality setting the tables were made with, comparing table 0
+    with the luminance table and the first other table with the chrominance table.
+    Returns `None` without a table 0. */
+pub fn estimate_quality(tables: &[QuantizationTable]) -> Option<QualityEstimate> {
+  let luminance = tables.iter().find(|t| t.id == 0)?;
+  let chrominance = tables.iter().find(|t| t.id != 0);
+  let mut compared = vec![(luminance, &STANDARD_LUMINANCE)];
+  if let Some(chrominance) = chrominance {
+    compared.push((chrominance, &STANDARD_CHROMINANCE));
+  }
+  let count = compared.len() * 64;
+
+  let mut best : Option<(u32, u32)> = None;
+  for quality in 1 ..= 100 {
+    let difference : u32 = compared.iter().map(|&(table, standard)| {
+      let max = if table.is_16bit { 32767 } else { 255 };
+      let scaled = scaled_table(standard, quality, max);
+      table.values.iter().zip(scaled.iter())
+        .map(|(a, b)| (*a as i32 - *b as i32).unsigned_abs())
+        .sum::<u32>()
+    }).sum();
+    //several qualities can give the same tables, prefer the lowest
+    if best.is_none_or(|(_, best_difference)| difference < best_difference) {
+      best = Some((quality, difference));
+    }
+  }
+  best.map(|(quality, difference)| QualityEstimate {
+    quality: quality as u8,
+    is_standard: difference == 0,
+    error: difference as f64 / count as f64
+  })
+}
+
+/** Reads the DQT segments of a JPEG file and estimates the quality they were made with */
+pub fn read_quality(jpeg: &[u8]) -> ParseResult<Option<QualityEstimate>> {
+  let mut tables = Vec::new();
+  for result in JPEGSegmentIterator::new(Cursor::new(jpeg, Endianness::Big)) {
+    let (marker, segment) = result?;
+    if marker == SegmentMarker::DQT {
+      tables.extend(read_quantization_tables(&segment)?);
+    }
+  }
+  Ok(estimate_quality(&tables))
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::error::ParseError;
+  use ::test_fixtures::{JPEG_SAMPLE, expected_segments};
+  use super::{read_quantization_tables, estimate_quality, read_quality, scaled_table, QualityEstimate};
+  use super::{ZIGZAG, STANDARD_LUMINANCE, STANDARD_CHROMINANCE};
+
+  fn dqt_segment(id: u8, is_16bit: bool, values: &[u16; 64]) -> Vec<u8> {
+    let mut segment = vec![(is_16bit as u8) << 4 | id];
+    for position in ZIGZAG.iter() {
+      if is_16bit {
+        segment.extend_from_slice(&values[*position].to_be_bytes());
+      } else {
+        segment.push(values[*position] as u8);
+      }
+    }
+    segment
+  }
+
+  #[test]
+  fn test_sample_quality() {
+    let segment = &expected_segments()[10];
+    let cursor = Cursor::new(&JPEG_SAMPLE[segment.offset .. segment.offset + segment.len], Endianness::Little);
+    let tables = read_quantization_tables(&cursor).unwrap();
+    assert_eq!(tables.len(), 1);
+    assert_eq!(tables[0].id, 0);
+    assert!(!tables[0].is_16bit);
+    assert_eq!(&tables[0].values[.. 8], &[3, 2, 2, 3, 5, 8, 10, 12]);
+
+    assert_eq!(read_quality(JPEG_SAMPLE), Ok(Some(QualityEstimate {
+      quality: 90,
+      is_standard: true,
+      error: 0.0
+    })));
+  }
+
+  #[test]
+  fn test_tables_in_one_segment() {
+    let luminance = scaled_table(&STANDARD_LUMINANCE, 75, 32767);
+    let chrominance = scaled_table(&STANDARD_CHROMINANCE, 75, 255);
+    let mut segment = dqt_segment(0, true, &luminance);
+    segment.extend(dqt_segment(1, false, &chrominance));
+    let tables = read_quantization_tables(&Cursor::new(&segment, Endianness::Big)).unwrap();
+    assert_eq!(tables.len(), 2);
+    assert!(tables[0].is_16bit);
+    assert_eq!(tables[0].values, luminance);
+    assert_eq!(tables[1].values, chrominance);
+    let estimate = estimate_quality(&tables).unwrap();
+    assert_eq!((estimate.quality, estimate.is_standard), (75, true));
+
+    //a custom table is matched approximately
+    let mut custom = tables.clone();
+    custom[0].values[0] += 4;
+    let estimate = estimate_quality(&custom).unwrap();
+    assert_eq!((estimate.quality, estimate.is_standard), (75, false));
+    assert!(estimate.error > 0.0 && estimate.error < 0.1);
+
+    assert_eq!(estimate_quality(&tables[1 ..]), None);
+    let invalid = [0x20u8; 65];
+    assert_eq!(read_quantization_tables(&Cursor::new(&invalid, Endianness::Big)),
+      Err(ParseError::InvalidQuantizationTable { offset: 0 }));
+    assert_eq!(read_quantization_tables(&Cursor::new(&segment[.. 100], Endianness::Big)),
+      Err(ParseError::UnexpectedEOF));
+  }
+}
@@ -6,7 +6,21 @@ pub enum ParseError {
   InvalidTiffData{ data: u16 },
   UnexpectedEOF,
   ValueOutOfBounds { offset: usize },
-  InvalidJPEGSegmentHeader{ header: u8 }
+  InvalidJPEGSegmentHeader{ header: u8 },
+  SegmentTooLarge{ len: usize },
+  PatchSizeMismatch{ expected: usize, actual: usize },
+  TagNotInData,
+  InvalidPlist{ offset: usize },
+  InvalidXml{ offset: usize },
+  InvalidExtendedXmp{ offset: usize },
+  ExtendedXmpChecksumMismatch,
+  InvalidImageResource{ signature: [u8;4] },
+  InvalidIccProfile{ offset: usize },
+  InvalidIccChunk{ sequence: u8 },
+  MissingIccChunk{ sequence: u8 },
+  InvalidJfif{ offset: usize },
+  InvalidQuantizationTable{ offset: usize },
+  InvalidHuffmanTable{ offset: usize }
 }
 
 pub type ParseResult<T> = Result<T, ParseError>;
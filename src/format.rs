@@ -0,0 +1,274 @@
+use std::fmt;
+use ::tags::Section;
+use ::tag::{RawExifTag, ExifVariant};
+use ::names::tag_name;
+use ::value_types::{
+  decode_enumerated,
+  Version,
+  ComponentsConfiguration,
+  FileSource,
+  SceneType
+};
+
+/** A tag value in human readable form, e.g. `1/250 s` or `f/2.8`,
+    with the raw value kept alongside it. */
+pub struct FormattedTag {
+  pub section: Section,
+  pub no: u16,
+  pub name: Option<&'static str>,
+  pub raw: String,
+  pub formatted: String
+}
+
+impl fmt::Display for FormattedTag {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.name {
+      Some(name) => write!(f, "{}: {}", name, self.formatted),
+      None => write!(f, "0x{:04X}: {}", self.no, self.formatted)
+    }
+  }
+}
+
+pub fn format_tag(tag: &RawExifTag, section: Section) -> FormattedTag {
+  let raw = tag.value.to_string();
+  let formatted = format_value(section, tag.no, &tag.value)
+    .unwrap_or_else(|| raw.clone());
+  FormattedTag {
+    section,
+    no: tag.no,
+    name: tag_name(section, tag.no),
+    raw,
+    formatted
+  }
+}
+
+/** Formats GPSLatitude, GPSLongitude and their GPSDest counterparts
+    as degrees, minutes and seconds. The reference (`N`, `S`, `E` or `W`)
+    is stored in a separate tag, so it needs to be passed in. */
+pub fn format_gps_coordinate(value: &ExifVariant, reference: Option<&str>) -> Option<String> {
+  let parts = rationals(value)?;
+  if parts.len() != 3 {
+    return None;
+  }
+  let decimal = parts[0] + parts[1] / 60.0 + parts[2] / 3600.0;
+  //round to hundredths of a second first,
+  //so we never print 60.00 seconds
+  let hundredths = (decimal * 360000.0).round() as u64;
+  let degrees = hundredths / 360000;
+  let minutes = (hundredths % 360000) / 6000;
+  let seconds = (hundredths % 6000) as f64 / 100.0;
+  let coordinate = format!("{} deg {}' {:.2}\"", degrees, minutes, seconds);
+  match reference {
+    Some(r) => Some(format!("{} {}", coordinate, r.trim_end_matches('\0'))),
+    None => Some(coordinate)
+  }
+}
+
+fn format_value(section: Section, tag_no: u16, value: &ExifVariant) -> Option<String> {
+  if let Some(enumerated) = decode_enumerated(section, tag_no, value) {
+    return Some(enumerated.to_string());
+  }
+  if let ExifVariant::Undefined(bytes) = *value {
+    return format_undefined(section, tag_no, bytes);
+  }
+
+  match (section, tag_no) {
+    (Section::IFD0, 0x011A) | (Section::IFD0, 0x011B) |
+    (Section::IFD1, 0x011A) | (Section::IFD1, 0x011B) =>
+      value.as_f64().map(format_number),
+    (Section::SubIFD, 0x829A) =>
+      value.as_f64().map(format_exposure_time),
+    (Section::SubIFD, 0x829D) =>
+      value.as_f64().map(|n| format!("f/{:.1}", n)),
+    (Section::SubIFD, 0x8827) =>
+      value.as_u32().map(|n| format!("ISO {}", n)),
+    (Section::SubIFD, 0x9201) =>
+      value.as_f64().map(|apex| format_exposure_time(2f64.powf(-apex))),
+    (Section::SubIFD, 0x9202) | (Section::SubIFD, 0x9205) =>
+      value.as_f64().map(|apex| format!("f/{:.1}", 2f64.powf(apex / 2.0))),
+    (Section::SubIFD, 0x9204) =>
+      value.as_f64().map(format_exposure_bias),
+    (Section::SubIFD, 0x920A) =>
+      value.as_f64().map(|n| format!("{:.1} mm", n)),
+    (Section::SubIFD, 0xA405) =>
+      value.as_u32().map(|n| format!("{} mm", n)),
+    (Section::GPS, 0x0000) =>
+      match *value {
+        ExifVariant::Bytes(bytes) => {
+          let parts : Vec<String> = bytes.iter().map(|b| b.to_string()).collect();
+          Some(parts.join("."))
+        },
+        _ => None
+      },
+    (Section::GPS, 0x0001) | (Section::GPS, 0x0013) =>
+      format_reference(value, "North", "South"),
+    (Section::GPS, 0x0003) | (Section::GPS, 0x0015) =>
+      format_reference(value, "East", "West"),
+    (Section::GPS, 0x0002) | (Section::GPS, 0x0004) |
+    (Section::GPS, 0x0014) | (Section::GPS, 0x0016) =>
+      format_gps_coordinate(value, None),
+    (Section::GPS, 0x0006) =>
+      value.as_f64().map(|n| format!("{:.1} m", n)),
+    (Section::GPS, 0x0007) =>
+      format_gps_time(value),
+    _ => None
+  }
+}
+
+fn format_undefined(section: Section, tag_no: u16, bytes: &[u8]) -> Option<String> {
+  match (section, tag_no) {
+    (Section::SubIFD, 0x9000) | (Section::SubIFD, 0xA000) | (Section::InteropIFD, 0x0002) =>
+      Version::parse(bytes).map(|v| v.to_string()),
+    (Section::SubIFD, 0x9101) =>
+      ComponentsConfiguration::parse(bytes).map(|c| c.to_string()),
+    (Section::SubIFD, 0xA300) =>
+      FileSource::parse(bytes).map(|s| s.to_string()),
+    (Section::SubIFD, 0xA301) =>
+      SceneType::parse(bytes).map(|s| s.to_string()),
+    _ => None
+  }
+}
+
+fn format_reference(value: &ExifVariant, positive: &str, negative: &str) -> Option<String> {
+  match *value {
+    ExifVariant::Text(text) => match text.trim_end_matches('\0') {
+      "N" | "E" => Some(positive.to_string()),
+      "S" | "W" => Some(negative.to_string()),
+      _ => None
+    },
+    _ => None
+  }
+}
+
+fn format_gps_time(value: &ExifVariant) -> Option<String> {
+  let parts = rationals(value)?;
+  if parts.len() != 3 {
+    return None;
+  }
+  let seconds = if parts[2].fract() == 0.0 {
+    format!("{:02}", parts[2])
+  } else {
+    format!("{:05.2}", parts[2])
+  };
+  Some(format!("{:02}:{:02}:{}", parts[0], parts[1], seconds))
+}
+
+fn format_exposure_time(seconds: f64) -> String {
+  if seconds > 0.0 && seconds < 0.25001 {
+    format!("1/{} s", (0.5 + 1.0 / seconds) as u32)
+  } else {
+    format!("{} s", format_number((seconds * 10.0).round() / 10.0))
+  }
+}
+
+fn format_exposure_bias(ev: f64) -> String {
+  if ev == 0.0 {
+    "0 EV".to_string()
+  } else {
+    format!("{:+.1} EV", ev)
+  }
+}
+
+/** prints whole numbers without a fractional part */
+fn format_number(n: f64) -> String {
+  if n.fract() == 0.0 {
+    format!("{}", n as i64)
+  } else {
+    format!("{}", n)
+  }
+}
+
+fn rationals(value: &ExifVariant) -> Option<Vec<f64>> {
+  match *value {
+    ExifVariant::UIntFraction(ref c) =>
+      c.iter().map(|(n, d)| if d == 0 { None } else { Some(n as f64 / d as f64) }).collect(),
+    ExifVariant::IntFraction(ref c) =>
+      c.iter().map(|(n, d)| if d == 0 { None } else { Some(n as f64 / d as f64) }).collect(),
+    _ => None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::tags::{read_tags, Section};
+  use ::tag::ExifVariant;
+  use ::component::TagComponents;
+  use ::test_fixtures::{JPEG_SAMPLE, JPEG_SAMPLE_EXIF_OFFSET};
+  use super::{format_tag, format_gps_coordinate, format_value};
+
+  fn formatted_sample_tag(section: Section, no: u16) -> (String, String) {
+    let cursor = Cursor::new(JPEG_SAMPLE, Endianness::Little);
+    let cursor = cursor.with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).expect("EOF");
+    read_tags(cursor).unwrap()
+      .map(|r| r.unwrap())
+      .find(|&(ref tag, s)| s == section && tag.no == no)
+      .map(|(tag, s)| {
+        let formatted = format_tag(&tag, s);
+        (formatted.raw, formatted.formatted)
+      })
+      .expect("tag should be in sample")
+  }
+
+  #[test]
+  fn test_format_sample_values() {
+    assert_eq!(formatted_sample_tag(Section::SubIFD, 0x829A),
+      ("103/32813".to_string(), "1/319 s".to_string()));
+    assert_eq!(formatted_sample_tag(Section::SubIFD, 0x829D),
+      ("11/5".to_string(), "f/2.2".to_string()));
+    assert_eq!(formatted_sample_tag(Section::SubIFD, 0x8827),
+      ("100".to_string(), "ISO 100".to_string()));
+    assert_eq!(formatted_sample_tag(Section::IFD0, 0x0112),
+      ("1".to_string(), "Horizontal (normal)".to_string()));
+    assert_eq!(formatted_sample_tag(Section::IFD0, 0x011A),
+      ("72/1".to_string(), "72".to_string()));
+    assert_eq!(formatted_sample_tag(Section::SubIFD, 0x9000),
+      ("(Binary data 4 bytes)".to_string(), "2.20".to_string()));
+    assert_eq!(formatted_sample_tag(Section::SubIFD, 0x9101).1, "Y, Cb, Cr, -");
+    assert_eq!(formatted_sample_tag(Section::GPS, 0x0000).1, "2.2.0.0");
+    assert_eq!(formatted_sample_tag(Section::GPS, 0x0001).1, "North");
+    assert_eq!(formatted_sample_tag(Section::GPS, 0x0002),
+      ("55/1 2/1 1952/100".to_string(), "55 deg 2' 19.52\"".to_string()));
+    assert_eq!(formatted_sample_tag(Section::IFD0, 0x010F),
+      ("Nokia".to_string(), "Nokia".to_string()));
+  }
+
+  #[test]
+  fn test_format_tag_display() {
+    let cursor = Cursor::new(JPEG_SAMPLE, Endianness::Little);
+    let cursor = cursor.with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).expect("EOF");
+    let (tag, section) = read_tags(cursor).unwrap().next().unwrap().unwrap();
+    assert_eq!(format_tag(&tag, section).to_string(), "Make: Nokia");
+  }
+
+  #[test]
+  fn test_format_focal_length_and_aperture() {
+    let focal_length = [0u8, 0u8, 0u8, 35u8, 0u8, 0u8, 0u8, 1u8];
+    let value = ExifVariant::UIntFraction(
+      TagComponents::new(Cursor::new(&focal_length, Endianness::Big), 1));
+    assert_eq!(format_value(Section::SubIFD, 0x920A, &value), Some("35.0 mm".to_string()));
+
+    let exposure_time = [0u8, 0u8, 0u8, 1u8, 0u8, 0u8, 0u8, 250u8];
+    let value = ExifVariant::UIntFraction(
+      TagComponents::new(Cursor::new(&exposure_time, Endianness::Big), 1));
+    assert_eq!(format_value(Section::SubIFD, 0x829A, &value), Some("1/250 s".to_string()));
+
+    let aperture = [0u8, 0u8, 0u8, 3u8, 0u8, 0u8, 0u8, 1u8];
+    let value = ExifVariant::UIntFraction(
+      TagComponents::new(Cursor::new(&aperture, Endianness::Big), 1));
+    assert_eq!(format_value(Section::SubIFD, 0x9202, &value), Some("f/2.8".to_string()));
+  }
+
+  #[test]
+  fn test_format_gps_coordinate_with_reference() {
+    let data = [
+      0u8, 0u8, 0u8, 47u8, 0u8, 0u8, 0u8, 1u8,
+      0u8, 0u8, 0u8, 36u8, 0u8, 0u8, 0u8, 1u8,
+      0u8, 0u8, 0x08u8, 0xE8u8, 0u8, 0u8, 0u8, 100u8
+    ];
+    let value = ExifVariant::UIntFraction(
+      TagComponents::new(Cursor::new(&data, Endianness::Big), 3));
+    assert_eq!(format_gps_coordinate(&value, Some("N")),
+      Some("47 deg 36' 22.80\" N".to_string()));
+  }
+}
@@ -0,0 +1,406 @@
+use ::cursor::{Cursor, Endianness};
+use ::error::{ParseError, ParseResult};
+use ::jpeg::{JPEGSegmentIterator, SegmentMarker};
+
+/** the start of an APP2 segment containing a chunk of an ICC profile */
+pub const ICC_SEGMENT_HEADER : &[u8] = b"ICC_PROFILE\0";
+const HEADER_LEN : usize = 128;
+const PROFILE_SIGNATURE : &[u8] = b"acsp";
+
+/** A part of an ICC profile, which is split over APP2 segments
+    as it mostly doesn't fit into one */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct IccChunk<'a> {
+  /** the 1-based position of this chunk */
+  pub sequence: u8,
+  /** the number of chunks of the profile */
+  pub count: u8,
+  pub data: &'a [u8]
+}
+
+/** Returns the ICC profile chunk of an APP2 segment, or `None` if the segment holds something else */
+pub fn read_icc_chunk<'a>(app2_cursor: Cursor<'a>) -> ParseResult<Option<IccChunk<'a>>> {
+  let mut cursor = app2_cursor;
+  if cursor.read_bytes(ICC_SEGMENT_HEADER.len()) != Some(ICC_SEGMENT_HEADER) {
+    return Ok(None);
+  }
+  let sequence : u8 = cursor.read_num_or_fail()?;
+  let count : u8 = cursor.read_num_or_fail()?;
+  let data = cursor.read_bytes_or_fail(cursor.len())?;
+  Ok(Some(IccChunk { sequence, count, data }))
+}
+
+/** Joins the chunks of an ICC profile, which may be in any order.
+    Every chunk from 1 up to the count must be present exactly once. */
+pub fn assemble_icc_profile(chunks: &[IccChunk]) -> ParseResult<Vec<u8>> {
+  let count = match chunks.first() {
+    Some(chunk) => chunk.count,
+    None => return Err(ParseError::MissingIccChunk { sequence: 1 })
+  };
+  let mut profile = Vec::new();
+  for sequence in 1 ..= count {
+    let mut matching = chunks.iter().filter(|c| c.sequence == sequence);
+    let chunk = matching.next().ok_or(ParseError::MissingIccChunk { sequence })?;
+    if matching.next().is_some() {
+      return Err(ParseError::InvalidIccChunk { sequence });
+    }
+    profile.extend_from_slice(chunk.data);
+  }
+  //chunks outside of 1..=count, or disagreeing about the count
+  if let Some(chunk) = chunks.iter().find(|c| c.count != count || c.sequence == 0 || c.sequence > count) {
+    return Err(ParseError::InvalidIccChunk { sequence: chunk.sequence });
+  }
+  Ok(profile)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IccDeviceClass {
+  Input,
+  Display,
+  Output,
+  DeviceLink,
+  ColorSpace,
+  Abstract,
+  NamedColor,
+  Other([u8; 4])
+}
+
+impl IccDeviceClass {
+  fn from_signature(signature: [u8; 4]) -> IccDeviceClass {
+    match &signature {
+      b"scnr" => IccDeviceClass::Input,
+      b"mntr" => IccDeviceClass::Display,
+      b"prtr" => IccDeviceClass::Output,
+      b"link" => IccDeviceClass::DeviceLink,
+      b"spac" => IccDeviceClass::ColorSpace,
+      b"abst" => IccDeviceClass::Abstract,
+      b"nmcl" => IccDeviceClass::NamedColor,
+      _ => IccDeviceClass::Other(signature)
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IccColorSpace {
+  Xyz,
+  Lab,
+  Rgb,
+  Gray,
+  Cmyk,
+  YCbCr,
+  Other([u8; 4])
+}
+
+impl IccColorSpace {
+  fn from_signature(signature: [u8; 4]) -> IccColorSpace {
+    match &signature {
+      b"XYZ " => IccColorSpace::Xyz,
+      b"Lab " => IccColorSpace::Lab,
+      b"RGB " => IccColorSpace::Rgb,
+      b"GRAY" => IccColorSpace::Gray,
+      b"CMYK" => IccColorSpace::Cmyk,
+      b"YCbr" => IccColorSpace::YCbCr,
+      _ => IccColorSpace::Other(signature)
+    }
+  }
+}
+
+/** The well known RGB spaces, told apart by their description or red primary */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RgbProfile {
+  Srgb,
+  DisplayP3,
+  AdobeRgb
+}
+
+//the X of the D50 adapted red primary, as stored in the rXYZ tag
+const RED_PRIMARIES : [(f64, RgbProfile); 3] = [
+  (0.4361, RgbProfile::Srgb),
+  (0.5151, RgbProfile::DisplayP3),
+  (0.6097, RgbProfile::AdobeRgb)
+];
+
+/** An entry of the tag table following the profile header */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct IccTag<'a> {
+  pub signature: [u8; 4],
+  pub data: &'a [u8]
+}
+
+/** The header and tag table of an ICC profile */
+#[derive(Debug, PartialEq, Clone)]
+pub struct IccProfile<'a> {
+  pub cmm: [u8; 4],
+  /** the major and minor version, like (4, 3) */
+  pub version: (u8, u8),
+  pub device_class: IccDeviceClass,
+  pub color_space: IccColorSpace,
+  pub connection_space: IccColorSpace,
+  pub tags: Vec<IccTag<'a>>
+}
+
+fn read_signature(cursor: &mut Cursor) -> ParseResult<[u8; 4]> {
+  let bytes = cursor.read_bytes_or_fail(4)?;
+  Ok([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+impl<'a> IccProfile<'a> {
+  pub fn parse(data: &'a [u8]) -> ParseResult<IccProfile<'a>> {
+    let profile = Cursor::new(data, Endianness::Big);
+    let mut cursor = profile;
+    let size : u32 = cursor.read_num_or_fail()?;
+    if (size as usize) < HEADER_LEN || size as usize > data.len() {
+      return Err(ParseError::InvalidIccProfile { offset: 0 });
+    }
+    let cmm = read_signature(&mut cursor)?;
+    let version = cursor.read_bytes_or_fail(4)?;
+    let device_class = IccDeviceClass::from_signature(read_signature(&mut cursor)?);
+    let color_space = IccColorSpace::from_signature(read_signature(&mut cursor)?);
+    let connection_space = IccColorSpace::from_signature(read_signature(&mut cursor)?);
+    if profile.with_skip_or_fail(36)?.read_bytes(4) != Some(PROFILE_SIGNATURE) {
+      return Err(ParseError::InvalidIccProfile { offset: 36 });
+    }
+
+    let mut cursor = profile.with_skip_or_fail(HEADER_LEN)?;
+    let tag_count : u32 = cursor.read_num_or_fail()?;
+    let mut tags = Vec::new();
+    for i in 0 .. tag_count as usize {
+      let signature = read_signature(&mut cursor)?;
+      let offset : u32 = cursor.read_num_or_fail()?;
+      let len : u32 = cursor.read_num_or_fail()?;
+      let data = profile.with_skip(offset as usize)
+        .and_then(|mut c| c.read_bytes(len as usize))
+        .ok_or(ParseError::InvalidIccProfile { offset: HEADER_LEN + 4 + i * 12 })?;
+      tags.push(IccTag { signature, data });
+    }
+
+    Ok(IccProfile {
+      cmm,
+      version: (version[0], version[1] >> 4),
+      device_class,
+      color_space,
+      connection_space,
+      tags
+    })
+  }
+
+  pub fn tag(&self, signature: &[u8; 4]) -> Option<&'a [u8]> {
+    self.tags.iter().find(|t| &t.signature == signature).map(|t| t.data)
+  }
+
+  /** the profile description, from a v2 `desc` or the first record of a v4 `mluc` */
+  pub fn description(&self) -> Option<String> {
+    let mut cursor = Cursor::new(self.tag(b"desc")?, Endianness::Big);
+    let tag_type = cursor.read_bytes(4)?;
+    cursor = cursor.with_skip(4)?;
+    match tag_type {
+      b"desc" => {
+        let len : u32 = cursor.read_num()?;
+        let ascii = cursor.read_bytes(len as usize)?;
+        let text : String = ascii.iter().map(|b| *b as char).collect();
+        Some(text.trim_end_matches('\0').to_string())
+      },
+      b"mluc" => {
+        let tag = cursor;
+        let count : u32 = cursor.read_num()?;
+        if count == 0 {
+          return None;
+        }
+        cursor = cursor.with_skip(8)?;
+        let len : u32 = cursor.read_num()?;
+        let offset : u32 = cursor.read_num()?;
+        //the offset is from the start of the tag
+        let mut text = tag.with_skip((offset as usize).checked_sub(8)?)?;
+        let units = (0 .. len / 2).map(|_| text.read_num::<u16>()).collect::<Option<Vec<_>>>()?;
+        Some(String::from_utf16_lossy(&units))
+      },
+      _ => None
+    }
+  }
+
+  /** the X of an XYZ tag, like the red primary `rXYZ` */
+  fn xyz_x(&self, signature: &[u8; 4]) -> Option<f64> {
+    let mut cursor = Cursor::new(self.tag(signature)?, Endianness::Big);
+    if cursor.read_bytes(4)? != b"XYZ " {
+      return None;
+    }
+    let x : i32 = cursor.with_skip(4)?.read_num()?;
+    Some(x as f64 / 65536.0)
+  }
+
+  /** Recognises sRGB, Display P3 and Adobe RGB profiles by their description,
+      falling back to their red primary for unfamiliar descriptions */
+  pub fn rgb_profile(&self) -> Option<RgbProfile> {
+    if self.color_space != IccColorSpace::Rgb {
+      return None;
+    }
+    if let Some(description) = self.description() {
+      if description.contains("P3") {
+        return Some(RgbProfile::DisplayP3);
+      } else if description.contains("sRGB") {
+        return Some(RgbProfile::Srgb);
+      } else if description.contains("Adobe RGB") {
+        return Some(RgbProfile::AdobeRgb);
+      }
+    }
+    let red_x = self.xyz_x(b"rXYZ")?;
+    RED_PRIMARIES.iter()
+      .find(|&&(x, _)| (x - red_x).abs() < 0.005)
+      .map(|&(_, profile)| profile)
+  }
+}
+
+/** Reads the ICC profile of a JPEG file, joined from all its APP2 chunks */
+pub fn read_icc_profile(jpeg: &[u8]) -> ParseResult<Option<Vec<u8>>> {
+  let mut chunks = Vec::new();
+  for result in JPEGSegmentIterator::new(Cursor::new(jpeg, Endianness::Big)) {
+    let (marker, segment) = result?;
+    if marker != SegmentMarker::APP(2) {
+      continue;
+    }
+    if let Some(chunk) = read_icc_chunk(segment)? {
+      chunks.push(chunk);
+    }
+  }
+  if chunks.is_empty() {
+    return Ok(None);
+  }
+  assemble_icc_profile(&chunks).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::error::ParseError;
+  use ::jpeg::SegmentMarker;
+  use ::jpeg_editor::JPEGEditor;
+  use ::test_fixtures::JPEG_SAMPLE;
+  use super::{read_icc_chunk, assemble_icc_profile, read_icc_profile, IccChunk, IccProfile};
+  use super::{IccDeviceClass, IccColorSpace, RgbProfile, ICC_SEGMENT_HEADER};
+
+  fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+  }
+
+  fn v2_description(text: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    push_u32(&mut tag, text.len() as u32 + 1);
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    tag
+  }
+
+  fn v4_description(text: &str) -> Vec<u8> {
+    let units : Vec<u16> = text.encode_utf16().collect();
+    let mut tag = b"mluc\0\0\0\0".to_vec();
+    push_u32(&mut tag, 1);
+    push_u32(&mut tag, 12);
+    tag.extend_from_slice(b"enUS");
+    push_u32(&mut tag, units.len() as u32 * 2);
+    push_u32(&mut tag, 28);
+    for unit in units {
+      tag.extend_from_slice(&unit.to_be_bytes());
+    }
+    tag
+  }
+
+  fn red_primary(x: f64) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    for value in &[x, 0.2225, 0.0139] {
+      tag.extend_from_slice(&((value * 65536.0).round() as i32).to_be_bytes());
+    }
+    tag
+  }
+
+  fn build_profile(version: u8, tags: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut header = vec![0u8; 128];
+    header[4 .. 8].copy_from_slice(b"appl");
+    header[8] = version;
+    header[9] = 0x40;
+    header[12 .. 16].copy_from_slice(b"mntr");
+    header[16 .. 20].copy_from_slice(b"RGB ");
+    header[20 .. 24].copy_from_slice(b"XYZ ");
+    header[36 .. 40].copy_from_slice(b"acsp");
+    let mut table = Vec::new();
+    push_u32(&mut table, tags.len() as u32);
+    let mut data = Vec::new();
+    let data_offset = 128 + 4 + tags.len() * 12;
+    for (signature, tag) in tags {
+      table.extend_from_slice(&signature[..]);
+      push_u32(&mut table, (data_offset + data.len()) as u32);
+      push_u32(&mut table, tag.len() as u32);
+      data.extend_from_slice(tag);
+    }
+    let mut profile = header;
+    profile.extend(table);
+    profile.extend(data);
+    let len = profile.len() as u32;
+    profile[0 .. 4].copy_from_slice(&len.to_be_bytes());
+    profile
+  }
+
+  #[test]
+  fn test_profile_header() {
+    let data = build_profile(4, &[(b"desc", v4_description("Display P3"))]);
+    let profile = IccProfile::parse(&data).unwrap();
+    assert_eq!(profile.cmm, *b"appl");
+    assert_eq!(profile.version, (4, 4));
+    assert_eq!(profile.device_class, IccDeviceClass::Display);
+    assert_eq!(profile.color_space, IccColorSpace::Rgb);
+    assert_eq!(profile.connection_space, IccColorSpace::Xyz);
+    assert_eq!(profile.description(), Some("Display P3".to_string()));
+    assert_eq!(profile.rgb_profile(), Some(RgbProfile::DisplayP3));
+
+    let data = build_profile(2, &[(b"desc", v2_description("sRGB IEC61966-2.1"))]);
+    let profile = IccProfile::parse(&data).unwrap();
+    assert_eq!(profile.description(), Some("sRGB IEC61966-2.1".to_string()));
+    assert_eq!(profile.rgb_profile(), Some(RgbProfile::Srgb));
+
+    //without a familiar description the red primary decides
+    let data = build_profile(2, &[(b"desc", v2_description("Custom")), (b"rXYZ", red_primary(0.5151))]);
+    assert_eq!(IccProfile::parse(&data).unwrap().rgb_profile(), Some(RgbProfile::DisplayP3));
+    let data = build_profile(2, &[(b"rXYZ", red_primary(0.4361))]);
+    assert_eq!(IccProfile::parse(&data).unwrap().rgb_profile(), Some(RgbProfile::Srgb));
+
+    let mut data = build_profile(2, &[]);
+    data[36] = b'x';
+    assert_eq!(IccProfile::parse(&data), Err(ParseError::InvalidIccProfile { offset: 36 }));
+  }
+
+  #[test]
+  fn test_chunks() {
+    let chunk = |sequence, count, data| IccChunk { sequence, count, data };
+    let chunks = [chunk(2, 3, &b"cd"[..]), chunk(1, 3, &b"ab"[..]), chunk(3, 3, &b"e"[..])];
+    assert_eq!(assemble_icc_profile(&chunks), Ok(b"abcde".to_vec()));
+    assert_eq!(assemble_icc_profile(&chunks[.. 2]), Err(ParseError::MissingIccChunk { sequence: 3 }));
+    let duplicate = [chunk(1, 2, &b"a"[..]), chunk(1, 2, &b"a"[..]), chunk(2, 2, &b"b"[..])];
+    assert_eq!(assemble_icc_profile(&duplicate), Err(ParseError::InvalidIccChunk { sequence: 1 }));
+    let extra = [chunk(1, 1, &b"a"[..]), chunk(2, 1, &b"b"[..])];
+    assert_eq!(assemble_icc_profile(&extra), Err(ParseError::InvalidIccChunk { sequence: 2 }));
+
+    let segment = b"ICC_PROFILE\0\x01\x02ab";
+    let read = read_icc_chunk(Cursor::new(segment, Endianness::Big)).unwrap();
+    assert_eq!(read, Some(chunk(1, 2, &b"ab"[..])));
+    assert_eq!(read_icc_chunk(Cursor::new(b"FPXR\0", Endianness::Big)), Ok(None));
+  }
+
+  #[test]
+  fn test_read_icc_profile() {
+    assert_eq!(read_icc_profile(JPEG_SAMPLE), Ok(None));
+
+    let profile = build_profile(2, &[(b"desc", v2_description("Adobe RGB (1998)"))]);
+    let (first, second) = profile.split_at(100);
+    let mut editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    //inserting at the same index stores them out of order, which readers should accept
+    for &(sequence, part) in &[(1u8, first), (2u8, second)] {
+      let mut payload = ICC_SEGMENT_HEADER.to_vec();
+      payload.extend_from_slice(&[sequence, 2]);
+      payload.extend_from_slice(part);
+      editor.insert_segment(2, SegmentMarker::APP(2), payload);
+    }
+    let jpeg = editor.write().unwrap();
+    let data = read_icc_profile(&jpeg).unwrap().unwrap();
+    assert_eq!(data, profile);
+    assert_eq!(IccProfile::parse(&data).unwrap().rgb_profile(), Some(RgbProfile::AdobeRgb));
+  }
+}
@@ -0,0 +1,270 @@
+use ::cursor::{Cursor, Endianness};
+use ::error::ParseResult;
+use ::photoshop::{read_image_resources, IPTC_RESOURCE_ID};
+
+const TAG_MARKER : u8 = 0x1C;
+/** the escape sequence in 1:90 declaring UTF-8 */
+const UTF8_CHARSET : &[u8] = b"\x1B%G";
+
+const IPTC_DATASET_NAMES : &[((u8, u8), &str)] = &[
+  ((1, 0), "EnvelopeRecordVersion"),
+  ((1, 90), "CodedCharacterSet"),
+  ((2, 0), "ApplicationRecordVersion"),
+  ((2, 5), "ObjectName"),
+  ((2, 10), "Urgency"),
+  ((2, 15), "Category"),
+  ((2, 20), "SupplementalCategories"),
+  ((2, 25), "Keywords"),
+  ((2, 40), "SpecialInstructions"),
+  ((2, 55), "DateCreated"),
+  ((2, 60), "TimeCreated"),
+  ((2, 62), "DigitalCreationDate"),
+  ((2, 63), "DigitalCreationTime"),
+  ((2, 65), "OriginatingProgram"),
+  ((2, 80), "By-line"),
+  ((2, 85), "By-lineTitle"),
+  ((2, 90), "City"),
+  ((2, 92), "Sub-location"),
+  ((2, 95), "Province-State"),
+  ((2, 100), "Country-PrimaryLocationCode"),
+  ((2, 101), "Country-PrimaryLocationName"),
+  ((2, 103), "OriginalTransmissionReference"),
+  ((2, 105), "Headline"),
+  ((2, 110), "Credit"),
+  ((2, 115), "Source"),
+  ((2, 116), "CopyrightNotice"),
+  ((2, 118), "Contact"),
+  ((2, 120), "Caption-Abstract"),
+  ((2, 122), "Writer-Editor")
+];
+
+/** the name of a dataset, as used by ExifTool */
+pub fn iptc_dataset_name(record: u8, dataset: u8) -> Option<&'static str> {
+  IPTC_DATASET_NAMES.iter()
+    .find(|&&(id, _)| id == (record, dataset))
+    .map(|&(_, name)| name)
+}
+
+/** the datasets holding binary numbers rather than text */
+fn is_binary(record: u8, dataset: u8) -> bool {
+  matches!((record, dataset), (1, 0) | (1, 90) | (2, 0))
+}
+
+/** A raw IPTC-IIM dataset, identified by its record and dataset number like `2:120` */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct IptcDataSet<'a> {
+  pub record: u8,
+  pub dataset: u8,
+  pub data: &'a [u8]
+}
+
+pub struct IptcIterator<'a> {
+  cursor: Cursor<'a>,
+  failed: bool
+}
+
+impl<'a> IptcIterator<'a> {
+  fn try_next(&mut self) -> ParseResult<IptcDataSet<'a>> {
+    self.cursor.read_num_or_fail::<u8>()?;
+    let record : u8 = self.cursor.read_num_or_fail()?;
+    let dataset : u8 = self.cursor.read_num_or_fail()?;
+    let len : u16 = self.cursor.read_num_or_fail()?;
+    //the extended form stores the size of the length in the lower 15 bits
+    let len = if len & 0x8000 != 0 {
+      let size = (len & 0x7FFF) as usize;
+      let bytes = self.cursor.read_bytes_or_fail(size)?;
+      bytes.iter().fold(0usize, |n, b| n << 8 | *b as usize)
+    } else {
+      len as usize
+    };
+    let data = self.cursor.read_bytes_or_fail(len)?;
+    Ok(IptcDataSet { record, dataset, data })
+  }
+}
+
+impl<'a> Iterator for IptcIterator<'a> {
+  type Item = ParseResult<IptcDataSet<'a>>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    //anything but a tag marker is padding after the last dataset
+    if self.failed || self.cursor.clone().read_num::<u8>() != Some(TAG_MARKER) {
+      return None;
+    }
+    let result = self.try_next();
+    self.failed = result.is_err();
+    Some(result)
+  }
+}
+
+/** Iterates the datasets of IPTC-IIM data, like the Photoshop resource 0x0404 */
+pub fn read_iptc_datasets(data: &[u8]) -> IptcIterator<'_> {
+  IptcIterator {
+    cursor: Cursor::new(data, Endianness::Big),
+    failed: false
+  }
+}
+
+fn decode_latin1(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| *b as char).collect()
+}
+
+/** The text datasets of IPTC-IIM data. Text is decoded as UTF-8 if the
+    CodedCharacterSet (1:90) says so, and as Latin-1 otherwise. */
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Iptc {
+  pub record_version: Option<u16>,
+  pub is_utf8: bool,
+  /** the text datasets as (record, dataset, text), in the order they are stored */
+  pub values: Vec<(u8, u8, String)>
+}
+
+impl Iptc {
+  pub fn parse(data: &[u8]) -> ParseResult<Iptc> {
+    let datasets = read_iptc_datasets(data).collect::<ParseResult<Vec<_>>>()?;
+    let is_utf8 = datasets.iter()
+      .any(|d| (d.record, d.dataset) == (1, 90) && d.data == UTF8_CHARSET);
+    let mut iptc = Iptc { is_utf8, ..Iptc::default() };
+    for dataset in datasets {
+      if (dataset.record, dataset.dataset) == (2, 0) && dataset.data.len() == 2 {
+        iptc.record_version = Some((dataset.data[0] as u16) << 8 | dataset.data[1] as u16);
+      }
+      if is_binary(dataset.record, dataset.dataset) {
+        continue;
+      }
+      let text = if iptc.is_utf8 {
+        String::from_utf8_lossy(dataset.data).into_owned()
+      } else {
+        decode_latin1(dataset.data)
+      };
+      iptc.values.push((dataset.record, dataset.dataset, text.trim_end_matches('\0').to_string()));
+    }
+    Ok(iptc)
+  }
+
+  /** the first value of a dataset */
+  pub fn get(&self, record: u8, dataset: u8) -> Option<&str> {
+    self.get_all(record, dataset).into_iter().next()
+  }
+
+  /** all values of a repeatable dataset, like Keywords */
+  pub fn get_all(&self, record: u8, dataset: u8) -> Vec<&str> {
+    self.values.iter()
+      .filter(|&&(r, d, _)| (r, d) == (record, dataset))
+      .map(|(_, _, text)| text.as_str())
+      .collect()
+  }
+
+  pub fn object_name(&self) -> Option<&str> {
+    self.get(2, 5)
+  }
+
+  pub fn keywords(&self) -> Vec<&str> {
+    self.get_all(2, 25)
+  }
+
+  pub fn by_line(&self) -> Vec<&str> {
+    self.get_all(2, 80)
+  }
+
+  pub fn city(&self) -> Option<&str> {
+    self.get(2, 90)
+  }
+
+  pub fn country(&self) -> Option<&str> {
+    self.get(2, 101)
+  }
+
+  pub fn headline(&self) -> Option<&str> {
+    self.get(2, 105)
+  }
+
+  pub fn credit(&self) -> Option<&str> {
+    self.get(2, 110)
+  }
+
+  pub fn copyright(&self) -> Option<&str> {
+    self.get(2, 116)
+  }
+
+  pub fn caption(&self) -> Option<&str> {
+    self.get(2, 120)
+  }
+}
+
+/** Finds and parses the IPTC-IIM resource of an APP13 segment */
+pub fn read_iptc(app13_cursor: Cursor) -> ParseResult<Option<Iptc>> {
+  let resources = match read_image_resources(app13_cursor) {
+    Some(resources) => resources,
+    None => return Ok(None)
+  };
+  for resource in resources {
+    let resource = resource?;
+    if resource.id == IPTC_RESOURCE_ID {
+      return Iptc::parse(resource.data).map(Some);
+    }
+  }
+  Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::error::ParseError;
+  use ::test_fixtures::{JPEG_SAMPLE, expected_segments};
+  use super::{read_iptc, read_iptc_datasets, iptc_dataset_name, Iptc};
+
+  fn dataset(record: u8, dataset: u8, data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x1C, record, dataset, (data.len() >> 8) as u8, data.len() as u8];
+    out.extend_from_slice(data);
+    out
+  }
+
+  #[test]
+  fn test_sample_iptc() {
+    let segment = &expected_segments()[4];
+    let cursor = Cursor::new(&JPEG_SAMPLE[segment.offset .. segment.offset + segment.len], Endianness::Big);
+    let iptc = read_iptc(cursor).unwrap().unwrap();
+    assert!(iptc.is_utf8);
+    assert_eq!(iptc.record_version, Some(2));
+    assert_eq!(iptc.get(2, 55), Some("20130510"));
+    assert_eq!(iptc.get(2, 60), Some("152135"));
+    assert_eq!(iptc.values.len(), 4);
+    assert_eq!(iptc.caption(), None);
+  }
+
+  #[test]
+  fn test_text_datasets() {
+    let mut data = dataset(1, 90, b"\x1B%G");
+    data.extend(dataset(2, 120, "Caf\u{e9} au lait".as_bytes()));
+    data.extend(dataset(2, 25, b"coffee"));
+    data.extend(dataset(2, 25, b"breakfast"));
+    data.extend(dataset(2, 80, b"Jane Doe"));
+    data.extend(dataset(2, 90, b"Lisbon"));
+    data.extend(dataset(2, 116, "\u{a9} 2024".as_bytes()));
+    data.extend_from_slice(&[0, 0]);
+    let iptc = Iptc::parse(&data).unwrap();
+    assert_eq!(iptc.caption(), Some("Caf\u{e9} au lait"));
+    assert_eq!(iptc.keywords(), vec!["coffee", "breakfast"]);
+    assert_eq!(iptc.by_line(), vec!["Jane Doe"]);
+    assert_eq!(iptc.city(), Some("Lisbon"));
+    assert_eq!(iptc.copyright(), Some("\u{a9} 2024"));
+
+    //without a CodedCharacterSet, text is Latin-1
+    let iptc = Iptc::parse(&dataset(2, 120, b"Caf\xE9")).unwrap();
+    assert_eq!(iptc.caption(), Some("Caf\u{e9}"));
+  }
+
+  #[test]
+  fn test_extended_length() {
+    let text = vec![b'a'; 300];
+    let mut data = vec![0x1C, 2, 120, 0x80, 0x02, 0x01, 0x2C];
+    data.extend_from_slice(&text);
+    let datasets : Vec<_> = read_iptc_datasets(&data).map(|d| d.unwrap()).collect();
+    assert_eq!(datasets.len(), 1);
+    assert_eq!(datasets[0].data.len(), 300);
+    assert_eq!(iptc_dataset_name(2, 120), Some("Caption-Abstract"));
+
+    let truncated = &data[.. 100];
+    assert_eq!(read_iptc_datasets(truncated).next(), Some(Err(ParseError::UnexpectedEOF)));
+  }
+}
@@ -0,0 +1,172 @@
+use ::cursor::{Cursor, Endianness};
+use ::error::{ParseError, ParseResult};
+
+/** the start of an APP0 segment in JFIF files */
+pub const JFIF_SEGMENT_HEADER : &[u8] = b"JFIF\0";
+/** the start of an APP0 segment holding a JFIF extension thumbnail */
+pub const JFXX_SEGMENT_HEADER : &[u8] = b"JFXX\0";
+const PALETTE_LEN : usize = 256 * 3;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DensityUnit {
+  /** the density only gives the pixel aspect ratio */
+  AspectRatio,
+  DotsPerInch,
+  DotsPerCm
+}
+
+/** A thumbnail in the JFIF segment or a JFXX extension segment */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JfifThumbnail<'a> {
+  /** a complete JPEG stream */
+  Jpeg(&'a [u8]),
+  /** one byte per pixel indexing a palette of 256 RGB colours */
+  Palette { width: u8, height: u8, palette: &'a [u8], pixels: &'a [u8] },
+  /** three bytes per pixel */
+  Rgb { width: u8, height: u8, pixels: &'a [u8] }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Jfif<'a> {
+  /** the major and minor version, like (1, 2) */
+  pub version: (u8, u8),
+  pub units: DensityUnit,
+  pub x_density: u16,
+  pub y_density: u16,
+  pub thumbnail: Option<JfifThumbnail<'a>>
+}
+
+impl<'a> Jfif<'a> {
+  /** the horizontal and vertical resolution in dots per inch,
+      or `None` if the density is only an aspect ratio */
+  pub fn dpi(&self) -> Option<(f64, f64)> {
+    let factor = match self.units {
+      DensityUnit::AspectRatio => return None,
+      DensityUnit::DotsPerInch => 1.0,
+      DensityUnit::DotsPerCm => 2.54
+    };
+    Some((self.x_density as f64 * factor, self.y_density as f64 * factor))
+  }
+}
+
+fn read_uncompressed<'a>(cursor: &mut Cursor<'a>, bytes_per_pixel: usize) -> ParseResult<(u8, u8, &'a [u8])> {
+  let width : u8 = cursor.read_num_or_fail()?;
+  let height : u8 = cursor.read_num_or_fail()?;
+  let pixels = cursor.read_bytes_or_fail(width as usize * height as usize * bytes_per_pixel)?;
+  Ok((width, height, pixels))
+}
+
+/** Returns the JFIF header of an APP0 segment, or `None` if the segment holds something else */
+pub fn read_jfif<'a>(app0_cursor: Cursor<'a>) -> ParseResult<Option<Jfif<'a>>> {
+  let segment = app0_cursor.with_endianness(Endianness::Big);
+  let mut cursor = segment;
+  if cursor.read_bytes(JFIF_SEGMENT_HEADER.len()) != Some(JFIF_SEGMENT_HEADER) {
+    return Ok(None);
+  }
+  let major : u8 = cursor.read_num_or_fail()?;
+  let minor : u8 = cursor.read_num_or_fail()?;
+  let units_offset = cursor.offset_from(&segment);
+  let units = match cursor.read_num_or_fail::<u8>()? {
+    0 => DensityUnit::AspectRatio,
+    1 => DensityUnit::DotsPerInch,
+    2 => DensityUnit::DotsPerCm,
+    _ => return Err(ParseError::InvalidJfif { offset: units_offset })
+  };
+  let x_density : u16 = cursor.read_num_or_fail()?;
+  let y_density : u16 = cursor.read_num_or_fail()?;
+  let (width, height, pixels) = read_uncompressed(&mut cursor, 3)?;
+  let thumbnail = if pixels.is_empty() {
+    None
+  } else {
+    Some(JfifThumbnail::Rgb { width, height, pixels })
+  };
+  Ok(Some(Jfif { version: (major, minor), units, x_density, y_density, thumbnail }))
+}
+
+/** Returns the thumbnail of a JFXX APP0 segment, or `None` if the segment holds something else */
+pub fn read_jfxx_thumbnail<'a>(app0_cursor: Cursor<'a>) -> ParseResult<Option<JfifThumbnail<'a>>> {
+  let segment = app0_cursor.with_endianness(Endianness::Big);
+  let mut cursor = segment;
+  if cursor.read_bytes(JFXX_SEGMENT_HEADER.len()) != Some(JFXX_SEGMENT_HEADER) {
+    return Ok(None);
+  }
+  let format_offset = cursor.offset_from(&segment);
+  let thumbnail = match cursor.read_num_or_fail::<u8>()? {
+    0x10 => JfifThumbnail::Jpeg(cursor.read_bytes_or_fail(cursor.len())?),
+    0x11 => {
+      let width : u8 = cursor.read_num_or_fail()?;
+      let height : u8 = cursor.read_num_or_fail()?;
+      let palette = cursor.read_bytes_or_fail(PALETTE_LEN)?;
+      let pixels = cursor.read_bytes_or_fail(width as usize * height as usize)?;
+      JfifThumbnail::Palette { width, height, palette, pixels }
+    },
+    0x13 => {
+      let (width, height, pixels) = read_uncompressed(&mut cursor, 3)?;
+      JfifThumbnail::Rgb { width, height, pixels }
+    },
+    _ => return Err(ParseError::InvalidJfif { offset: format_offset })
+  };
+  Ok(Some(thumbnail))
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::error::ParseError;
+  use ::test_fixtures::{JPEG_SAMPLE, expected_segments};
+  use super::{read_jfif, read_jfxx_thumbnail, Jfif, JfifThumbnail, DensityUnit};
+
+  #[test]
+  fn test_sample_jfif() {
+    let segment = &expected_segments()[1];
+    let cursor = Cursor::new(&JPEG_SAMPLE[segment.offset .. segment.offset + segment.len], Endianness::Little);
+    let jfif = read_jfif(cursor).unwrap().unwrap();
+    assert_eq!(jfif, Jfif {
+      version: (1, 1),
+      units: DensityUnit::AspectRatio,
+      x_density: 72,
+      y_density: 72,
+      thumbnail: None
+    });
+    assert_eq!(jfif.dpi(), None);
+    assert_eq!(read_jfxx_thumbnail(cursor), Ok(None));
+  }
+
+  #[test]
+  fn test_jfif_thumbnail() {
+    let segment = b"JFIF\0\x01\x02\x02\x00\x64\x00\x64\x02\x01\xFF\x00\x00\x00\xFF\x00";
+    let jfif = read_jfif(Cursor::new(segment, Endianness::Big)).unwrap().unwrap();
+    assert_eq!(jfif.dpi(), Some((254.0, 254.0)));
+    assert_eq!(jfif.thumbnail, Some(JfifThumbnail::Rgb { width: 2, height: 1, pixels: &segment[14 ..] }));
+
+    let truncated = &segment[.. segment.len() - 1];
+    assert_eq!(read_jfif(Cursor::new(truncated, Endianness::Big)), Err(ParseError::UnexpectedEOF));
+    let invalid_units = b"JFIF\0\x01\x02\x03\x00\x64\x00\x64\x00\x00";
+    assert_eq!(read_jfif(Cursor::new(invalid_units, Endianness::Big)), Err(ParseError::InvalidJfif { offset: 7 }));
+  }
+
+  #[test]
+  fn test_jfxx_thumbnails() {
+    let jpeg = b"JFXX\0\x10\xFF\xD8\xFF\xD9";
+    let thumbnail = read_jfxx_thumbnail(Cursor::new(jpeg, Endianness::Big)).unwrap();
+    assert_eq!(thumbnail, Some(JfifThumbnail::Jpeg(b"\xFF\xD8\xFF\xD9")));
+
+    let mut palette = b"JFXX\0\x11\x02\x02".to_vec();
+    palette.extend_from_slice(&[7; 768]);
+    palette.extend_from_slice(&[0, 1, 2, 3]);
+    match read_jfxx_thumbnail(Cursor::new(&palette, Endianness::Big)).unwrap() {
+      Some(JfifThumbnail::Palette { width: 2, height: 2, palette, pixels }) => {
+        assert_eq!(palette.len(), 768);
+        assert_eq!(pixels, &[0, 1, 2, 3]);
+      },
+      other => panic!("unexpected thumbnail {:?}", other)
+    }
+
+    let rgb = b"JFXX\0\x13\x01\x01\x01\x02\x03";
+    let thumbnail = read_jfxx_thumbnail(Cursor::new(rgb, Endianness::Big)).unwrap();
+    assert_eq!(thumbnail, Some(JfifThumbnail::Rgb { width: 1, height: 1, pixels: b"\x01\x02\x03" }));
+
+    let unknown = b"JFXX\0\x12";
+    assert_eq!(read_jfxx_thumbnail(Cursor::new(unknown, Endianness::Big)), Err(ParseError::InvalidJfif { offset: 5 }));
+  }
+}
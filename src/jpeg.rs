@@ -1,5 +1,5 @@
 use std::iter::Iterator;
-use ::cursor::Cursor;
+use ::cursor::{Cursor, Endianness};
 use ::Size;
 use ::error::{ParseError, ParseResult};
 
@@ -19,12 +19,11 @@ pub enum SegmentMarker {
 }
 
 impl SegmentMarker {
-  fn has_size(self) -> bool {
+  pub fn has_size(self) -> bool {
     match self {
       SegmentMarker::RST(_) | 
       SegmentMarker::EOI | 
-      SegmentMarker::SOI | 
-      SegmentMarker::SOS => false,
+      SegmentMarker::SOI => false,
       _ => true
     }
   }
@@ -51,6 +50,25 @@ impl From<u8> for SegmentMarker {
   }
 }
 
+impl From<SegmentMarker> for u8 {
+
+  fn from(marker: SegmentMarker) -> u8 {
+    match marker {
+      SegmentMarker::SOI => 0xD8,
+      SegmentMarker::DHT => 0xC4,
+      SegmentMarker::DQT => 0xDB,
+      SegmentMarker::DRI => 0xDD,
+      SegmentMarker::SOS => 0xDA,
+      SegmentMarker::COM => 0xFE,
+      SegmentMarker::EOI => 0xD9,
+      SegmentMarker::APP(n) => 0xE0 + n,
+      SegmentMarker::RST(n) => 0xD0 + n,
+      SegmentMarker::SOF(n) => 0xC0 + n,
+      SegmentMarker::Other(value) => value
+    }
+  }
+}
+
 pub struct JPEGSegmentIterator<'a> {
   cursor: Cursor<'a>,
   at_end: bool,
@@ -82,9 +100,9 @@ impl<'a> JPEGSegmentIterator<'a> {
     let marker = SegmentMarker::from(
       self.cursor.read_num_or_fail::<u8>()?);
 
-    //stop reading the stream at the SOS (Start of Stream) marker,
-    //because its length is not stored in the header so we can't
-    //know where to jump to. The only marker after that is just EOI (End Of Image) anyway
+    //stop reading the stream after the SOS (Start of Scan) segment, because
+    //the entropy-coded data that follows it has no length, so we can't know
+    //where to jump to. The only marker after that is just EOI (End Of Image) anyway
     if marker == SegmentMarker::SOS {
       self.at_end = true;
     }
@@ -94,7 +112,6 @@ impl<'a> JPEGSegmentIterator<'a> {
     } else {
       0
     };
-    println!("len: {}", len);
     let segment_cursor = self.cursor.with_max_len(len as usize);
 
     self.next_skip = len;
@@ -120,11 +137,175 @@ impl<'a> Iterator for JPEGSegmentIterator<'a> {
 }
 
 pub fn read_image_size_from_sof<'a>(sof_cursor: &Cursor<'a>) -> ParseResult<Size> {
-  let mut sof_cursor = sof_cursor.with_skip_or_fail(1)?;
-  let height : u16 = sof_cursor.read_num_or_fail()?;
-  let width : u16 = sof_cursor.read_num_or_fail()?;
+  //the size is at the same place in all SOF segments
+  read_frame_header(SegmentMarker::SOF(0), sof_cursor).map(|header| header.size)
+}
+
+/** The coding process of a frame, given by the index of its SOF marker */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CodingProcess {
+  Baseline,
+  ExtendedSequential,
+  Progressive,
+  Lossless
+}
+
+/** How the chroma components are subsampled relative to the luma component */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChromaSubsampling {
+  S444,
+  S422,
+  S420,
+  S411,
+  S440,
+  /** the horizontal and vertical sampling factors of the luma and chroma components,
+      when they don't give one of the ratios above */
+  Other { luma: (u8, u8), chroma: (u8, u8) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameComponent {
+  pub id: u8,
+  pub horizontal_sampling: u8,
+  pub vertical_sampling: u8,
+  pub quantization_table: u8
+}
+
+/** The contents of a SOF segment */
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameHeader {
+  /** the n of the SOFn marker */
+  pub sof_index: u8,
+  /** bits per sample, 8 or 12 for lossy frames */
+  pub precision: u8,
+  pub size: Size,
+  pub components: Vec<FrameComponent>
+}
+
+impl FrameHeader {
+  pub fn coding_process(&self) -> CodingProcess {
+    match self.sof_index & 0x3 {
+      0 => CodingProcess::Baseline,
+      1 => CodingProcess::ExtendedSequential,
+      2 => CodingProcess::Progressive,
+      _ => CodingProcess::Lossless
+    }
+  }
+
+  /** SOF9 and up use arithmetic coding instead of Huffman coding */
+  pub fn is_arithmetic(&self) -> bool {
+    self.sof_index >= 8
+  }
+
+  /** SOF5-7 and SOF13-15 are differential frames of hierarchical images */
+  pub fn is_differential(&self) -> bool {
+    self.sof_index & 0x4 != 0
+  }
+
+  /** The subsampling of three component images, assuming the first component is luma.
+      Returns `None` if the two chroma components are sampled differently. */
+  pub fn chroma_subsampling(&self) -> Option<ChromaSubsampling> {
+    if self.components.len() != 3 {
+      return None;
+    }
+    let factors = |c: &FrameComponent| (c.horizontal_sampling, c.vertical_sampling);
+    let luma = factors(&self.components[0]);
+    let chroma = factors(&self.components[1]);
+    if chroma != factors(&self.components[2]) || chroma.0 == 0 || chroma.1 == 0 {
+      return None;
+    }
+    let ratio = if luma.0.is_multiple_of(chroma.0) && luma.1.is_multiple_of(chroma.1) {
+      Some((luma.0 / chroma.0, luma.1 / chroma.1))
+    } else {
+      None
+    };
+    Some(match ratio {
+      Some((1, 1)) => ChromaSubsampling::S444,
+      Some((2, 1)) => ChromaSubsampling::S422,
+      Some((2, 2)) => ChromaSubsampling::S420,
+      Some((4, 1)) => ChromaSubsampling::S411,
+      Some((1, 2)) => ChromaSubsampling::S440,
+      _ => ChromaSubsampling::Other { luma, chroma }
+    })
+  }
+}
+
+/** Reads the precision, size and components of a SOF segment */
+pub fn read_frame_header(marker: SegmentMarker, sof_cursor: &Cursor) -> ParseResult<FrameHeader> {
+  let sof_index = match marker {
+    SegmentMarker::SOF(n) => n,
+    _ => return Err(ParseError::InvalidJPEGSegmentHeader { header: u8::from(marker) })
+  };
+  let mut cursor = sof_cursor.with_endianness(Endianness::Big);
+  let precision : u8 = cursor.read_num_or_fail()?;
+  let height : u16 = cursor.read_num_or_fail()?;
+  let width : u16 = cursor.read_num_or_fail()?;
+  let component_count : u8 = cursor.read_num_or_fail()?;
+  let mut components = Vec::with_capacity(component_count as usize);
+  for _ in 0 .. component_count {
+    let id : u8 = cursor.read_num_or_fail()?;
+    let sampling : u8 = cursor.read_num_or_fail()?;
+    let quantization_table : u8 = cursor.read_num_or_fail()?;
+    components.push(FrameComponent {
+      id,
+      horizontal_sampling: sampling >> 4,
+      vertical_sampling: sampling & 0xF,
+      quantization_table
+    });
+  }
+  Ok(FrameHeader {
+    sof_index,
+    precision,
+    size: Size { height, width },
+    components
+  })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanComponent {
+  /** the id of the component in the SOF segment */
+  pub id: u8,
+  pub dc_table: u8,
+  pub ac_table: u8
+}
+
+/** The header of a scan, following the SOS marker */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanHeader {
+  pub components: Vec<ScanComponent>,
+  /** the first and last coefficient of the spectral selection, 0 and 63 for sequential scans */
+  pub spectral_start: u8,
+  pub spectral_end: u8,
+  /** the bit positions of the successive approximation, 0 for sequential scans */
+  pub approximation_high: u8,
+  pub approximation_low: u8
+}
+
+/** Reads the header of a scan from a SOS segment, not including the entropy-coded data after it */
+pub fn read_scan_header(sos_cursor: &Cursor) -> ParseResult<ScanHeader> {
+  let mut cursor = sos_cursor.with_endianness(Endianness::Big);
+  let component_count : u8 = cursor.read_num_or_fail()?;
+  let mut components = Vec::with_capacity(component_count as usize);
+  for _ in 0 .. component_count {
+    let id : u8 = cursor.read_num_or_fail()?;
+    let tables : u8 = cursor.read_num_or_fail()?;
+    components.push(ScanComponent { id, dc_table: tables >> 4, ac_table: tables & 0xF });
+  }
+  let spectral_start : u8 = cursor.read_num_or_fail()?;
+  let spectral_end : u8 = cursor.read_num_or_fail()?;
+  let approximation : u8 = cursor.read_num_or_fail()?;
+  Ok(ScanHeader {
+    components,
+    spectral_start,
+    spectral_end,
+    approximation_high: approximation >> 4,
+    approximation_low: approximation & 0xF
+  })
+}
 
-  Ok(Size { height: height, width: width })
+/** Reads the number of MCUs between restart markers from a DRI segment, 0 if there are none */
+pub fn read_restart_interval(dri_cursor: &Cursor) -> ParseResult<u16> {
+  dri_cursor.with_endianness(Endianness::Big).read_num_or_fail()
 }
 
 #[cfg(test)]
@@ -136,7 +317,14 @@ mod tests {
   use super::{
     JPEGSegmentIterator,
     SegmentMarker,
-    read_image_size_from_sof
+    read_image_size_from_sof,
+    read_frame_header,
+    FrameComponent,
+    CodingProcess,
+    ChromaSubsampling,
+    ScanComponent,
+    read_scan_header,
+    read_restart_interval
   };
   use ::test_fixtures::{
     JPEG_SAMPLE,
@@ -191,4 +379,73 @@ mod tests {
     assert_eq!(size.width, 2);
     assert_eq!(size.height, 1);
   }
+
+  #[test]
+  fn test_frame_header() {
+    let segment = &expected_segments()[5];
+    let cursor = Cursor::new(&JPEG_SAMPLE[segment.offset .. segment.offset + segment.len], Endianness::Little);
+    let header = read_frame_header(SegmentMarker::SOF(0), &cursor).unwrap();
+    assert_eq!(header.precision, 8);
+    assert_eq!((header.size.width, header.size.height), (2, 1));
+    assert_eq!(header.components.len(), 3);
+    assert_eq!(header.components[0], FrameComponent {
+      id: 1,
+      horizontal_sampling: 1,
+      vertical_sampling: 2,
+      quantization_table: 0
+    });
+    assert_eq!(header.components[2].quantization_table, 1);
+    assert_eq!(header.coding_process(), CodingProcess::Baseline);
+    assert!(!header.is_arithmetic() && !header.is_differential());
+    assert_eq!(header.chroma_subsampling(), Some(ChromaSubsampling::S440));
+
+    let progressive = b"\x08\x00\x10\x00\x10\x03\x01\x22\x00\x02\x11\x01\x03\x11\x01";
+    let cursor = Cursor::new(progressive, Endianness::Big);
+    let header = read_frame_header(SegmentMarker::SOF(10), &cursor).unwrap();
+    assert_eq!(header.coding_process(), CodingProcess::Progressive);
+    assert!(header.is_arithmetic());
+    assert_eq!(header.chroma_subsampling(), Some(ChromaSubsampling::S420));
+
+    //chroma sampled at a higher rate than luma
+    let upsampled = b"\x08\x00\x10\x00\x10\x03\x01\x11\x00\x02\x22\x01\x03\x22\x01";
+    let header = read_frame_header(SegmentMarker::SOF(0), &Cursor::new(upsampled, Endianness::Big)).unwrap();
+    assert_eq!(header.chroma_subsampling(), Some(ChromaSubsampling::Other { luma: (1, 1), chroma: (2, 2) }));
+    //Cb and Cr sampled differently
+    let mixed = b"\x08\x00\x10\x00\x10\x03\x01\x22\x00\x02\x11\x01\x03\x21\x01";
+    let header = read_frame_header(SegmentMarker::SOF(0), &Cursor::new(mixed, Endianness::Big)).unwrap();
+    assert_eq!(header.chroma_subsampling(), None);
+
+    assert!(read_frame_header(SegmentMarker::DQT, &cursor).is_err());
+    let truncated = Cursor::new(&progressive[.. 10], Endianness::Big);
+    assert!(read_frame_header(SegmentMarker::SOF(2), &truncated).is_err());
+  }
+
+  #[test]
+  fn test_scan_header() {
+    let jpeg = Cursor::new(JPEG_SAMPLE, Endianness::Big);
+    let segments : Vec<_> = JPEGSegmentIterator::new(jpeg).map(|r| r.unwrap()).collect();
+    let &(_, dri) = segments.iter().find(|&&(marker, _)| marker == SegmentMarker::DRI).unwrap();
+    assert_eq!(read_restart_interval(&dri).unwrap(), 1);
+
+    let &(marker, sos) = segments.last().unwrap();
+    assert_eq!(marker, SegmentMarker::SOS);
+    let header = read_scan_header(&sos).unwrap();
+    assert_eq!(header.components, vec![
+      ScanComponent { id: 1, dc_table: 0, ac_table: 0 },
+      ScanComponent { id: 2, dc_table: 1, ac_table: 1 },
+      ScanComponent { id: 3, dc_table: 1, ac_table: 1 }
+    ]);
+    assert_eq!((header.spectral_start, header.spectral_end), (0, 63));
+    assert_eq!((header.approximation_high, header.approximation_low), (0, 0));
+
+    //a progressive AC refinement scan of the first component
+    let progressive = b"\x01\x01\x01\x01\x3F\x21";
+    let header = read_scan_header(&Cursor::new(progressive, Endianness::Big)).unwrap();
+    assert_eq!(header.components, vec![ScanComponent { id: 1, dc_table: 0, ac_table: 1 }]);
+    assert_eq!((header.spectral_start, header.spectral_end), (1, 63));
+    assert_eq!((header.approximation_high, header.approximation_low), (2, 1));
+
+    let truncated = Cursor::new(&progressive[.. 5], Endianness::Big);
+    assert!(read_scan_header(&truncated).is_err());
+  }
 }
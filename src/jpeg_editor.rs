@@ -0,0 +1,246 @@
+use ::cursor::{Cursor, Endianness};
+use ::jpeg::{JPEGSegmentIterator, SegmentMarker};
+use ::error::{ParseError, ParseResult};
+
+/** the length field of a segment includes itself */
+const MAX_SEGMENT_PAYLOAD : usize = 0xFFFF - 2;
+
+enum SegmentData<'a> {
+  /** the segment as in the original file, including marker and length */
+  Original(&'a [u8]),
+  New(Vec<u8>)
+}
+
+pub struct EditorSegment<'a> {
+  marker: SegmentMarker,
+  payload_range: (usize, usize),
+  data: SegmentData<'a>
+}
+
+impl<'a> EditorSegment<'a> {
+  pub fn marker(&self) -> SegmentMarker {
+    self.marker
+  }
+
+  pub fn payload(&self) -> &[u8] {
+    match self.data {
+      SegmentData::Original(bytes) => &bytes[self.payload_range.0 .. self.payload_range.1],
+      SegmentData::New(ref payload) => payload
+    }
+  }
+
+  pub fn is_modified(&self) -> bool {
+    match self.data {
+      SegmentData::Original(_) => false,
+      SegmentData::New(_) => true
+    }
+  }
+
+  pub fn is_exif(&self) -> bool {
+    self.marker == SegmentMarker::APP(1) && self.payload().starts_with(b"Exif\0\0")
+  }
+}
+
+/** Re-emits the segment stream of a JPEG file with some segments replaced,
+    inserted or removed. Untouched segments are copied byte for byte, as is
+    everything from the SOS marker on (the entropy-coded image data and
+    whatever follows EOI). */
+pub struct JPEGEditor<'a> {
+  segments: Vec<EditorSegment<'a>>,
+  scan: &'a [u8]
+}
+
+impl<'a> JPEGEditor<'a> {
+  pub fn new(data: &'a [u8]) -> ParseResult<JPEGEditor<'a>> {
+    let file_cursor = Cursor::new(data, Endianness::Big);
+    let mut segments = Vec::new();
+
+    for segment in JPEGSegmentIterator::new(file_cursor) {
+      let (marker, cursor) = segment?;
+      let offset = cursor.offset_from(&file_cursor);
+      let header_len = if marker.has_size() { 4 } else { 2 };
+
+      if marker == SegmentMarker::SOS {
+        return Ok(JPEGEditor {
+          segments,
+          scan: &data[offset - header_len ..]
+        });
+      }
+      //SOI has no data, it is written by `write`
+      if marker == SegmentMarker::SOI {
+        continue;
+      }
+
+      let start = offset - header_len;
+      let end = offset + cursor.len();
+      segments.push(EditorSegment {
+        marker,
+        payload_range: (header_len, end - start),
+        data: SegmentData::Original(&data[start .. end])
+      });
+    }
+    //the iterator only ends without error after SOS
+    Err(ParseError::UnexpectedEOF)
+  }
+
+  pub fn segments(&self) -> &[EditorSegment<'a>] {
+    &self.segments
+  }
+
+  /** keeps only the segments for which `keep` returns true */
+  pub fn retain<F>(&mut self, mut keep: F) where F: FnMut(&EditorSegment<'a>) -> bool {
+    self.segments.retain(|s| keep(s));
+  }
+
+  pub fn insert_segment(&mut self, index: usize, marker: SegmentMarker, payload: Vec<u8>) {
+    let len = payload.len();
+    self.segments.insert(index, EditorSegment {
+      marker,
+      payload_range: (0, len),
+      data: SegmentData::New(payload)
+    });
+  }
+
+  pub fn replace_segment(&mut self, index: usize, payload: Vec<u8>) {
+    let segment = &mut self.segments[index];
+    segment.payload_range = (0, payload.len());
+    segment.data = SegmentData::New(payload);
+  }
+
+  /** Replaces the Exif APP1 segment with `app1_payload`, as written by
+      `ExifWriter::write_app1`. If there is no Exif segment yet, it is inserted
+      at the start, after a JFIF APP0 segment if there is one. */
+  pub fn set_exif(&mut self, app1_payload: Vec<u8>) {
+    if let Some(index) = self.segments.iter().position(|s| s.is_exif()) {
+      self.replace_segment(index, app1_payload);
+      return;
+    }
+    let index = match self.segments.first() {
+      Some(s) if s.marker == SegmentMarker::APP(0) => 1,
+      _ => 0
+    };
+    self.insert_segment(index, SegmentMarker::APP(1), app1_payload);
+  }
+
+  pub fn remove_exif(&mut self) {
+    self.segments.retain(|s| !s.is_exif());
+  }
+
+  pub fn write(&self) -> ParseResult<Vec<u8>> {
+    let size = self.segments.iter().map(|s| s.payload().len() + 4).sum::<usize>();
+    let mut out = Vec::with_capacity(2 + size + self.scan.len());
+    out.extend_from_slice(&[0xFF, u8::from(SegmentMarker::SOI)]);
+
+    for segment in self.segments.iter() {
+      match segment.data {
+        SegmentData::Original(bytes) => out.extend_from_slice(bytes),
+        SegmentData::New(ref payload) => {
+          out.extend_from_slice(&[0xFF, u8::from(segment.marker)]);
+          if segment.marker.has_size() {
+            if payload.len() > MAX_SEGMENT_PAYLOAD {
+              return Err(ParseError::SegmentTooLarge { len: payload.len() });
+            }
+            let len = (payload.len() + 2) as u16;
+            out.extend_from_slice(&len.to_be_bytes());
+          }
+          out.extend_from_slice(payload);
+        }
+      }
+    }
+
+    out.extend_from_slice(self.scan);
+    Ok(out)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::jpeg::{JPEGSegmentIterator, SegmentMarker};
+  use ::tags::{read_tags, Section};
+  use ::writer::{ExifWriter, ExifValue};
+  use ::error::ParseError;
+  use ::test_fixtures::{JPEG_SAMPLE, JPEG_SAMPLE_EXIF_OFFSET, expected_segments};
+  use super::JPEGEditor;
+
+  fn markers(data: &[u8]) -> Vec<SegmentMarker> {
+    JPEGSegmentIterator::new(Cursor::new(data, Endianness::Big))
+      .map(|r| r.unwrap().0)
+      .collect()
+  }
+
+  #[test]
+  fn test_unmodified_is_identical() {
+    let editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    assert_eq!(editor.segments().len(), expected_segments().len() - 2);
+    assert_eq!(editor.write().unwrap(), JPEG_SAMPLE.to_vec());
+  }
+
+  #[test]
+  fn test_replace_exif() {
+    let cursor = Cursor::new(JPEG_SAMPLE, Endianness::Big)
+      .with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).unwrap();
+    let mut writer = ExifWriter::new(Endianness::Big);
+    for result in read_tags(cursor).unwrap() {
+      let (tag, section) = result.unwrap();
+      writer.add_tag(section, tag.no, ExifValue::from_variant(&tag.value));
+    }
+    writer.add_tag(Section::SubIFD, 0x9003,
+      ExifValue::Text("2014:06:11 16:22:36".to_string()));
+
+    let mut editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    editor.set_exif(writer.write_app1());
+    let edited = editor.write().unwrap();
+
+    let expected_markers : Vec<SegmentMarker> = expected_segments().iter().map(|s| s.marker).collect();
+    assert_eq!(markers(&edited), expected_markers);
+
+    let exif_start = 4 + 16 + 4;
+    let cursor = Cursor::new(&edited, Endianness::Big).with_skip_or_fail(exif_start).unwrap();
+    let date = read_tags(cursor).unwrap()
+      .map(|r| r.unwrap())
+      .find(|&(ref tag, section)| section == Section::SubIFD && tag.no == 0x9003)
+      .map(|(tag, _)| tag.value.to_string());
+    assert_eq!(date, Some("2014:06:11 16:22:36".to_string()));
+
+    //everything from the XMP segment on is unchanged
+    let xmp_start = expected_segments()[3].offset - 4;
+    let edited_xmp_start = edited.len() - (JPEG_SAMPLE.len() - xmp_start);
+    assert_eq!(&edited[edited_xmp_start ..], &JPEG_SAMPLE[xmp_start ..]);
+  }
+
+  #[test]
+  fn test_insert_exif_after_jfif() {
+    let mut editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    editor.remove_exif();
+    assert!(!editor.segments().iter().any(|s| s.is_exif()));
+    assert_eq!(markers(&editor.write().unwrap()).len(), expected_segments().len() - 1);
+
+    let mut writer = ExifWriter::new(Endianness::Little);
+    writer.add_tag(Section::IFD0, 0x0112, ExifValue::UShort(vec![1]));
+    editor.set_exif(writer.write_app1());
+    let edited = editor.write().unwrap();
+    let edited_markers = markers(&edited);
+    assert_eq!(&edited_markers[0 .. 4], &[
+      SegmentMarker::SOI,
+      SegmentMarker::APP(0),
+      SegmentMarker::APP(1),
+      SegmentMarker::APP(1)
+    ]);
+    assert!(editor.segments()[1].is_exif());
+    assert!(editor.segments()[1].is_modified());
+    assert!(!editor.segments()[2].is_exif());
+  }
+
+  #[test]
+  fn test_segment_too_large() {
+    let mut editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    editor.set_exif(vec![0u8; 0x10000]);
+    assert_eq!(editor.write(), Err(ParseError::SegmentTooLarge { len: 0x10000 }));
+  }
+
+  #[test]
+  fn test_truncated_file() {
+    assert!(JPEGEditor::new(&JPEG_SAMPLE[0 .. 100]).is_err());
+  }
+}
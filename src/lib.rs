@@ -1,14 +1,34 @@
 mod cursor;
 mod error;
 mod jpeg;
+mod jpeg_editor;
 mod component;
 mod tag;
 mod section; 
 mod tags;
+mod names;
+mod format;
+mod composite;
+mod writer;
+mod scrub;
+mod patch;
+mod makernote;
+mod md5;
+mod xml;
+mod xmp;
+mod photoshop;
+mod iptc;
+mod icc;
+mod jfif;
+mod adobe;
+mod mpf;
+mod dqt;
+mod dht;
 mod value_types;
 #[cfg(test)]
 mod test_fixtures;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Size {
 	pub height: u16,
 	pub width: u16
@@ -16,3 +36,77 @@ pub struct Size {
 
 pub use cursor::{Cursor, Endianness};
 pub use error::{ParseError, ParseResult};
+pub use jpeg::{JPEGSegmentIterator, SegmentMarker, read_image_size_from_sof};
+pub use jpeg::{FrameHeader, FrameComponent, CodingProcess, ChromaSubsampling, read_frame_header};
+pub use jpeg::{ScanHeader, ScanComponent, read_scan_header, read_restart_interval};
+pub use jpeg_editor::{JPEGEditor, EditorSegment};
+pub use tags::{Section, ExifTagIterator, read_tags};
+pub use names::tag_name;
+pub use tag::{RawExifTag, TagSpan, ExifVariant, ExifFormat};
+pub use component::{TagComponents, ComponentIterator};
+pub use format::{FormattedTag, format_tag, format_gps_coordinate};
+pub use composite::{CompositeTags, DerivedValue};
+pub use writer::{ExifWriter, ExifValue};
+pub use scrub::{ScrubPolicy, ScrubResult, Removed, scrub};
+pub use patch::{TagPatch, apply_patches};
+pub use makernote::{Vendor, MakerNote, MakerNoteIterator, read_maker_note};
+pub use makernote::{CanonMakerNote, CanonArray};
+pub use makernote::{NikonMakerNote, NikonLensData, NikonVRInfo, NikonShotInfo, nikon_decrypt};
+pub use makernote::{SonyMakerNote, sony_decipher};
+pub use makernote::{FujifilmMakerNote, film_simulation_name};
+pub use makernote::{OlympusMakerNote, OlympusEquipment, OlympusCameraSettings};
+pub use makernote::PanasonicMakerNote;
+pub use makernote::{AppleMakerNote, AppleRunTime, PlistValue, parse_bplist};
+pub use xmp::{Xmp, XmpValue, XmpProperty, read_xmp_packet, read_xmp, XMP_SEGMENT_HEADER};
+pub use xmp::{ExtendedXmpChunk, read_extended_xmp_chunk, assemble_extended_xmp, EXTENDED_XMP_SEGMENT_HEADER};
+pub use xmp::{NS_RDF, NS_XMP, NS_EXIF, NS_DC, NS_PHOTOSHOP, NS_LIGHTROOM, NS_XMP_NOTE};
+pub use photoshop::{ImageResource, ImageResourceIterator, read_image_resources};
+pub use photoshop::{PHOTOSHOP_SEGMENT_HEADER, IPTC_RESOURCE_ID, IPTC_DIGEST_RESOURCE_ID};
+pub use iptc::{Iptc, IptcDataSet, IptcIterator, read_iptc, read_iptc_datasets, iptc_dataset_name};
+pub use icc::{IccChunk, read_icc_chunk, assemble_icc_profile, read_icc_profile, ICC_SEGMENT_HEADER};
+pub use icc::{IccProfile, IccTag, IccDeviceClass, IccColorSpace, RgbProfile};
+pub use jfif::{Jfif, JfifThumbnail, DensityUnit, read_jfif, read_jfxx_thumbnail, JFIF_SEGMENT_HEADER, JFXX_SEGMENT_HEADER};
+pub use adobe::{AdobeApp14, JpegColorSpace, read_adobe_app14, read_color_space, ADOBE_SEGMENT_HEADER};
+pub use mpf::{MpIndex, MpEntry, MpImageType, read_mp_index, read_mpf, MPF_SEGMENT_HEADER};
+pub use dqt::{QuantizationTable, QualityEstimate, read_quantization_tables, estimate_quality, read_quality};
+pub use dht::{HuffmanTable, HuffmanClass, read_huffman_tables};
+pub use value_types::{
+  Version,
+  Channel,
+  ComponentsConfiguration,
+  FileSource,
+  SceneType,
+  CfaColor,
+  CfaPattern,
+  RationalTable,
+  DeviceSettings,
+  UndefinedValue,
+  decode_undefined
+};
+pub use value_types::{
+  Orientation,
+  Compression,
+  ResolutionUnit,
+  FocalPlaneResolutionUnit,
+  YCbCrPositioning,
+  ExposureProgram,
+  MeteringMode,
+  LightSource,
+  ColorSpace,
+  SensingMethod,
+  CustomRendered,
+  ExposureMode,
+  WhiteBalance,
+  SceneCaptureType,
+  GainControl,
+  Contrast,
+  Saturation,
+  Sharpness,
+  SubjectDistanceRange,
+  GpsAltitudeRef,
+  FlashReturn,
+  FlashMode,
+  Flash,
+  EnumeratedValue,
+  decode_enumerated
+};
@@ -0,0 +1,185 @@
+use ::error::ParseResult;
+use ::tag::ExifVariant;
+use super::{MakerNote, text, bytes};
+use super::bplist::parse_bplist;
+
+pub const APPLE_TAG_NAMES : &[(u16, &str)] = &[
+  (0x0001, "MakerNoteVersion"),
+  (0x0002, "AEMatrix"),
+  (0x0003, "RunTime"),
+  (0x0004, "AEStable"),
+  (0x0005, "AETarget"),
+  (0x0006, "AEAverage"),
+  (0x0007, "AFStable"),
+  (0x0008, "AccelerationVector"),
+  (0x000A, "HDRImageType"),
+  (0x000B, "BurstUUID"),
+  (0x000C, "FocusDistanceRange"),
+  (0x000F, "OISMode"),
+  (0x0011, "ContentIdentifier"),
+  (0x0014, "ImageCaptureType"),
+  (0x0015, "ImageUniqueID"),
+  (0x0017, "LivePhotoVideoIndex"),
+  (0x001F, "PhotosAppFeatureFlags"),
+  (0x0020, "ImageCaptureRequestID"),
+  (0x0021, "HDRHeadroom"),
+  (0x0023, "AFPerformance"),
+  (0x0025, "SceneFlags"),
+  (0x0026, "SignalToNoiseRatioType"),
+  (0x0027, "SignalToNoiseRatio"),
+  (0x002B, "PhotoIdentifier"),
+  (0x002D, "ColorTemperature"),
+  (0x002E, "CameraType"),
+  (0x002F, "FocusPosition"),
+  (0x0030, "HDRGain"),
+  (0x0038, "AFMeasuredDepth"),
+  (0x003D, "AFConfidence"),
+  (0x0040, "SemanticStyle")
+];
+
+/** The RunTime tag: the time since the device booted when the photo was taken,
+    as a CMTime of `value / timescale` seconds */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AppleRunTime {
+  pub flags: i64,
+  pub value: i64,
+  pub timescale: i64,
+  pub epoch: i64
+}
+
+impl AppleRunTime {
+  pub fn seconds(&self) -> Option<f64> {
+    if self.timescale == 0 {
+      None
+    } else {
+      Some(self.value as f64 / self.timescale as f64)
+    }
+  }
+}
+
+/** The fields of an Apple MakerNote, as written by iPhones and iPads */
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AppleMakerNote {
+  pub version: Option<i32>,
+  pub run_time: Option<AppleRunTime>,
+  /** the direction of gravity relative to the device, in g */
+  pub acceleration_vector: Option<[f64; 3]>,
+  /** 3 for the HDR image, 4 for the original image kept next to it */
+  pub hdr_image_type: Option<u32>,
+  /** shared by all images of a burst */
+  pub burst_uuid: Option<String>,
+  /** shared with the video of a Live Photo,
+      which stores it as `com.apple.quicktime.content.identifier` */
+  pub content_identifier: Option<String>,
+  pub image_unique_id: Option<String>,
+  pub photo_identifier: Option<String>
+}
+
+impl AppleMakerNote {
+  pub fn parse(maker_note: &MakerNote) -> ParseResult<AppleMakerNote> {
+    let mut apple = AppleMakerNote::default();
+    for result in maker_note.iter()? {
+      let (tag, _) = result?;
+      let value = &tag.value;
+      match tag.no {
+        0x0001 => apple.version = match *value {
+          ExifVariant::Int(ref c) => c.iter().next(),
+          _ => value.as_u32().map(|n| n as i32)
+        },
+        //a RunTime that can't be decoded is skipped, like any other unexpected value
+        0x0003 => apple.run_time = bytes(value)
+          .and_then(|data| parse_bplist(data).ok())
+          .and_then(|plist| Some(AppleRunTime {
+            flags: plist.get("flags")?.as_i64()?,
+            value: plist.get("value")?.as_i64()?,
+            timescale: plist.get("timescale")?.as_i64()?,
+            epoch: plist.get("epoch").and_then(|e| e.as_i64()).unwrap_or(0)
+          })),
+        0x0008 => apple.acceleration_vector = match *value {
+          ExifVariant::IntFraction(ref c) => {
+            let v : Vec<f64> = c.iter()
+              .map(|(n, d)| if d == 0 { 0.0 } else { n as f64 / d as f64 })
+              .collect();
+            if v.len() == 3 { Some([v[0], v[1], v[2]]) } else { None }
+          },
+          _ => None
+        },
+        0x000A => apple.hdr_image_type = match *value {
+          ExifVariant::Int(ref c) => c.iter().next().map(|n| n as u32),
+          _ => value.as_u32()
+        },
+        0x000B => apple.burst_uuid = text(value),
+        0x0011 => apple.content_identifier = text(value),
+        0x0015 => apple.image_unique_id = text(value),
+        0x002B => apple.photo_identifier = text(value),
+        _ => ()
+      }
+    }
+    Ok(apple)
+  }
+
+  pub fn is_hdr(&self) -> bool {
+    self.hdr_image_type == Some(3)
+  }
+
+  pub fn is_burst(&self) -> bool {
+    self.burst_uuid.is_some()
+  }
+
+  /** whether this image and another one were taken as part of the same Live Photo */
+  pub fn is_live_photo_pair(&self, video_content_identifier: &str) -> bool {
+    self.content_identifier.as_ref().is_some_and(|id| id == video_content_identifier)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::tags::Section;
+  use ::writer::{ExifWriter, ExifValue};
+  use ::makernote::{read_maker_note, Vendor};
+  use ::makernote::test_support::{write_ifd, int_dict_bplist};
+  use super::{AppleMakerNote, AppleRunTime};
+
+  #[test]
+  fn test_parse_apple() {
+    let run_time = int_dict_bplist(&[
+      ("flags", 1),
+      ("value", 184_467_440_737),
+      ("timescale", 1_000_000_000),
+      ("epoch", 0)
+    ]);
+    let mut maker_note = b"Apple iOS\0\0\x01MM".to_vec();
+    maker_note.extend(write_ifd(&[
+      (0x0001, ExifValue::Int(vec![14])),
+      (0x0003, ExifValue::Undefined(run_time)),
+      (0x0008, ExifValue::IntFraction(vec![(-1, 100), (-98, 100), (2, 10)])),
+      (0x000A, ExifValue::Int(vec![3])),
+      (0x000B, ExifValue::Text("D4C5F1AE-2D4B-4E4B-8C9A-1A2B3C4D5E6F".to_string())),
+      (0x0011, ExifValue::Text("8A1B7F1E-3C2D-4E5F-9A8B-7C6D5E4F3A2B".to_string()))
+    ], 14, Endianness::Big));
+
+    //Apple MakerNotes stay big endian in little endian Exif data
+    let mut writer = ExifWriter::new(Endianness::Little);
+    writer.add_tag(Section::IFD0, 0x010F, ExifValue::Text("Apple".to_string()));
+    writer.add_tag(Section::SubIFD, 0x927C, ExifValue::Undefined(maker_note));
+    let app1 = writer.write_app1();
+    let maker_note = read_maker_note(Cursor::new(&app1, Endianness::Little)).unwrap().unwrap();
+    assert_eq!(maker_note.vendor, Vendor::Apple);
+
+    let apple = AppleMakerNote::parse(&maker_note).unwrap();
+    assert_eq!(apple.version, Some(14));
+    assert_eq!(apple.run_time, Some(AppleRunTime {
+      flags: 1,
+      value: 184_467_440_737,
+      timescale: 1_000_000_000,
+      epoch: 0
+    }));
+    assert_eq!(apple.run_time.unwrap().seconds(), Some(184.467440737));
+    assert_eq!(apple.acceleration_vector, Some([-0.01, -0.98, 0.2]));
+    assert!(apple.is_hdr());
+    assert!(apple.is_burst());
+    assert!(apple.is_live_photo_pair("8A1B7F1E-3C2D-4E5F-9A8B-7C6D5E4F3A2B"));
+    assert!(!apple.is_live_photo_pair("D4C5F1AE-2D4B-4E4B-8C9A-1A2B3C4D5E6F"));
+  }
+}
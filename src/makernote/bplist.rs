@@ -0,0 +1,286 @@
+use ::cursor::{Cursor, Endianness};
+use ::error::{ParseError, ParseResult};
+
+const TRAILER_SIZE : usize = 32;
+//containers can refer to each other, which limits how deep they can nest
+const MAX_DEPTH : usize = 32;
+//objects can be referred to more than once, which limits how often, so shared
+//references can't expand a small plist into an exponential number of values
+const MAX_OBJECTS_PER_OFFSET : usize = 8;
+
+/** A value of a binary property list, as far as MakerNotes use them */
+#[derive(Debug, PartialEq, Clone)]
+pub enum PlistValue {
+  Bool(bool),
+  Int(i64),
+  Real(f64),
+  /** seconds since 2001-01-01 00:00:00 UTC */
+  Date(f64),
+  Data(Vec<u8>),
+  String(String),
+  Uid(u64),
+  Array(Vec<PlistValue>),
+  /** the entries in the order they are stored */
+  Dict(Vec<(String, PlistValue)>)
+}
+
+impl PlistValue {
+  /** the value of a key, if this is a dictionary */
+  pub fn get(&self, key: &str) -> Option<&PlistValue> {
+    match *self {
+      PlistValue::Dict(ref entries) => entries.iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v),
+      _ => None
+    }
+  }
+
+  pub fn as_i64(&self) -> Option<i64> {
+    match *self {
+      PlistValue::Int(n) => Some(n),
+      _ => None
+    }
+  }
+
+  pub fn as_f64(&self) -> Option<f64> {
+    match *self {
+      PlistValue::Int(n) => Some(n as f64),
+      PlistValue::Real(n) | PlistValue::Date(n) => Some(n),
+      _ => None
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match *self {
+      PlistValue::String(ref s) => Some(s),
+      _ => None
+    }
+  }
+}
+
+struct Plist<'a> {
+  data: &'a [u8],
+  offsets: Vec<usize>,
+  ref_size: usize,
+  /** the objects containing the one being decoded, to detect cycles */
+  path: Vec<u64>,
+  decoded: usize
+}
+
+/** reads a big endian unsigned integer of 1 to 8 bytes */
+fn read_uint(cursor: &mut Cursor, size: usize) -> ParseResult<u64> {
+  if size == 0 || size > 8 {
+    return Err(ParseError::InvalidPlist { offset: 0 });
+  }
+  let bytes = cursor.read_bytes_or_fail(size)?;
+  Ok(bytes.iter().fold(0u64, |n, b| n << 8 | *b as u64))
+}
+
+impl<'a> Plist<'a> {
+  fn object(&mut self, index: u64) -> ParseResult<PlistValue> {
+    let offset = *self.offsets.get(index as usize)
+      .ok_or(ParseError::InvalidPlist { offset: 0 })?;
+    self.decoded += 1;
+    let too_many = self.decoded > self.offsets.len() * MAX_OBJECTS_PER_OFFSET;
+    if too_many || self.path.len() > MAX_DEPTH || self.path.contains(&index) {
+      return Err(ParseError::InvalidPlist { offset });
+    }
+    self.path.push(index);
+    let value = self.decode(offset);
+    self.path.pop();
+    value
+  }
+
+  fn decode(&mut self, offset: usize) -> ParseResult<PlistValue> {
+    let invalid = ParseError::InvalidPlist { offset };
+    let mut cursor = Cursor::new(self.data, Endianness::Big).with_skip_or_fail(offset)?;
+    let marker : u8 = cursor.read_num_or_fail()?;
+    let info = (marker & 0x0F) as usize;
+
+    let value = match marker >> 4 {
+      0x0 => match marker {
+        0x08 => PlistValue::Bool(false),
+        0x09 => PlistValue::Bool(true),
+        _ => return Err(invalid)
+      },
+      0x1 => {
+        let size = 1 << info;
+        let n = read_uint(&mut cursor, size)?;
+        //only 8 byte integers are signed
+        PlistValue::Int(n as i64)
+      },
+      0x2 => match info {
+        2 => PlistValue::Real(f32::from_bits(cursor.read_num_or_fail()?) as f64),
+        3 => PlistValue::Real(f64::from_bits(cursor.read_num_or_fail()?)),
+        _ => return Err(invalid)
+      },
+      0x3 if info == 3 => PlistValue::Date(f64::from_bits(cursor.read_num_or_fail()?)),
+      0x4 => {
+        let len = self.count(&mut cursor, info)?;
+        PlistValue::Data(cursor.read_bytes_or_fail(len)?.to_vec())
+      },
+      0x5 => {
+        let len = self.count(&mut cursor, info)?;
+        let bytes = cursor.read_bytes_or_fail(len)?;
+        PlistValue::String(String::from_utf8_lossy(bytes).into_owned())
+      },
+      0x6 => {
+        let len = self.count(&mut cursor, info)?;
+        //the length is untrusted, so it doesn't size the vector beyond the data
+        let mut units = Vec::with_capacity(len.min(cursor.len() / 2));
+        for _ in 0 .. len {
+          units.push(cursor.read_num_or_fail::<u16>()?);
+        }
+        PlistValue::String(String::from_utf16_lossy(&units))
+      },
+      0x8 => PlistValue::Uid(read_uint(&mut cursor, info + 1)?),
+      0xA => {
+        let len = self.count(&mut cursor, info)?;
+        let mut items = Vec::new();
+        for _ in 0 .. len {
+          let index = read_uint(&mut cursor, self.ref_size)?;
+          items.push(self.object(index)?);
+        }
+        PlistValue::Array(items)
+      },
+      0xD => {
+        let len = self.count(&mut cursor, info)?;
+        let mut keys = Vec::new();
+        for _ in 0 .. len {
+          keys.push(read_uint(&mut cursor, self.ref_size)?);
+        }
+        let mut entries = Vec::new();
+        for key in keys {
+          let key = match self.object(key)? {
+            PlistValue::String(key) => key,
+            _ => return Err(invalid)
+          };
+          let index = read_uint(&mut cursor, self.ref_size)?;
+          entries.push((key, self.object(index)?));
+        }
+        PlistValue::Dict(entries)
+      },
+      _ => return Err(invalid)
+    };
+    Ok(value)
+  }
+
+  /** the number of items of a container, which is stored in an int object after the marker if it is 15 or more */
+  fn count(&self, cursor: &mut Cursor, info: usize) -> ParseResult<usize> {
+    if info != 0x0F {
+      return Ok(info);
+    }
+    let marker : u8 = cursor.read_num_or_fail()?;
+    if marker >> 4 != 0x1 {
+      return Err(ParseError::InvalidPlist { offset: 0 });
+    }
+    Ok(read_uint(cursor, 1 << (marker & 0x0F))? as usize)
+  }
+}
+
+/** Parses a binary property list (`bplist00`), returning its top object */
+pub fn parse_bplist(data: &[u8]) -> ParseResult<PlistValue> {
+  if !data.starts_with(b"bplist00") || data.len() < 8 + TRAILER_SIZE {
+    return Err(ParseError::InvalidPlist { offset: 0 });
+  }
+  let mut trailer = Cursor::new(data, Endianness::Big)
+    .with_skip_or_fail(data.len() - TRAILER_SIZE + 6)?;
+  let offset_size : u8 = trailer.read_num_or_fail()?;
+  let ref_size : u8 = trailer.read_num_or_fail()?;
+  let object_count : u64 = trailer.read_num_or_fail()?;
+  let top_object : u64 = trailer.read_num_or_fail()?;
+  let offset_table : u64 = trailer.read_num_or_fail()?;
+  if !(1 ..= 8).contains(&offset_size) || !(1 ..= 8).contains(&ref_size) {
+    return Err(ParseError::InvalidPlist { offset: data.len() - TRAILER_SIZE + 6 });
+  }
+
+  let table_len = (object_count as usize).checked_mul(offset_size as usize);
+  let table_fits = table_len.and_then(|len| len.checked_add(offset_table as usize))
+    .is_some_and(|end| end <= data.len() - TRAILER_SIZE);
+  if !table_fits {
+    return Err(ParseError::InvalidPlist { offset: offset_table as usize });
+  }
+  let mut table = Cursor::new(data, Endianness::Big).with_skip_or_fail(offset_table as usize)?;
+  let mut offsets = Vec::new();
+  for _ in 0 .. object_count {
+    offsets.push(read_uint(&mut table, offset_size as usize)? as usize);
+  }
+
+  let mut plist = Plist { data, offsets, ref_size: ref_size as usize, path: Vec::new(), decoded: 0 };
+  plist.object(top_object)
+}
+
+#[cfg(test)]
+mod tests {
+  use ::error::ParseError;
+  use ::makernote::test_support::int_dict_bplist;
+  use super::{parse_bplist, PlistValue};
+
+
+  #[test]
+  fn test_parse_dict() {
+    let data = int_dict_bplist(&[("flags", 1), ("value", -5), ("timescale", 1_000_000_000)]);
+    let plist = parse_bplist(&data).unwrap();
+    assert_eq!(plist.get("flags"), Some(&PlistValue::Int(1)));
+    assert_eq!(plist.get("value").and_then(|v| v.as_i64()), Some(-5));
+    assert_eq!(plist.get("timescale").and_then(|v| v.as_f64()), Some(1e9));
+    assert_eq!(plist.get("epoch"), None);
+  }
+
+  #[test]
+  fn test_self_referencing_array() {
+    //an array containing itself
+    let mut data = b"bplist00".to_vec();
+    data.extend_from_slice(&[0xA1, 0x00]);
+    data.push(8);
+    data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 1, 1]);
+    data.extend_from_slice(&1u64.to_be_bytes());
+    data.extend_from_slice(&0u64.to_be_bytes());
+    data.extend_from_slice(&10u64.to_be_bytes());
+    assert_eq!(parse_bplist(&data), Err(ParseError::InvalidPlist { offset: 8 }));
+  }
+
+  fn bplist_with_trailer(objects: &[u8], offsets: &[u8], offset_size: u8, object_count: u64) -> Vec<u8> {
+    let mut data = b"bplist00".to_vec();
+    data.extend_from_slice(objects);
+    let table_offset = data.len() as u64;
+    data.extend_from_slice(offsets);
+    data.extend_from_slice(&[0, 0, 0, 0, 0, 0, offset_size, 1]);
+    data.extend_from_slice(&object_count.to_be_bytes());
+    data.extend_from_slice(&0u64.to_be_bytes());
+    data.extend_from_slice(&table_offset.to_be_bytes());
+    data
+  }
+
+  #[test]
+  fn test_shared_references() {
+    //26 arrays each referring to the next one twice, which would expand to 2^26 values
+    let mut objects = Vec::new();
+    let mut offsets = Vec::new();
+    for i in 0 .. 26u8 {
+      offsets.push(8 + objects.len() as u8);
+      if i == 25 {
+        objects.push(0x08);
+      } else {
+        objects.extend_from_slice(&[0xA2, i + 1, i + 1]);
+      }
+    }
+    let data = bplist_with_trailer(&objects, &offsets, 1, offsets.len() as u64);
+    assert!(parse_bplist(&data).is_err());
+  }
+
+  #[test]
+  fn test_untrusted_sizes() {
+    //no offset table, but a huge object count
+    let data = bplist_with_trailer(&[0x08], &[], 0, u64::MAX);
+    assert!(parse_bplist(&data).is_err());
+    //a UTF-16 string claiming far more units than there are bytes
+    let data = bplist_with_trailer(&[0x6F, 0x13, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], &[8], 1, 1);
+    assert_eq!(parse_bplist(&data), Err(ParseError::UnexpectedEOF));
+  }
+
+  #[test]
+  fn test_invalid_header() {
+    assert_eq!(parse_bplist(b"bplist15"), Err(ParseError::InvalidPlist { offset: 0 }));
+  }
+}
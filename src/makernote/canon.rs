@@ -0,0 +1,288 @@
+use ::error::ParseResult;
+use ::tag::ExifVariant;
+use super::{MakerNote, text};
+
+pub const CANON_TAG_NAMES : &[(u16, &str)] = &[
+  (0x0001, "CanonCameraSettings"),
+  (0x0002, "CanonFocalLength"),
+  (0x0004, "CanonShotInfo"),
+  (0x0006, "CanonImageType"),
+  (0x0007, "CanonFirmwareVersion"),
+  (0x0008, "FileNumber"),
+  (0x0009, "OwnerName"),
+  (0x000C, "SerialNumber"),
+  (0x0010, "CanonModelID"),
+  (0x0028, "ImageUniqueID"),
+  (0x0093, "CanonFileInfo"),
+  (0x0095, "LensModel"),
+  (0x0096, "InternalSerialNumber")
+];
+
+const CAMERA_SETTINGS_FIELDS : &[(usize, &str)] = &[
+  (1, "MacroMode"),
+  (2, "SelfTimer"),
+  (3, "Quality"),
+  (4, "CanonFlashMode"),
+  (5, "ContinuousDrive"),
+  (7, "FocusMode"),
+  (9, "RecordMode"),
+  (10, "CanonImageSize"),
+  (11, "EasyMode"),
+  (12, "DigitalZoom"),
+  (13, "Contrast"),
+  (14, "Saturation"),
+  (15, "Sharpness"),
+  (16, "CameraISO"),
+  (17, "MeteringMode"),
+  (18, "FocusRange"),
+  (19, "AFPoint"),
+  (20, "CanonExposureMode"),
+  (22, "LensType"),
+  (23, "MaxFocalLength"),
+  (24, "MinFocalLength"),
+  (25, "FocalUnits"),
+  (26, "MaxAperture"),
+  (27, "MinAperture"),
+  (28, "FlashActivity"),
+  (29, "FlashBits"),
+  (32, "FocusContinuous"),
+  (33, "AESetting"),
+  (34, "ImageStabilization"),
+  (35, "DisplayAperture"),
+  (36, "ZoomSourceWidth"),
+  (37, "ZoomTargetWidth"),
+  (39, "SpotMeteringMode"),
+  (40, "PhotoEffect"),
+  (41, "ManualFlashOutput"),
+  (42, "ColorTone"),
+  (46, "SRAWQuality")
+];
+
+const SHOT_INFO_FIELDS : &[(usize, &str)] = &[
+  (1, "AutoISO"),
+  (2, "BaseISO"),
+  (3, "MeasuredEV"),
+  (4, "TargetAperture"),
+  (5, "TargetExposureTime"),
+  (6, "ExposureCompensation"),
+  (7, "WhiteBalance"),
+  (8, "SlowShutter"),
+  (9, "SequenceNumber"),
+  (10, "OpticalZoomCode"),
+  (12, "CameraTemperature"),
+  (13, "FlashGuideNumber"),
+  (14, "AFPointsInFocus"),
+  (15, "FlashExposureComp"),
+  (16, "AutoExposureBracketing"),
+  (17, "AEBBracketValue"),
+  (18, "ControlMode"),
+  (19, "FocusDistanceUpper"),
+  (20, "FocusDistanceLower"),
+  (21, "FNumber"),
+  (22, "ExposureTime"),
+  (23, "MeasuredEV2"),
+  (24, "BulbDuration"),
+  (26, "CameraType"),
+  (27, "AutoRotate"),
+  (28, "NDFilter"),
+  (29, "SelfTimer2"),
+  (33, "FlashOutput")
+];
+
+const FILE_INFO_FIELDS : &[(usize, &str)] = &[
+  (1, "FileNumber"),
+  (3, "BracketMode"),
+  (4, "BracketValue"),
+  (5, "BracketShotNumber"),
+  (6, "RawJpgQuality"),
+  (7, "RawJpgSize"),
+  (8, "LongExposureNoiseReduction2"),
+  (9, "WBBracketMode"),
+  (12, "WBBracketValueAB"),
+  (13, "WBBracketValueGM"),
+  (14, "FilterEffect"),
+  (15, "ToningEffect"),
+  (16, "MacroMagnification"),
+  (19, "LiveViewShooting"),
+  (20, "FocusDistanceUpper"),
+  (21, "FocusDistanceLower"),
+  (25, "FlashExposureLock")
+];
+
+/** One of the Canon tags that pack many settings into an array of 16 bit values.
+    The first value is the size of the array in bytes, the fields start at index 1.
+    Values are kept raw, most of them are codes or APEX values scaled by 32. */
+#[derive(Debug, PartialEq, Clone)]
+pub struct CanonArray {
+  values: Vec<i16>,
+  fields: &'static [(usize, &'static str)]
+}
+
+impl CanonArray {
+  fn from_variant(value: &ExifVariant, fields: &'static [(usize, &'static str)]) -> Option<CanonArray> {
+    let values = match *value {
+      ExifVariant::UShort(ref c) => c.iter().map(|n| n as i16).collect(),
+      ExifVariant::Short(ref c) => c.iter().collect(),
+      _ => return None
+    };
+    Some(CanonArray { values, fields })
+  }
+
+  pub fn get(&self, index: usize) -> Option<i16> {
+    self.values.get(index).cloned()
+  }
+
+  /** the value of a field by its ExifTool name, like `LensType` */
+  pub fn field(&self, name: &str) -> Option<i16> {
+    self.fields.iter()
+      .find(|&&(_, n)| n == name)
+      .and_then(|&(index, _)| self.get(index))
+  }
+
+  /** all named fields present in the array */
+  pub fn fields(&self) -> Vec<(&'static str, i16)> {
+    self.fields.iter()
+      .filter_map(|&(index, name)| self.get(index).map(|v| (name, v)))
+      .collect()
+  }
+}
+
+/** The fields of a Canon MakerNote that are most often asked for */
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CanonMakerNote {
+  pub camera_settings: Option<CanonArray>,
+  pub shot_info: Option<CanonArray>,
+  pub file_info: Option<CanonArray>,
+  pub image_type: Option<String>,
+  pub firmware_version: Option<String>,
+  pub file_number: Option<u32>,
+  pub owner_name: Option<String>,
+  pub serial_number: Option<u32>,
+  pub model_id: Option<u32>,
+  pub image_unique_id: Option<String>,
+  pub lens_model: Option<String>,
+  pub internal_serial_number: Option<String>
+}
+
+impl CanonMakerNote {
+  pub fn parse(maker_note: &MakerNote) -> ParseResult<CanonMakerNote> {
+    let mut canon = CanonMakerNote::default();
+    for result in maker_note.iter()? {
+      let (tag, _) = result?;
+      let value = &tag.value;
+      match tag.no {
+        0x0001 => canon.camera_settings = CanonArray::from_variant(value, CAMERA_SETTINGS_FIELDS),
+        0x0004 => canon.shot_info = CanonArray::from_variant(value, SHOT_INFO_FIELDS),
+        0x0006 => canon.image_type = text(value),
+        0x0007 => canon.firmware_version = text(value),
+        0x0008 => canon.file_number = value.as_u32(),
+        0x0009 => canon.owner_name = text(value),
+        0x000C => canon.serial_number = value.as_u32(),
+        0x0010 => canon.model_id = value.as_u32(),
+        0x0028 => canon.image_unique_id = match *value {
+          ExifVariant::Bytes(bytes) | ExifVariant::Undefined(bytes) if bytes.iter().any(|b| *b != 0) =>
+            Some(bytes.iter().map(|b| format!("{:02x}", b)).collect()),
+          _ => None
+        },
+        0x0093 => canon.file_info = CanonArray::from_variant(value, FILE_INFO_FIELDS),
+        0x0095 => canon.lens_model = text(value),
+        0x0096 => canon.internal_serial_number = text(value),
+        _ => ()
+      }
+    }
+    Ok(canon)
+  }
+
+  /** the shortest and longest focal length of the lens in mm */
+  pub fn focal_length_range(&self) -> Option<(f64, f64)> {
+    let settings = self.camera_settings.as_ref()?;
+    let units = match settings.field("FocalUnits") {
+      Some(u) if u > 0 => u as f64,
+      _ => 1.0
+    };
+    let min = settings.field("MinFocalLength")? as u16 as f64 / units;
+    let max = settings.field("MaxFocalLength")? as u16 as f64 / units;
+    Some((min, max))
+  }
+
+  /** The number of the image file in the form `100-1234`, directory and file number,
+      as used by the camera in the file name. Most Canon models don't store
+      a shutter count in the MakerNote, this is the closest to it. */
+  pub fn file_number_display(&self) -> Option<String> {
+    let n = self.file_number?;
+    Some(format!("{}-{:04}", n / 10000, n % 10000))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::tag::read_exif_tag;
+  use ::tags::Section;
+  use ::writer::{ExifWriter, ExifValue};
+  use ::makernote::MakerNote;
+  use super::CanonMakerNote;
+
+  /** A Canon MakerNote IFD, written as IFD0 of its own TIFF structure
+      so the offsets in it are relative to the start of `tiff`, followed
+      by a MakerNote entry pointing to it */
+  fn canon_tiff() -> (Vec<u8>, usize) {
+    let mut camera_settings = vec![0u16; 47];
+    camera_settings[0] = 94;
+    camera_settings[22] = 61;
+    camera_settings[23] = 105;
+    camera_settings[24] = 24;
+    camera_settings[25] = 1;
+    let mut shot_info = vec![0u16; 34];
+    shot_info[0] = 68;
+    shot_info[21] = 160;
+
+    let mut writer = ExifWriter::new(Endianness::Little);
+    writer.add_tag(Section::IFD0, 0x0001, ExifValue::UShort(camera_settings));
+    writer.add_tag(Section::IFD0, 0x0004, ExifValue::UShort(shot_info));
+    writer.add_tag(Section::IFD0, 0x0006, ExifValue::Text("Canon EOS 5D Mark III".to_string()));
+    writer.add_tag(Section::IFD0, 0x0008, ExifValue::UInt(vec![1_001_234]));
+    writer.add_tag(Section::IFD0, 0x000C, ExifValue::UInt(vec![123_456_789]));
+    writer.add_tag(Section::IFD0, 0x0095, ExifValue::Text("EF24-105mm f/4L IS USM".to_string()));
+    writer.add_tag(Section::IFD0, 0x0028, ExifValue::Bytes(vec![0xAB; 16]));
+    let mut tiff = writer.write_tiff();
+
+    let entry_offset = tiff.len();
+    let len = (tiff.len() - 8) as u32;
+    tiff.extend_from_slice(&[0x7C, 0x92, 7, 0]);
+    tiff.extend_from_slice(&len.to_le_bytes());
+    tiff.extend_from_slice(&8u32.to_le_bytes());
+    (tiff, entry_offset)
+  }
+
+  #[test]
+  fn test_parse_canon() {
+    let (tiff, entry_offset) = canon_tiff();
+    let tiff_cursor = Cursor::new(&tiff, Endianness::Little);
+    let entry = tiff_cursor.with_skip_or_fail(entry_offset).unwrap();
+    let tag = read_exif_tag(entry, tiff_cursor).unwrap();
+    let maker_note = MakerNote::new(&tag, Some("Canon")).unwrap().unwrap();
+    let canon = CanonMakerNote::parse(&maker_note).unwrap();
+
+    assert_eq!(canon.image_type, Some("Canon EOS 5D Mark III".to_string()));
+    assert_eq!(canon.lens_model, Some("EF24-105mm f/4L IS USM".to_string()));
+    assert_eq!(canon.serial_number, Some(123_456_789));
+    assert_eq!(canon.file_number_display(), Some("100-1234".to_string()));
+    assert_eq!(canon.image_unique_id, Some("ab".repeat(16)));
+    assert_eq!(canon.focal_length_range(), Some((24.0, 105.0)));
+
+    let settings = canon.camera_settings.as_ref().unwrap();
+    assert_eq!(settings.field("LensType"), Some(61));
+    assert_eq!(settings.fields()[0], ("MacroMode", 0));
+    assert_eq!(canon.shot_info.as_ref().unwrap().field("FNumber"), Some(160));
+    assert!(canon.file_info.is_none());
+  }
+
+  #[test]
+  fn test_canon_tag_names() {
+    use ::makernote::Vendor;
+    use ::names::tag_name;
+    assert_eq!(tag_name(Section::MakerNote(Vendor::Canon), 0x0095), Some("LensModel"));
+    assert_eq!(tag_name(Section::MakerNote(Vendor::Apple), 0x0095), None);
+  }
+}
@@ -0,0 +1,182 @@
+use ::error::ParseResult;
+use super::{MakerNote, text};
+
+pub const FUJIFILM_TAG_NAMES : &[(u16, &str)] = &[
+  (0x0000, "Version"),
+  (0x0010, "InternalSerialNumber"),
+  (0x1000, "Quality"),
+  (0x1001, "Sharpness"),
+  (0x1002, "WhiteBalance"),
+  (0x1003, "Saturation"),
+  (0x1004, "Contrast"),
+  (0x1005, "ColorTemperature"),
+  (0x100A, "WhiteBalanceFineTune"),
+  (0x100E, "NoiseReduction"),
+  (0x1010, "FujiFlashMode"),
+  (0x1011, "FlashExposureComp"),
+  (0x1020, "Macro"),
+  (0x1021, "FocusMode"),
+  (0x1022, "AFMode"),
+  (0x1030, "SlowSync"),
+  (0x1031, "PictureMode"),
+  (0x1032, "ExposureCount"),
+  (0x1100, "AutoBracketing"),
+  (0x1101, "SequenceNumber"),
+  (0x1300, "BlurWarning"),
+  (0x1301, "FocusWarning"),
+  (0x1302, "ExposureWarning"),
+  (0x1400, "DynamicRange"),
+  (0x1401, "FilmMode"),
+  (0x1402, "DynamicRangeSetting"),
+  (0x1403, "DevelopmentDynamicRange"),
+  (0x1404, "MinFocalLength"),
+  (0x1405, "MaxFocalLength"),
+  (0x1406, "MaxApertureAtMinFocal"),
+  (0x1407, "MaxApertureAtMaxFocal"),
+  (0x1422, "ImageStabilization"),
+  (0x1431, "Rating"),
+  (0x1436, "ImageGeneration"),
+  (0x1438, "ImageCount"),
+  (0x4100, "FacesDetected"),
+  (0x8000, "FileSource"),
+  (0x8002, "OrderNumber"),
+  (0x8003, "FrameNumber")
+];
+
+/** the film simulation of a FilmMode (0x1401) value */
+pub fn film_simulation_name(film_mode: u16) -> Option<&'static str> {
+  let name = match film_mode {
+    0x000 => "Provia/Standard",
+    0x100 => "Studio Portrait",
+    0x110 => "Studio Portrait Enhanced Saturation",
+    0x120 => "Astia/Soft",
+    0x130 => "Studio Portrait Increased Sharpness",
+    0x200 => "Velvia/Fujichrome",
+    0x300 => "Studio Portrait Ex",
+    0x400 => "Velvia",
+    0x500 => "Pro Neg. Std",
+    0x501 => "Pro Neg. Hi",
+    0x600 => "Classic Chrome",
+    0x700 => "Eterna",
+    0x800 => "Classic Negative",
+    0x900 => "Bleach Bypass",
+    0xA00 => "Nostalgic Neg",
+    0xB00 => "Reala ACE",
+    _ => return None
+  };
+  Some(name)
+}
+
+/** the monochrome film simulations, which are stored in Saturation (0x1003) instead */
+fn monochrome_name(saturation: u16) -> Option<&'static str> {
+  let name = match saturation {
+    0x300 => "Monochrome",
+    0x301 => "Monochrome + R Filter",
+    0x302 => "Monochrome + Ye Filter",
+    0x303 => "Monochrome + G Filter",
+    0x310 => "Sepia",
+    0x500 => "Acros",
+    0x501 => "Acros + R Filter",
+    0x502 => "Acros + Ye Filter",
+    0x503 => "Acros + G Filter",
+    _ => return None
+  };
+  Some(name)
+}
+
+/** The fields of a Fujifilm MakerNote that are most often asked for */
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FujifilmMakerNote {
+  pub version: Option<String>,
+  pub internal_serial_number: Option<String>,
+  pub quality: Option<String>,
+  pub saturation: Option<u16>,
+  pub film_mode: Option<u16>,
+  pub min_focal_length: Option<f64>,
+  pub max_focal_length: Option<f64>,
+  pub max_aperture_at_min_focal: Option<f64>,
+  pub max_aperture_at_max_focal: Option<f64>,
+  /** the number of images taken by the camera, which wraps at 32768 */
+  pub image_count: Option<u16>
+}
+
+impl FujifilmMakerNote {
+  pub fn parse(maker_note: &MakerNote) -> ParseResult<FujifilmMakerNote> {
+    let mut fuji = FujifilmMakerNote::default();
+    for result in maker_note.iter()? {
+      let (tag, _) = result?;
+      let value = &tag.value;
+      match tag.no {
+        0x0000 => fuji.version = text(value),
+        0x0010 => fuji.internal_serial_number = text(value),
+        0x1000 => fuji.quality = text(value),
+        0x1003 => fuji.saturation = value.as_u32().map(|n| n as u16),
+        0x1401 => fuji.film_mode = value.as_u32().map(|n| n as u16),
+        0x1404 => fuji.min_focal_length = value.as_f64(),
+        0x1405 => fuji.max_focal_length = value.as_f64(),
+        0x1406 => fuji.max_aperture_at_min_focal = value.as_f64(),
+        0x1407 => fuji.max_aperture_at_max_focal = value.as_f64(),
+        0x1438 => fuji.image_count = value.as_u32().map(|n| n as u16 & 0x7FFF),
+        _ => ()
+      }
+    }
+    Ok(fuji)
+  }
+
+  /** The film simulation the image was taken with. Monochrome simulations
+      are read from Saturation, as the FilmMode tag is absent for them. */
+  pub fn film_simulation(&self) -> Option<&'static str> {
+    self.saturation.and_then(monochrome_name)
+      .or_else(|| self.film_mode.and_then(film_simulation_name))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::Endianness;
+  use ::writer::ExifValue;
+  use ::makernote::Vendor;
+  use ::makernote::test_support::{write_ifd, tiff_with_maker_note, read_test_maker_note};
+  use super::FujifilmMakerNote;
+
+  fn fuji_maker_note(tags: &[(u16, ExifValue)]) -> Vec<u8> {
+    //the IFD is little endian and its offsets are relative to the MakerNote
+    let mut maker_note = b"FUJIFILM\x0C\0\0\0".to_vec();
+    maker_note.extend(write_ifd(tags, 12, Endianness::Little));
+    maker_note
+  }
+
+  #[test]
+  fn test_parse_fujifilm() {
+    let maker_note = fuji_maker_note(&[
+      (0x0000, ExifValue::Undefined(b"0130".to_vec())),
+      (0x0010, ExifValue::Text("FC  A1234567     593330323016".to_string())),
+      (0x1401, ExifValue::UShort(vec![0x800])),
+      (0x1404, ExifValue::UIntFraction(vec![(1800, 100)])),
+      (0x1405, ExifValue::UIntFraction(vec![(5500, 100)])),
+      (0x1438, ExifValue::UShort(vec![0x8000 | 1234]))
+    ]);
+    //a big endian TIFF, the Fujifilm IFD stays little endian
+    let tiff = tiff_with_maker_note(Endianness::Big, &maker_note);
+    let maker_note = read_test_maker_note(&tiff, Endianness::Big, "FUJIFILM");
+    assert_eq!(maker_note.vendor, Vendor::Fujifilm);
+    let fuji = FujifilmMakerNote::parse(&maker_note).unwrap();
+    assert_eq!(fuji.version, Some("0130".to_string()));
+    assert_eq!(fuji.internal_serial_number, Some("FC  A1234567     593330323016".to_string()));
+    assert_eq!(fuji.film_simulation(), Some("Classic Negative"));
+    assert_eq!(fuji.min_focal_length, Some(18.0));
+    assert_eq!(fuji.max_focal_length, Some(55.0));
+    assert_eq!(fuji.image_count, Some(1234));
+  }
+
+  #[test]
+  fn test_monochrome_film_simulation() {
+    let maker_note = fuji_maker_note(&[
+      (0x1003, ExifValue::UShort(vec![0x501]))
+    ]);
+    let tiff = tiff_with_maker_note(Endianness::Little, &maker_note);
+    let maker_note = read_test_maker_note(&tiff, Endianness::Little, "FUJIFILM");
+    let fuji = FujifilmMakerNote::parse(&maker_note).unwrap();
+    assert_eq!(fuji.film_simulation(), Some("Acros + R Filter"));
+  }
+}
@@ -0,0 +1,409 @@
+use ::cursor::{Cursor, Endianness};
+use ::error::{ParseError, ParseResult};
+use ::section::{read_section, SectionIterator};
+use ::tag::{RawExifTag, ExifVariant};
+use ::tags::{read_tags, Section};
+
+mod canon;
+mod nikon;
+mod sony;
+mod fujifilm;
+mod olympus;
+mod panasonic;
+mod apple;
+mod bplist;
+#[cfg(test)]
+mod test_support;
+
+pub use self::canon::{CanonMakerNote, CanonArray};
+pub use self::nikon::{NikonMakerNote, NikonLensData, NikonVRInfo, NikonShotInfo, nikon_decrypt};
+pub use self::sony::{SonyMakerNote, sony_decipher};
+pub use self::fujifilm::{FujifilmMakerNote, film_simulation_name};
+pub use self::olympus::{OlympusMakerNote, OlympusEquipment, OlympusCameraSettings};
+pub use self::panasonic::PanasonicMakerNote;
+pub use self::apple::{AppleMakerNote, AppleRunTime};
+pub use self::bplist::{PlistValue, parse_bplist};
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Vendor {
+  Canon,
+  Nikon,
+  Sony,
+  Fujifilm,
+  Olympus,
+  Panasonic,
+  Apple
+}
+
+/** the names of the MakerNote tags of a vendor, as used by ExifTool */
+pub fn maker_note_tag_names(vendor: Vendor) -> &'static [(u16, &'static str)] {
+  match vendor {
+    Vendor::Canon => canon::CANON_TAG_NAMES,
+    Vendor::Nikon => nikon::NIKON_TAG_NAMES,
+    Vendor::Sony => sony::SONY_TAG_NAMES,
+    Vendor::Fujifilm => fujifilm::FUJIFILM_TAG_NAMES,
+    Vendor::Olympus => olympus::OLYMPUS_TAG_NAMES,
+    Vendor::Panasonic => panasonic::PANASONIC_TAG_NAMES,
+    Vendor::Apple => apple::APPLE_TAG_NAMES
+  }
+}
+
+/** a text value up to its first NUL, `None` if it is empty */
+fn text(value: &ExifVariant) -> Option<String> {
+  let text = match *value {
+    ExifVariant::Text(text) => text,
+    ExifVariant::Undefined(bytes) => ::std::str::from_utf8(bytes).ok()?,
+    _ => return None
+  };
+  let text = text.split('\0').next().unwrap_or("").trim();
+  if text.is_empty() { None } else { Some(text.to_string()) }
+}
+
+fn bytes<'a>(value: &ExifVariant<'a>) -> Option<&'a [u8]> {
+  match *value {
+    ExifVariant::Bytes(bytes) | ExifVariant::Undefined(bytes) => Some(bytes),
+    _ => None
+  }
+}
+
+/** what the offsets in the MakerNote IFD are relative to */
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum OffsetBase {
+  /** the TIFF header of the Exif data, like the standard IFDs */
+  Tiff,
+  /** the start of the MakerNote value */
+  MakerNote,
+  /** a TIFF header inside the MakerNote, at the given offset from its start */
+  EmbeddedTiff(usize)
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+struct Layout {
+  vendor: Vendor,
+  /** the offset of the IFD from the MakerNote start, or from the embedded TIFF header */
+  ifd_offset: usize,
+  offset_base: OffsetBase,
+  /** `None` if the byte order of the Exif data is used */
+  endianness: Option<Endianness>
+}
+
+impl Layout {
+  fn new(vendor: Vendor, ifd_offset: usize, offset_base: OffsetBase) -> Layout {
+    Layout { vendor, ifd_offset, offset_base, endianness: None }
+  }
+
+  fn with_endianness(mut self, endianness: Endianness) -> Layout {
+    self.endianness = Some(endianness);
+    self
+  }
+}
+
+fn endianness_from_marker(marker: &[u8]) -> Option<Endianness> {
+  match marker {
+    b"II" => Some(Endianness::Little),
+    b"MM" => Some(Endianness::Big),
+    _ => None
+  }
+}
+
+/** Detects the MakerNote format from its header, falling back to
+    the Make tag for vendors that don't write a header. */
+fn detect_layout(make: Option<&str>, data: &[u8]) -> Option<Layout> {
+  if data.starts_with(b"Nikon\0\x02") {
+    return Some(Layout::new(Vendor::Nikon, 0, OffsetBase::EmbeddedTiff(10)));
+  }
+  if data.starts_with(b"Nikon\0\x01") {
+    return Some(Layout::new(Vendor::Nikon, 8, OffsetBase::Tiff));
+  }
+  if data.starts_with(b"FUJIFILM") {
+    //the IFD offset is stored after the header, always little endian
+    let offset : u32 = Cursor::new(data, Endianness::Little).with_skip(8)?.read_num()?;
+    return Some(Layout::new(Vendor::Fujifilm, offset as usize, OffsetBase::MakerNote)
+      .with_endianness(Endianness::Little));
+  }
+  if data.starts_with(b"OLYMPUS\0") {
+    let endianness = data.get(8 .. 10).and_then(endianness_from_marker)?;
+    return Some(Layout::new(Vendor::Olympus, 12, OffsetBase::MakerNote)
+      .with_endianness(endianness));
+  }
+  if data.starts_with(b"OM SYSTEM\0") {
+    let endianness = data.get(12 .. 14).and_then(endianness_from_marker)?;
+    return Some(Layout::new(Vendor::Olympus, 16, OffsetBase::MakerNote)
+      .with_endianness(endianness));
+  }
+  if data.starts_with(b"OLYMP\0") {
+    return Some(Layout::new(Vendor::Olympus, 8, OffsetBase::Tiff));
+  }
+  if data.starts_with(b"SONY DSC \0\0\0") || data.starts_with(b"SONY CAM \0\0\0") {
+    return Some(Layout::new(Vendor::Sony, 12, OffsetBase::Tiff));
+  }
+  if data.starts_with(b"Panasonic\0\0\0") {
+    return Some(Layout::new(Vendor::Panasonic, 12, OffsetBase::Tiff));
+  }
+  if data.starts_with(b"Apple iOS\0") {
+    let endianness = data.get(12 .. 14).and_then(endianness_from_marker)?;
+    return Some(Layout::new(Vendor::Apple, 14, OffsetBase::MakerNote)
+      .with_endianness(endianness));
+  }
+
+  let make = make?.trim_end_matches('\0').to_lowercase();
+  if make.starts_with("canon") {
+    Some(Layout::new(Vendor::Canon, 0, OffsetBase::Tiff))
+  } else if make.starts_with("nikon") {
+    //older Nikon models write the IFD without header
+    Some(Layout::new(Vendor::Nikon, 0, OffsetBase::Tiff))
+  } else if make.starts_with("sony") {
+    Some(Layout::new(Vendor::Sony, 0, OffsetBase::Tiff))
+  } else {
+    None
+  }
+}
+
+/** A MakerNote whose format could be detected.
+    Iterating it yields its tags in `Section::MakerNote(vendor)`. */
+pub struct MakerNote<'a> {
+  pub vendor: Vendor,
+  data: Cursor<'a>,
+  ifd: Cursor<'a>,
+  offset_base: Cursor<'a>
+}
+
+impl<'a> MakerNote<'a> {
+  /** Parses the MakerNote tag (0x927C), using the value of the Make tag (0x010F)
+      for detecting the vendor. Returns `Ok(None)` if the format is not known. */
+  pub fn new(tag: &RawExifTag<'a>, make: Option<&str>) -> ParseResult<Option<MakerNote<'a>>> {
+    let data = tag.value_cursor();
+    let layout = match detect_layout(make, tag.value_bytes()) {
+      Some(layout) => layout,
+      None => return Ok(None)
+    };
+    let endianness = layout.endianness.unwrap_or_else(|| data.endianness());
+
+    let (offset_base, ifd) = match layout.offset_base {
+      OffsetBase::Tiff => {
+        let tiff = tag.tiff_marker().with_endianness(endianness);
+        let ifd = data.with_endianness(endianness).with_skip_or_fail(layout.ifd_offset)?;
+        (tiff, ifd)
+      },
+      OffsetBase::MakerNote => {
+        let data = data.with_endianness(endianness);
+        (data, data.with_skip_or_fail(layout.ifd_offset)?)
+      },
+      OffsetBase::EmbeddedTiff(tiff_offset) => {
+        let tiff = data.with_skip_or_fail(tiff_offset)?;
+        let mut header = tiff;
+        let byte_order = header.read_bytes_or_fail(2)?;
+        let endianness = match endianness_from_marker(byte_order) {
+          Some(endianness) => endianness,
+          None => return Err(ParseError::InvalidTiffHeader {
+            header: (byte_order[0] as u16) << 8 | byte_order[1] as u16
+          })
+        };
+        let mut header = header.with_endianness(endianness);
+        let tiff_data : u16 = header.read_num_or_fail()?;
+        if tiff_data != 0x002A {
+          return Err(ParseError::InvalidTiffData { data: tiff_data });
+        }
+        let ifd_offset : u32 = header.read_num_or_fail()?;
+        let tiff = tiff.with_endianness(endianness);
+        (tiff, tiff.with_skip_or_fail(ifd_offset as usize)?)
+      }
+    };
+
+    Ok(Some(MakerNote {
+      vendor: layout.vendor,
+      data: data.with_endianness(endianness),
+      ifd,
+      offset_base
+    }))
+  }
+
+  /** the whole MakerNote value, in the byte order of the MakerNote */
+  pub fn data(&self) -> Cursor<'a> {
+    self.data
+  }
+
+  /** the cursor offsets in the MakerNote IFD are relative to */
+  pub fn offset_base(&self) -> Cursor<'a> {
+    self.offset_base
+  }
+
+  pub fn endianness(&self) -> Endianness {
+    self.offset_base.endianness()
+  }
+
+  pub fn iter(&self) -> ParseResult<MakerNoteIterator<'a>> {
+    Ok(MakerNoteIterator {
+      section: read_section(self.ifd, self.offset_base)?,
+      vendor: self.vendor
+    })
+  }
+
+  /** Iterates a sub-IFD of the MakerNote, like the Olympus Equipment IFD.
+      A LONG or IFD value is an offset relative to the offset base of the MakerNote,
+      an UNDEFINED value contains the IFD itself, with offsets relative to its start. */
+  pub fn sub_ifd(&self, tag: &RawExifTag<'a>) -> ParseResult<MakerNoteIterator<'a>> {
+    let endianness = self.endianness();
+    let (ifd, offset_base) = match tag.value {
+      ExifVariant::UInt(_) => {
+        let offset = tag.value.as_u32().ok_or(ParseError::UnexpectedEOF)?;
+        (self.offset_base.with_skip_or_fail(offset as usize)?, self.offset_base)
+      },
+      ExifVariant::Undefined(_) => {
+        let value = tag.value_cursor().with_endianness(endianness);
+        (value, value)
+      },
+      _ => return Err(ParseError::InvalidValueFormat { format: tag.format.number() })
+    };
+    Ok(MakerNoteIterator {
+      section: read_section(ifd, offset_base)?,
+      vendor: self.vendor
+    })
+  }
+}
+
+pub struct MakerNoteIterator<'a> {
+  section: SectionIterator<'a>,
+  vendor: Vendor
+}
+
+impl<'a> Iterator for MakerNoteIterator<'a> {
+  type Item = ParseResult<(RawExifTag<'a>, Section)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let section = Section::MakerNote(self.vendor);
+    self.section.next().map(|tag| tag.map(|t| (t, section)))
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.section.size_hint()
+  }
+}
+
+/** Finds the Make and MakerNote tags in an Exif APP1 segment and parses the MakerNote */
+pub fn read_maker_note<'a>(app1_cursor: Cursor<'a>) -> ParseResult<Option<MakerNote<'a>>> {
+  let mut make = None;
+  let mut maker_note = None;
+  for result in read_tags(app1_cursor)? {
+    let (tag, section) = result?;
+    match (section, tag.no, &tag.value) {
+      (Section::IFD0, 0x010F, &ExifVariant::Text(text)) => make = Some(text),
+      (Section::SubIFD, 0x927C, _) => maker_note = Some(tag),
+      _ => ()
+    }
+  }
+  match maker_note {
+    Some(tag) => MakerNote::new(&tag, make),
+    None => Ok(None)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::tag::read_exif_tag;
+  use ::tags::Section;
+  use ::writer::{ExifWriter, ExifValue};
+  use super::{detect_layout, read_maker_note, MakerNote, Vendor, OffsetBase, Layout};
+
+  /** an Exif APP1 segment with the given Make and MakerNote */
+  fn app1_with_maker_note(endianness: Endianness, make: &str, maker_note: Vec<u8>) -> Vec<u8> {
+    let mut writer = ExifWriter::new(endianness);
+    writer.add_tag(Section::IFD0, 0x010F, ExifValue::Text(make.to_string()));
+    writer.add_tag(Section::SubIFD, 0x927C, ExifValue::Undefined(maker_note));
+    writer.write_app1()
+  }
+
+  /** A big endian IFD with a SHORT tag and a tag with 8 bytes of text stored at `text_offset` */
+  fn ifd(text_offset: u32) -> Vec<u8> {
+    let mut ifd = vec![0u8, 2u8];
+    ifd.extend_from_slice(&[0x00, 0x01, 0x00, 0x03, 0, 0, 0, 1, 0x12, 0x34, 0, 0]);
+    ifd.extend_from_slice(&[0x00, 0x02, 0x00, 0x02, 0, 0, 0, 8]);
+    ifd.extend_from_slice(&[(text_offset >> 24) as u8, (text_offset >> 16) as u8, (text_offset >> 8) as u8, text_offset as u8]);
+    ifd.extend_from_slice(&[0, 0, 0, 0]);
+    ifd
+  }
+
+  fn tag_values(maker_note: &MakerNote) -> Vec<(u16, String)> {
+    maker_note.iter().unwrap()
+      .map(|r| r.unwrap())
+      .map(|(tag, section)| {
+        assert_eq!(section, Section::MakerNote(maker_note.vendor));
+        (tag.no, tag.value.to_string())
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_detect_layout() {
+    assert_eq!(detect_layout(None, b"Nikon\0\x02\x10\0\0MM\0\x2A"),
+      Some(Layout::new(Vendor::Nikon, 0, OffsetBase::EmbeddedTiff(10))));
+    assert_eq!(detect_layout(Some("Canon"), &[0, 1]),
+      Some(Layout::new(Vendor::Canon, 0, OffsetBase::Tiff)));
+    assert_eq!(detect_layout(Some("Apple"), b"Apple iOS\0\0\x01MM").map(|l| l.endianness),
+      Some(Some(Endianness::Big)));
+    assert_eq!(detect_layout(Some("Nokia"), &[0, 1]), None);
+  }
+
+  #[test]
+  fn test_offsets_relative_to_maker_note() {
+    //Apple MakerNotes are always big endian, even in little endian Exif data
+    let mut maker_note = b"Apple iOS\0\0\x01MM".to_vec();
+    let text_offset = maker_note.len() as u32 + 30;
+    maker_note.extend(ifd(text_offset));
+    maker_note.extend_from_slice(b"MakerNo\0");
+
+    let app1 = app1_with_maker_note(Endianness::Little, "Apple", maker_note);
+    let maker_note = read_maker_note(Cursor::new(&app1, Endianness::Little)).unwrap().unwrap();
+    assert_eq!(maker_note.vendor, Vendor::Apple);
+    assert_eq!(maker_note.endianness(), Endianness::Big);
+    assert_eq!(tag_values(&maker_note), vec![
+      (1, "4660".to_string()),
+      (2, "MakerNo".to_string())
+    ]);
+  }
+
+  #[test]
+  fn test_offsets_relative_to_embedded_tiff() {
+    let mut maker_note = b"Nikon\0\x02\x10\0\0MM\0\x2A\0\0\0\x08".to_vec();
+    maker_note.extend(ifd(8 + 30));
+    maker_note.extend_from_slice(b"Embedde\0");
+
+    let app1 = app1_with_maker_note(Endianness::Little, "NIKON CORPORATION", maker_note);
+    let maker_note = read_maker_note(Cursor::new(&app1, Endianness::Little)).unwrap().unwrap();
+    assert_eq!(maker_note.vendor, Vendor::Nikon);
+    assert_eq!(tag_values(&maker_note), vec![
+      (1, "4660".to_string()),
+      (2, "Embedde".to_string())
+    ]);
+  }
+
+  #[test]
+  fn test_offsets_relative_to_tiff() {
+    //a Canon MakerNote is a plain IFD, with offsets relative to the Exif TIFF header
+    let data = ifd(0x40);
+    let mut tiff = vec![0u8; 0x40];
+    tiff.extend_from_slice(b"Canon12\0");
+    let entry_offset = tiff.len();
+    tiff.extend_from_slice(&[0x92, 0x7C, 0, 7, 0, 0, 0, data.len() as u8, 0, 0, 0, 0x54]);
+    tiff.extend(data);
+
+    let tiff_cursor = Cursor::new(&tiff, Endianness::Big);
+    let entry = tiff_cursor.with_skip_or_fail(entry_offset).unwrap();
+    let tag = read_exif_tag(entry, tiff_cursor).unwrap();
+    assert!(MakerNote::new(&tag, None).unwrap().is_none());
+    let maker_note = MakerNote::new(&tag, Some("Canon")).unwrap().unwrap();
+    assert_eq!(tag_values(&maker_note), vec![
+      (1, "4660".to_string()),
+      (2, "Canon12".to_string())
+    ]);
+  }
+
+  #[test]
+  fn test_sample_has_no_maker_note() {
+    use ::test_fixtures::{JPEG_SAMPLE, JPEG_SAMPLE_EXIF_OFFSET};
+    let cursor = Cursor::new(JPEG_SAMPLE, Endianness::Big)
+      .with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).unwrap();
+    assert!(read_maker_note(cursor).unwrap().is_none());
+  }
+}
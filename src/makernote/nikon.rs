@@ -0,0 +1,454 @@
+use ::cursor::{Cursor, Endianness};
+use ::error::ParseResult;
+use ::tag::ExifVariant;
+use super::{MakerNote, bytes};
+
+pub const NIKON_TAG_NAMES : &[(u16, &str)] = &[
+  (0x0001, "MakerNoteVersion"),
+  (0x0002, "ISO"),
+  (0x0004, "Quality"),
+  (0x0005, "WhiteBalance"),
+  (0x0007, "FocusMode"),
+  (0x001D, "SerialNumber"),
+  (0x001F, "VRInfo"),
+  (0x0083, "LensType"),
+  (0x0084, "Lens"),
+  (0x0091, "ShotInfo"),
+  (0x0098, "LensData"),
+  (0x00A7, "ShutterCount")
+];
+
+/** The tables to derive the decryption key from, as used by dcraw and ExifTool.
+    The first is indexed with the serial number, the second with the shutter count. */
+const XLAT : [[u8; 256]; 2] = [
+  [0xc1, 0xbf, 0x6d, 0x0d, 0x59, 0xc5, 0x13, 0x9d, 0x83, 0x61, 0x6b, 0x4f, 0xc7, 0x7f, 0x3d, 0x3d,
+   0x53, 0x59, 0xe3, 0xc7, 0xe9, 0x2f, 0x95, 0xa7, 0x95, 0x1f, 0xdf, 0x7f, 0x2b, 0x29, 0xc7, 0x0d,
+   0xdf, 0x07, 0xef, 0x71, 0x89, 0x3d, 0x13, 0x3d, 0x3b, 0x13, 0xfb, 0x0d, 0x89, 0xc1, 0x65, 0x1f,
+   0xb3, 0x0d, 0x6b, 0x29, 0xe3, 0xfb, 0xef, 0xa3, 0x6b, 0x47, 0x7f, 0x95, 0x35, 0xa7, 0x47, 0x4f,
+   0xc7, 0xf1, 0x59, 0x95, 0x35, 0x11, 0x29, 0x61, 0xf1, 0x3d, 0xb3, 0x2b, 0x0d, 0x43, 0x89, 0xc1,
+   0x9d, 0x9d, 0x89, 0x65, 0xf1, 0xe9, 0xdf, 0xbf, 0x3d, 0x7f, 0x53, 0x97, 0xe5, 0xe9, 0x95, 0x17,
+   0x1d, 0x3d, 0x8b, 0xfb, 0xc7, 0xe3, 0x67, 0xa7, 0x07, 0xf1, 0x71, 0xa7, 0x53, 0xb5, 0x29, 0x89,
+   0xe5, 0x2b, 0xa7, 0x17, 0x29, 0xe9, 0x4f, 0xc5, 0x65, 0x6d, 0x6b, 0xef, 0x0d, 0x89, 0x49, 0x2f,
+   0xb3, 0x43, 0x53, 0x65, 0x1d, 0x49, 0xa3, 0x13, 0x89, 0x59, 0xef, 0x6b, 0xef, 0x65, 0x1d, 0x0b,
+   0x59, 0x13, 0xe3, 0x4f, 0x9d, 0xb3, 0x29, 0x43, 0x2b, 0x07, 0x1d, 0x95, 0x59, 0x59, 0x47, 0xfb,
+   0xe5, 0xe9, 0x61, 0x47, 0x2f, 0x35, 0x7f, 0x17, 0x7f, 0xef, 0x7f, 0x95, 0x95, 0x71, 0xd3, 0xa3,
+   0x0b, 0x71, 0xa3, 0xad, 0x0b, 0x3b, 0xb5, 0xfb, 0xa3, 0xbf, 0x4f, 0x83, 0x1d, 0xad, 0xe9, 0x2f,
+   0x71, 0x65, 0xa3, 0xe5, 0x07, 0x35, 0x3d, 0x0d, 0xb5, 0xe9, 0xe5, 0x47, 0x3b, 0x9d, 0xef, 0x35,
+   0xa3, 0xbf, 0xb3, 0xdf, 0x53, 0xd3, 0x97, 0x53, 0x49, 0x71, 0x07, 0x35, 0x61, 0x71, 0x2f, 0x43,
+   0x2f, 0x11, 0xdf, 0x17, 0x97, 0xfb, 0x95, 0x3b, 0x7f, 0x6b, 0xd3, 0x25, 0xbf, 0xad, 0xc7, 0xc5,
+   0xc5, 0xb5, 0x8b, 0xef, 0x2f, 0xd3, 0x07, 0x6b, 0x25, 0x49, 0x95, 0x25, 0x49, 0x6d, 0x71, 0xc7],
+  [0xa7, 0xbc, 0xc9, 0xad, 0x91, 0xdf, 0x85, 0xe5, 0xd4, 0x78, 0xd5, 0x17, 0x46, 0x7c, 0x29, 0x4c,
+   0x4d, 0x03, 0xe9, 0x25, 0x68, 0x11, 0x86, 0xb3, 0xbd, 0xf7, 0x6f, 0x61, 0x22, 0xa2, 0x26, 0x34,
+   0x2a, 0xbe, 0x1e, 0x46, 0x14, 0x68, 0x9d, 0x44, 0x18, 0xc2, 0x40, 0xf4, 0x7e, 0x5f, 0x1b, 0xad,
+   0x0b, 0x94, 0xb6, 0x67, 0xb4, 0x0b, 0xe1, 0xea, 0x95, 0x9c, 0x66, 0xdc, 0xe7, 0x5d, 0x6c, 0x05,
+   0xda, 0xd5, 0xdf, 0x7a, 0xef, 0xf6, 0xdb, 0x1f, 0x82, 0x4c, 0xc0, 0x68, 0x47, 0xa1, 0xbd, 0xee,
+   0x39, 0x50, 0x56, 0x4a, 0xdd, 0xdf, 0xa5, 0xf8, 0xc6, 0xda, 0xca, 0x90, 0xca, 0x01, 0x42, 0x9d,
+   0x8b, 0x0c, 0x73, 0x43, 0x75, 0x05, 0x94, 0xde, 0x24, 0xb3, 0x80, 0x34, 0xe5, 0x2c, 0xdc, 0x9b,
+   0x3f, 0xca, 0x33, 0x45, 0xd0, 0xdb, 0x5f, 0xf5, 0x52, 0xc3, 0x21, 0xda, 0xe2, 0x22, 0x72, 0x6b,
+   0x3e, 0xd0, 0x5b, 0xa8, 0x87, 0x8c, 0x06, 0x5d, 0x0f, 0xdd, 0x09, 0x19, 0x93, 0xd0, 0xb9, 0xfc,
+   0x8b, 0x0f, 0x84, 0x60, 0x33, 0x1c, 0x9b, 0x45, 0xf1, 0xf0, 0xa3, 0x94, 0x3a, 0x12, 0x77, 0x33,
+   0x4d, 0x44, 0x78, 0x28, 0x3c, 0x9e, 0xfd, 0x65, 0x57, 0x16, 0x94, 0x6b, 0xfb, 0x59, 0xd0, 0xc8,
+   0x22, 0x36, 0xdb, 0xd2, 0x63, 0x98, 0x43, 0xa1, 0x04, 0x87, 0x86, 0xf7, 0xa6, 0x26, 0xbb, 0xd6,
+   0x59, 0x4d, 0xbf, 0x6a, 0x2e, 0xaa, 0x2b, 0xef, 0xe6, 0x78, 0xb6, 0x4e, 0xe0, 0x2f, 0xdc, 0x7c,
+   0xbe, 0x57, 0x19, 0x32, 0x7e, 0x2a, 0xd0, 0xb8, 0xba, 0x29, 0x00, 0x3c, 0x52, 0x7d, 0xa8, 0x49,
+   0x3b, 0x2d, 0xeb, 0x25, 0x49, 0xfa, 0xa3, 0xaa, 0x39, 0xa7, 0xc5, 0xa7, 0x50, 0x11, 0x36, 0xfb,
+   0xc6, 0x67, 0x4a, 0xf5, 0xa5, 0x12, 0x65, 0x7e, 0xb0, 0xdf, 0xaf, 0x4e, 0xb3, 0x61, 0x7f, 0x2f]
+];
+
+/** the serial number key for models that don't store a numeric serial number */
+const DEFAULT_SERIAL_KEY : u32 = 0x60;
+const D50_SERIAL_KEY : u32 = 0x22;
+
+/** The data of the encrypted tags (LensData, ShotInfo) is XORed with a stream
+    derived from the serial number and the shutter count. As it is an XOR,
+    the same function encrypts and decrypts. */
+pub fn nikon_decrypt(data: &mut [u8], serial: u32, shutter_count: u32) {
+  let key = shutter_count.to_be_bytes().iter().fold(0u8, |k, b| k ^ b);
+  let ci = XLAT[0][(serial & 0xff) as usize];
+  let mut cj = XLAT[1][key as usize];
+  let mut ck = 0x60u8;
+  for byte in data.iter_mut() {
+    cj = cj.wrapping_add(ci.wrapping_mul(ck));
+    ck = ck.wrapping_add(1);
+    *byte ^= cj;
+  }
+}
+
+/** Lens information, the same for all LensData versions */
+#[derive(Debug, PartialEq, Clone)]
+pub struct NikonLensData {
+  pub version: String,
+  pub lens_id_number: u8,
+  pub lens_f_stops: u8,
+  pub min_focal_length: u8,
+  pub max_focal_length: u8,
+  pub max_aperture_at_min_focal: u8,
+  pub max_aperture_at_max_focal: u8,
+  pub mcu_version: u8,
+  /** only stored from version 0101 on */
+  pub focal_length: Option<u8>,
+  /** only stored from version 0101 on */
+  pub focus_distance: Option<u8>
+}
+
+/** focal lengths are stored as 5 * 2^(n/24) mm */
+fn focal_length_mm(raw: u8) -> f64 {
+  5.0 * 2f64.powf(raw as f64 / 24.0)
+}
+
+/** apertures are stored as 2^(n/24) */
+fn aperture(raw: u8) -> f64 {
+  2f64.powf(raw as f64 / 24.0)
+}
+
+impl NikonLensData {
+  /** `data` needs to be decrypted already */
+  fn parse(data: &[u8]) -> Option<NikonLensData> {
+    let version = String::from_utf8_lossy(data.get(0 .. 4)?).into_owned();
+    //(LensIDNumber, FocalLength, FocusDistance) offsets, the other fields follow LensIDNumber
+    let (lens_id_offset, position) = match version.as_str() {
+      "0100" => (6, None),
+      "0101" | "0201" | "0202" | "0203" => (0x0B, Some((0x0A, 0x09))),
+      "0204" => (0x0C, Some((0x0B, 0x0A))),
+      _ => return None
+    };
+    let lens = data.get(lens_id_offset .. lens_id_offset + 7)?;
+    Some(NikonLensData {
+      version,
+      lens_id_number: lens[0],
+      lens_f_stops: lens[1],
+      min_focal_length: lens[2],
+      max_focal_length: lens[3],
+      max_aperture_at_min_focal: lens[4],
+      max_aperture_at_max_focal: lens[5],
+      mcu_version: lens[6],
+      focal_length: position.and_then(|(f, _)| data.get(f).cloned()),
+      focus_distance: position.and_then(|(_, d)| data.get(d).cloned())
+    })
+  }
+
+  pub fn focal_length_range(&self) -> (f64, f64) {
+    (focal_length_mm(self.min_focal_length), focal_length_mm(self.max_focal_length))
+  }
+
+  pub fn max_aperture_range(&self) -> (f64, f64) {
+    (aperture(self.max_aperture_at_min_focal), aperture(self.max_aperture_at_max_focal))
+  }
+
+  pub fn focal_length_mm(&self) -> Option<f64> {
+    self.focal_length.map(focal_length_mm)
+  }
+
+  /** the focus distance in meters, stored as 0.01 * 10^(n/40) */
+  pub fn focus_distance_m(&self) -> Option<f64> {
+    self.focus_distance.map(|n| 0.01 * 10f64.powf(n as f64 / 40.0))
+  }
+
+  /** The key ExifTool uses to look up the lens name, like `48 3C 8E B0 3C 3C 4B 0E`.
+      It combines the lens data with the LensType tag (0x0083). */
+  pub fn lens_id(&self, lens_type: u8) -> String {
+    let bytes = [
+      self.lens_id_number,
+      self.lens_f_stops,
+      self.min_focal_length,
+      self.max_focal_length,
+      self.max_aperture_at_min_focal,
+      self.max_aperture_at_max_focal,
+      self.mcu_version,
+      lens_type
+    ];
+    let parts : Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    parts.join(" ")
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct NikonVRInfo {
+  /** `None` if the lens has no vibration reduction */
+  pub vibration_reduction: Option<bool>,
+  /** 0 is Normal, 2 Active, 3 Sport */
+  pub vr_mode: Option<u8>
+}
+
+impl NikonVRInfo {
+  fn parse(data: &[u8]) -> Option<NikonVRInfo> {
+    let vibration_reduction = match *data.get(4)? {
+      1 => Some(true),
+      2 => Some(false),
+      _ => None
+    };
+    Some(NikonVRInfo {
+      vibration_reduction,
+      vr_mode: data.get(6).cloned()
+    })
+  }
+}
+
+/** ShotInfo with the encrypted part decrypted. Its layout differs per camera model,
+    the version (like `0210`) tells which one it is. Only the fields ExifTool knows
+    for the common versions are decoded, the rest is left in `data`. */
+#[derive(Debug, PartialEq, Clone)]
+pub struct NikonShotInfo {
+  pub version: String,
+  /** like `1.00a`, stored by all encrypted versions */
+  pub firmware_version: Option<String>,
+  pub shutter_count: Option<u32>,
+  pub data: Vec<u8>
+}
+
+/** the offset of the ShutterCount in the ShotInfo versions that store one,
+    from ExifTool's ShotInfoD80 and ShotInfoD90 tables */
+fn shot_info_shutter_count_offset(version: &str) -> Option<usize> {
+  match version {
+    "0208" => Some(586),
+    "0213" => Some(0x2D5),
+    _ => None
+  }
+}
+
+impl NikonShotInfo {
+  /** `data` needs to be decrypted already, the numbers are in the byte order of the MakerNote */
+  fn parse(data: Vec<u8>, endianness: Endianness) -> Option<NikonShotInfo> {
+    let version = String::from_utf8_lossy(data.get(0 .. 4)?).into_owned();
+    let encrypted = version.starts_with("02");
+    let firmware_version = data.get(4 .. 9)
+      .filter(|_| encrypted)
+      .map(|b| String::from_utf8_lossy(b).trim_end_matches('\0').to_string());
+    let shutter_count = shot_info_shutter_count_offset(&version).and_then(|offset| {
+      let mut cursor = Cursor::new(&data, endianness).with_skip_or_fail(offset).ok()?;
+      cursor.read_num_or_fail::<u32>().ok()
+    });
+    Some(NikonShotInfo { version, firmware_version, shutter_count, data })
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct NikonMakerNote {
+  pub version: Option<String>,
+  pub iso: Option<u32>,
+  pub serial_number: Option<String>,
+  pub lens_type: Option<u8>,
+  /** min and max focal length, max aperture at min and max focal length */
+  pub lens: Option<[f64; 4]>,
+  pub shutter_count: Option<u32>,
+  pub vr_info: Option<NikonVRInfo>,
+  pub lens_data: Option<NikonLensData>,
+  pub shot_info: Option<NikonShotInfo>
+}
+
+impl NikonMakerNote {
+  pub fn parse(maker_note: &MakerNote) -> ParseResult<NikonMakerNote> {
+    NikonMakerNote::parse_with_model(maker_note, None)
+  }
+
+  /** Like `parse`, with the value of the Model tag (0x0110), which
+      the encrypted tags need for models without a numeric serial number */
+  pub fn parse_with_model(maker_note: &MakerNote, model: Option<&str>) -> ParseResult<NikonMakerNote> {
+    let mut nikon = NikonMakerNote::default();
+    //the encrypted tags can only be decrypted once the serial number
+    //and shutter count are known, which may come after them
+    let mut lens_data = None;
+    let mut shot_info = None;
+
+    for result in maker_note.iter()? {
+      let (tag, _) = result?;
+      let value = &tag.value;
+      match tag.no {
+        0x0001 => nikon.version = bytes(value).map(|b| String::from_utf8_lossy(b).into_owned()),
+        0x0002 => nikon.iso = match *value {
+          ExifVariant::UShort(ref c) => c.iter().nth(1).map(|n| n as u32),
+          _ => None
+        },
+        0x001D => nikon.serial_number = match *value {
+          ExifVariant::Text(text) => Some(text.trim_end_matches('\0').to_string()),
+          _ => None
+        },
+        0x001F => nikon.vr_info = bytes(value).and_then(NikonVRInfo::parse),
+        0x0083 => nikon.lens_type = value.as_u32().map(|n| n as u8),
+        0x0084 => nikon.lens = match *value {
+          ExifVariant::UIntFraction(ref c) if c.iter().count() == 4 => {
+            let mut lens = [0f64; 4];
+            for (i, (n, d)) in c.iter().enumerate() {
+              lens[i] = if d == 0 { 0.0 } else { n as f64 / d as f64 };
+            }
+            Some(lens)
+          },
+          _ => None
+        },
+        0x0091 => shot_info = bytes(value).map(|b| b.to_vec()),
+        0x0098 => lens_data = bytes(value).map(|b| b.to_vec()),
+        0x00A7 => nikon.shutter_count = value.as_u32(),
+        _ => ()
+      }
+    }
+
+    let key = nikon.shutter_count.map(|count| (nikon.serial_key(model), count));
+    if let Some(mut data) = lens_data {
+      let encrypted = data.starts_with(b"02");
+      match key {
+        Some((serial, count)) if encrypted && data.len() > 4 =>
+          nikon_decrypt(&mut data[4 ..], serial, count),
+        _ => ()
+      }
+      if !encrypted || key.is_some() {
+        nikon.lens_data = NikonLensData::parse(&data);
+      }
+    }
+    //like LensData, only the 02xx versions are encrypted
+    if let Some(mut data) = shot_info {
+      let encrypted = data.starts_with(b"02");
+      match key {
+        Some((serial, count)) if encrypted && data.len() > 4 =>
+          nikon_decrypt(&mut data[4 ..], serial, count),
+        _ => ()
+      }
+      if !encrypted || key.is_some() {
+        nikon.shot_info = NikonShotInfo::parse(data, maker_note.endianness());
+      }
+    }
+    Ok(nikon)
+  }
+
+  /** The serial number used for decryption. Models with a non-numeric
+      serial number use a fixed key instead, 0x22 for the D50 and 0x60 for others. */
+  fn serial_key(&self, model: Option<&str>) -> u32 {
+    let is_d50 = model.is_some_and(|m| m.trim_end_matches('\0') == "NIKON D50");
+    self.serial_number.as_ref()
+      .and_then(|s| s.parse().ok())
+      .unwrap_or(if is_d50 { D50_SERIAL_KEY } else { DEFAULT_SERIAL_KEY })
+  }
+
+  /** the ExifTool lens id key, see `NikonLensData::lens_id` */
+  pub fn lens_id(&self) -> Option<String> {
+    let lens_data = self.lens_data.as_ref()?;
+    Some(lens_data.lens_id(self.lens_type?))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::tags::Section;
+  use ::writer::{ExifWriter, ExifValue};
+  use ::makernote::{read_maker_note, Vendor};
+  use super::{NikonMakerNote, nikon_decrypt};
+
+  const LENS_DATA : [u8; 20] = [
+    b'0', b'2', b'0', b'4',
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x50, //FocusDistance
+    0x3C, //FocalLength
+    0x48, 0x3C, 0x50, 0x8E, 0x24, 0x30, 0x04, 0x00
+  ];
+
+  fn nikon_app1(serial: &str, shutter_count: u32, lens_data: Vec<u8>) -> Vec<u8> {
+    nikon_app1_with_shot_info(serial, shutter_count, lens_data, None)
+  }
+
+  fn nikon_app1_with_shot_info(serial: &str, shutter_count: u32, lens_data: Vec<u8>, shot_info: Option<Vec<u8>>) -> Vec<u8> {
+    let mut writer = ExifWriter::new(Endianness::Big);
+    writer.add_tag(Section::IFD0, 0x0001, ExifValue::Undefined(b"0210".to_vec()));
+    writer.add_tag(Section::IFD0, 0x0002, ExifValue::UShort(vec![0, 400]));
+    writer.add_tag(Section::IFD0, 0x001D, ExifValue::Text(serial.to_string()));
+    writer.add_tag(Section::IFD0, 0x001F, ExifValue::Undefined(vec![b'0', b'1', b'0', b'0', 1, 0, 2, 0]));
+    writer.add_tag(Section::IFD0, 0x0083, ExifValue::Bytes(vec![0x0E]));
+    if let Some(shot_info) = shot_info {
+      writer.add_tag(Section::IFD0, 0x0091, ExifValue::Undefined(shot_info));
+    }
+    writer.add_tag(Section::IFD0, 0x0098, ExifValue::Undefined(lens_data));
+    writer.add_tag(Section::IFD0, 0x00A7, ExifValue::UInt(vec![shutter_count]));
+    let mut maker_note = b"Nikon\0\x02\x10\0\0".to_vec();
+    maker_note.extend(writer.write_tiff());
+
+    //the MakerNote uses its own byte order
+    let mut exif = ExifWriter::new(Endianness::Little);
+    exif.add_tag(Section::IFD0, 0x010F, ExifValue::Text("NIKON CORPORATION".to_string()));
+    exif.add_tag(Section::SubIFD, 0x927C, ExifValue::Undefined(maker_note));
+    exif.write_app1()
+  }
+
+  #[test]
+  fn test_decrypt_is_symmetric() {
+    let mut data = LENS_DATA.to_vec();
+    nikon_decrypt(&mut data, 6012345, 12345);
+    assert!(data != LENS_DATA.to_vec());
+    nikon_decrypt(&mut data, 6012345, 12345);
+    assert_eq!(data, LENS_DATA.to_vec());
+  }
+
+  #[test]
+  fn test_parse_nikon() {
+    let mut encrypted = LENS_DATA.to_vec();
+    nikon_decrypt(&mut encrypted[4 ..], 6012345, 12345);
+    let app1 = nikon_app1("6012345", 12345, encrypted);
+
+    let maker_note = read_maker_note(Cursor::new(&app1, Endianness::Little)).unwrap().unwrap();
+    assert_eq!(maker_note.vendor, Vendor::Nikon);
+    assert_eq!(maker_note.endianness(), Endianness::Big);
+    let nikon = NikonMakerNote::parse(&maker_note).unwrap();
+
+    assert_eq!(nikon.version, Some("0210".to_string()));
+    assert_eq!(nikon.iso, Some(400));
+    assert_eq!(nikon.shutter_count, Some(12345));
+    let vr = nikon.vr_info.unwrap();
+    assert_eq!(vr.vibration_reduction, Some(true));
+    assert_eq!(vr.vr_mode, Some(2));
+
+    let lens_data = nikon.lens_data.as_ref().unwrap();
+    assert_eq!(lens_data.version, "0204");
+    assert_eq!(nikon.lens_id(), Some("48 3C 50 8E 24 30 04 0E".to_string()));
+    let (min, max) = lens_data.focal_length_range();
+    assert_eq!((min.round(), max.round()), (50.0, 302.0));
+    assert_eq!(lens_data.focal_length_mm().map(|f| f.round()), Some(28.0));
+  }
+
+  #[test]
+  fn test_wrong_shutter_count() {
+    let mut encrypted = LENS_DATA.to_vec();
+    nikon_decrypt(&mut encrypted[4 ..], 6012345, 12345);
+    let app1 = nikon_app1("6012345", 54321, encrypted);
+    let maker_note = read_maker_note(Cursor::new(&app1, Endianness::Little)).unwrap().unwrap();
+    let nikon = NikonMakerNote::parse(&maker_note).unwrap();
+    //decrypted with the wrong shutter count, but the layout still parses
+    assert!(nikon.lens_id() != Some("48 3C 50 8E 24 30 04 0E".to_string()));
+  }
+
+  #[test]
+  fn test_d50_serial_key() {
+    let mut encrypted = LENS_DATA.to_vec();
+    nikon_decrypt(&mut encrypted[4 ..], 0x22, 12345);
+    let app1 = nikon_app1("NO= 1234abcd", 12345, encrypted);
+    let maker_note = read_maker_note(Cursor::new(&app1, Endianness::Little)).unwrap().unwrap();
+    let nikon = NikonMakerNote::parse_with_model(&maker_note, Some("NIKON D50")).unwrap();
+    assert_eq!(nikon.lens_id(), Some("48 3C 50 8E 24 30 04 0E".to_string()));
+    //other models use a different key
+    let nikon = NikonMakerNote::parse_with_model(&maker_note, Some("NIKON D70")).unwrap();
+    assert!(nikon.lens_id() != Some("48 3C 50 8E 24 30 04 0E".to_string()));
+  }
+
+  #[test]
+  fn test_shot_info() {
+    //a D80 ShotInfo, with the ShutterCount in the byte order of the MakerNote
+    let mut shot_info = b"02081.00a".to_vec();
+    shot_info.resize(586, 0);
+    shot_info.extend_from_slice(&12345u32.to_be_bytes());
+    let mut encrypted = shot_info.clone();
+    nikon_decrypt(&mut encrypted[4 ..], 6012345, 12345);
+    let app1 = nikon_app1_with_shot_info("6012345", 12345, LENS_DATA.to_vec(), Some(encrypted));
+    let maker_note = read_maker_note(Cursor::new(&app1, Endianness::Little)).unwrap().unwrap();
+    let nikon = NikonMakerNote::parse(&maker_note).unwrap();
+    let decoded = nikon.shot_info.unwrap();
+    assert_eq!(decoded.version, "0208");
+    assert_eq!(decoded.firmware_version, Some("1.00a".to_string()));
+    assert_eq!(decoded.shutter_count, Some(12345));
+    assert_eq!(decoded.data, shot_info);
+
+    //the 01xx versions are not encrypted
+    let plain = b"0103\x01\x02\x03\x04".to_vec();
+    let app1 = nikon_app1_with_shot_info("6012345", 12345, LENS_DATA.to_vec(), Some(plain.clone()));
+    let maker_note = read_maker_note(Cursor::new(&app1, Endianness::Little)).unwrap().unwrap();
+    let decoded = NikonMakerNote::parse(&maker_note).unwrap().shot_info.unwrap();
+    assert_eq!(decoded.data, plain);
+    assert_eq!((decoded.firmware_version, decoded.shutter_count), (None, None));
+  }
+}
@@ -0,0 +1,207 @@
+use ::error::ParseResult;
+use ::tag::ExifVariant;
+use super::{MakerNote, text};
+
+/** The names of the tags in the main MakerNote IFD. The tags of the nested
+    IFDs have their own numbering, which overlaps with these. */
+pub const OLYMPUS_TAG_NAMES : &[(u16, &str)] = &[
+  (0x0000, "MakerNoteVersion"),
+  (0x0200, "SpecialMode"),
+  (0x0201, "Quality"),
+  (0x0202, "Macro"),
+  (0x0204, "DigitalZoom"),
+  (0x0207, "CameraType"),
+  (0x0208, "TextInfo"),
+  (0x0209, "CameraID"),
+  (0x0404, "SerialNumber"),
+  (0x2010, "Equipment"),
+  (0x2020, "CameraSettings"),
+  (0x2030, "RawDevelopment"),
+  (0x2031, "RawDev2"),
+  (0x2040, "ImageProcessing"),
+  (0x2050, "FocusInfo"),
+  (0x3000, "RawInfo")
+];
+
+/** the Equipment IFD (0x2010), describing the camera body and lens */
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OlympusEquipment {
+  pub camera_type: Option<String>,
+  pub serial_number: Option<String>,
+  pub internal_serial_number: Option<String>,
+  pub body_firmware_version: Option<u32>,
+  /** the 6 raw bytes identifying the lens: maker, unused, model and sub-model */
+  pub lens_type: Option<[u8; 6]>,
+  pub lens_serial_number: Option<String>,
+  pub lens_model: Option<String>,
+  pub min_focal_length: Option<u32>,
+  pub max_focal_length: Option<u32>
+}
+
+/** the CameraSettings IFD (0x2020), with the codes as stored */
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OlympusCameraSettings {
+  pub exposure_mode: Option<u32>,
+  pub focus_mode: Option<u32>,
+  pub scene_mode: Option<u32>,
+  pub picture_mode: Option<u32>,
+  pub drive_mode: Option<u32>
+}
+
+/** The fields of an Olympus or OM System MakerNote that are most often asked for.
+    These cameras don't store a shutter count in the MakerNote. */
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OlympusMakerNote {
+  pub camera_type: Option<String>,
+  pub serial_number: Option<String>,
+  pub equipment: Option<OlympusEquipment>,
+  pub camera_settings: Option<OlympusCameraSettings>
+}
+
+impl OlympusEquipment {
+  fn parse(maker_note: &MakerNote, tag: &::tag::RawExifTag) -> ParseResult<OlympusEquipment> {
+    let mut equipment = OlympusEquipment::default();
+    for result in maker_note.sub_ifd(tag)? {
+      let (tag, _) = result?;
+      let value = &tag.value;
+      match tag.no {
+        0x0100 => equipment.camera_type = text(value),
+        0x0101 => equipment.serial_number = text(value),
+        0x0102 => equipment.internal_serial_number = text(value),
+        0x0104 => equipment.body_firmware_version = value.as_u32(),
+        0x0201 => equipment.lens_type = match *value {
+          ExifVariant::Bytes(b) if b.len() == 6 => {
+            let mut lens_type = [0u8; 6];
+            lens_type.copy_from_slice(b);
+            Some(lens_type)
+          },
+          _ => None
+        },
+        0x0202 => equipment.lens_serial_number = text(value),
+        0x0203 => equipment.lens_model = text(value),
+        0x0207 => equipment.min_focal_length = value.as_u32(),
+        0x0208 => equipment.max_focal_length = value.as_u32(),
+        _ => ()
+      }
+    }
+    Ok(equipment)
+  }
+}
+
+impl OlympusCameraSettings {
+  fn parse(maker_note: &MakerNote, tag: &::tag::RawExifTag) -> ParseResult<OlympusCameraSettings> {
+    let mut settings = OlympusCameraSettings::default();
+    for result in maker_note.sub_ifd(tag)? {
+      let (tag, _) = result?;
+      let value = tag.value.as_u32();
+      match tag.no {
+        0x0200 => settings.exposure_mode = value,
+        0x0301 => settings.focus_mode = value,
+        0x0509 => settings.scene_mode = value,
+        0x0520 => settings.picture_mode = value,
+        0x0600 => settings.drive_mode = value,
+        _ => ()
+      }
+    }
+    Ok(settings)
+  }
+}
+
+impl OlympusMakerNote {
+  pub fn parse(maker_note: &MakerNote) -> ParseResult<OlympusMakerNote> {
+    let mut olympus = OlympusMakerNote::default();
+    for result in maker_note.iter()? {
+      let (tag, _) = result?;
+      match tag.no {
+        0x0207 => olympus.camera_type = text(&tag.value),
+        0x0404 => olympus.serial_number = text(&tag.value),
+        0x2010 => olympus.equipment = Some(OlympusEquipment::parse(maker_note, &tag)?),
+        0x2020 => olympus.camera_settings = Some(OlympusCameraSettings::parse(maker_note, &tag)?),
+        _ => ()
+      }
+    }
+    Ok(olympus)
+  }
+
+  /** the serial number of the body, from the Equipment IFD on newer models */
+  pub fn body_serial_number(&self) -> Option<&str> {
+    self.equipment.as_ref()
+      .and_then(|e| e.serial_number.as_ref())
+      .or(self.serial_number.as_ref())
+      .map(|s| s.as_str())
+  }
+
+  /** the lens type as ExifTool prints it, like `0 05 10` */
+  pub fn lens_type_id(&self) -> Option<String> {
+    let lens_type = self.equipment.as_ref()?.lens_type?;
+    Some(format!("{:x} {:02x} {:02x}", lens_type[0], lens_type[2], lens_type[3]))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::Endianness;
+  use ::writer::ExifValue;
+  use ::makernote::Vendor;
+  use ::makernote::test_support::{write_ifd, tiff_with_maker_note, read_test_maker_note};
+  use super::OlympusMakerNote;
+
+  /** An "OLYMPUS\0II" MakerNote with an Equipment IFD referenced by offset,
+      and a CameraSettings IFD stored in the value of its tag */
+  fn olympus_maker_note() -> Vec<u8> {
+    let equipment = [
+      (0x0101, ExifValue::Text("BHP123456".to_string())),
+      (0x0201, ExifValue::Bytes(vec![0, 0, 0x05, 0x10, 0, 0])),
+      (0x0203, ExifValue::Text("OLYMPUS M.12-40mm F2.8".to_string())),
+      (0x0207, ExifValue::UShort(vec![12])),
+      (0x0208, ExifValue::UShort(vec![40]))
+    ];
+    let camera_settings = write_ifd(&[
+      (0x0200, ExifValue::UShort(vec![3])),
+      (0x0520, ExifValue::UShort(vec![1, 0]))
+    ], 0, Endianness::Little);
+
+    let main_tags = |equipment_offset: u32| vec![
+      (0x0207, ExifValue::Text("S0034".to_string())),
+      (0x2010, ExifValue::UInt(vec![equipment_offset])),
+      (0x2020, ExifValue::Undefined(camera_settings.clone()))
+    ];
+    let header = b"OLYMPUS\0II\x03\0".to_vec();
+    let equipment_offset = header.len() + write_ifd(&main_tags(0), 12, Endianness::Little).len();
+    let mut main_ifd = write_ifd(&main_tags(equipment_offset as u32), 12, Endianness::Little);
+    //the Equipment tag has the IFD format
+    main_ifd[2 + 12 + 2] = 13;
+
+    let mut maker_note = header;
+    maker_note.extend(main_ifd);
+    maker_note.extend(write_ifd(&equipment, equipment_offset, Endianness::Little));
+    maker_note
+  }
+
+  #[test]
+  fn test_parse_olympus() {
+    let maker_note = olympus_maker_note();
+    //the byte order of the MakerNote is independent of the TIFF
+    let tiff = tiff_with_maker_note(Endianness::Big, &maker_note);
+    let maker_note = read_test_maker_note(&tiff, Endianness::Big, "OLYMPUS IMAGING CORP.");
+    assert_eq!(maker_note.vendor, Vendor::Olympus);
+    let olympus = OlympusMakerNote::parse(&maker_note).unwrap();
+
+    assert_eq!(olympus.camera_type, Some("S0034".to_string()));
+    assert_eq!(olympus.body_serial_number(), Some("BHP123456"));
+    assert_eq!(olympus.lens_type_id(), Some("0 05 10".to_string()));
+    let equipment = olympus.equipment.as_ref().unwrap();
+    assert_eq!(equipment.lens_model, Some("OLYMPUS M.12-40mm F2.8".to_string()));
+    assert_eq!((equipment.min_focal_length, equipment.max_focal_length), (Some(12), Some(40)));
+    let settings = olympus.camera_settings.as_ref().unwrap();
+    assert_eq!(settings.exposure_mode, Some(3));
+    assert_eq!(settings.picture_mode, Some(1));
+  }
+
+  #[test]
+  fn test_olympus_tag_names() {
+    use ::tags::Section;
+    use ::names::tag_name;
+    assert_eq!(tag_name(Section::MakerNote(Vendor::Olympus), 0x2010), Some("Equipment"));
+  }
+}
@@ -0,0 +1,100 @@
+use ::error::ParseResult;
+use super::{MakerNote, text, bytes};
+
+pub const PANASONIC_TAG_NAMES : &[(u16, &str)] = &[
+  (0x0001, "ImageQuality"),
+  (0x0002, "FirmwareVersion"),
+  (0x0003, "WhiteBalance"),
+  (0x0007, "FocusMode"),
+  (0x000F, "AFAreaMode"),
+  (0x001A, "ImageStabilization"),
+  (0x001C, "MacroMode"),
+  (0x001F, "ShootingMode"),
+  (0x0020, "Audio"),
+  (0x0024, "FlashBias"),
+  (0x0025, "InternalSerialNumber"),
+  (0x0026, "PanasonicExifVersion"),
+  (0x0028, "ColorEffect"),
+  (0x0029, "TimeSincePowerOn"),
+  (0x002A, "BurstMode"),
+  (0x002B, "SequenceNumber"),
+  (0x002C, "ContrastMode"),
+  (0x002D, "NoiseReduction"),
+  (0x002E, "SelfTimer"),
+  (0x0030, "Rotation"),
+  (0x0031, "AFAssistLamp"),
+  (0x0032, "ColorMode"),
+  (0x0034, "OpticalZoomMode"),
+  (0x0035, "ConversionLens"),
+  (0x0044, "ColorTempKelvin"),
+  (0x0051, "LensType"),
+  (0x0052, "LensSerialNumber"),
+  (0x0053, "AccessoryType"),
+  (0x0054, "AccessorySerialNumber"),
+  (0x0089, "PhotoStyle"),
+  (0x009F, "ShutterType"),
+  (0x8000, "MakerNoteVersion"),
+  (0x8001, "SceneMode")
+];
+
+/** The fields of a Panasonic MakerNote that are most often asked for.
+    Panasonic doesn't store a shutter count in the MakerNote of JPEG files. */
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PanasonicMakerNote {
+  pub firmware_version: Option<String>,
+  pub internal_serial_number: Option<String>,
+  pub lens_type: Option<String>,
+  pub lens_serial_number: Option<String>,
+  pub sequence_number: Option<u32>
+}
+
+impl PanasonicMakerNote {
+  pub fn parse(maker_note: &MakerNote) -> ParseResult<PanasonicMakerNote> {
+    let mut panasonic = PanasonicMakerNote::default();
+    for result in maker_note.iter()? {
+      let (tag, _) = result?;
+      let value = &tag.value;
+      match tag.no {
+        //either digits as text or one byte per version component
+        0x0002 => panasonic.firmware_version = match bytes(value) {
+          Some(b) if b.iter().all(|n| *n < 10) =>
+            Some(b.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(".")),
+          _ => text(value)
+        },
+        0x0025 => panasonic.internal_serial_number = text(value),
+        0x002B => panasonic.sequence_number = value.as_u32(),
+        0x0051 => panasonic.lens_type = text(value),
+        0x0052 => panasonic.lens_serial_number = text(value),
+        _ => ()
+      }
+    }
+    Ok(panasonic)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::Endianness;
+  use ::writer::ExifValue;
+  use ::makernote::test_support::{write_ifd, tiff_with_maker_note, read_test_maker_note, TEST_MAKER_NOTE_OFFSET};
+  use super::PanasonicMakerNote;
+
+  #[test]
+  fn test_parse_panasonic() {
+    let mut maker_note = b"Panasonic\0\0\0".to_vec();
+    maker_note.extend(write_ifd(&[
+      (0x0002, ExifValue::Undefined(vec![0, 1, 2, 1])),
+      (0x0025, ExifValue::Undefined(b"F541505080124\0\0\0".to_vec())),
+      (0x0051, ExifValue::Text("LUMIX G VARIO 12-32/F3.5-5.6".to_string())),
+      (0x0052, ExifValue::Text("01AH2281000".to_string()))
+    ], TEST_MAKER_NOTE_OFFSET + 12, Endianness::Big));
+
+    let tiff = tiff_with_maker_note(Endianness::Big, &maker_note);
+    let maker_note = read_test_maker_note(&tiff, Endianness::Big, "Panasonic");
+    let panasonic = PanasonicMakerNote::parse(&maker_note).unwrap();
+    assert_eq!(panasonic.firmware_version, Some("0.1.2.1".to_string()));
+    assert_eq!(panasonic.internal_serial_number, Some("F541505080124".to_string()));
+    assert_eq!(panasonic.lens_type, Some("LUMIX G VARIO 12-32/F3.5-5.6".to_string()));
+    assert_eq!(panasonic.lens_serial_number, Some("01AH2281000".to_string()));
+  }
+}
@@ -0,0 +1,171 @@
+use ::error::ParseResult;
+use ::tag::ExifVariant;
+use super::{MakerNote, text, bytes};
+
+pub const SONY_TAG_NAMES : &[(u16, &str)] = &[
+  (0x0010, "CameraInfo"),
+  (0x0020, "FocusInfo"),
+  (0x0102, "Quality"),
+  (0x0104, "FlashExposureComp"),
+  (0x0105, "Teleconverter"),
+  (0x0112, "WhiteBalanceFineTune"),
+  (0x0114, "CameraSettings"),
+  (0x0115, "WhiteBalance"),
+  (0x2002, "Rating"),
+  (0x2004, "Contrast"),
+  (0x2005, "Saturation"),
+  (0x2006, "Sharpness"),
+  (0x200A, "HDR"),
+  (0x200B, "MultiFrameNoiseReduction"),
+  (0x2010, "Tag2010"),
+  (0x201B, "FocusMode"),
+  (0x3000, "ShotInfo"),
+  (0x9050, "Tag9050"),
+  (0xB000, "FileFormat"),
+  (0xB001, "SonyModelID"),
+  (0xB020, "CreativeStyle"),
+  (0xB021, "ColorTemperature"),
+  (0xB023, "SceneMode"),
+  (0xB024, "ZoneMatching"),
+  (0xB025, "DynamicRangeOptimizer"),
+  (0xB026, "ImageStabilization"),
+  (0xB027, "LensType"),
+  (0xB029, "ColorMode"),
+  (0xB02A, "LensSpec"),
+  (0xB02B, "FullImageSize"),
+  (0xB041, "ExposureMode"),
+  (0xB047, "JPEGQuality"),
+  (0xB04A, "SequenceNumber")
+];
+
+/** Deciphers the Sony tags 0x2010, 0x9050 and 0x94xx in place.
+    Sony enciphers each byte below 249 as its cube modulo 249. */
+pub fn sony_decipher(data: &mut [u8]) {
+  let mut table = [0u8; 256];
+  for (i, entry) in table.iter_mut().enumerate() {
+    *entry = i as u8;
+  }
+  for i in 0 .. 249u32 {
+    table[(i * i * i % 249) as usize] = i as u8;
+  }
+  for b in data.iter_mut() {
+    *b = table[*b as usize];
+  }
+}
+
+/** the value of the binary coded decimal digits in `bytes`, two digits per byte */
+fn bcd(bytes: &[u8]) -> u32 {
+  bytes.iter().fold(0, |n, b| n * 100 + (b >> 4) as u32 * 10 + (b & 0xF) as u32)
+}
+
+/** The fields of a Sony MakerNote that are most often asked for.
+    The serial number is only stored in enciphered tags whose layout
+    differs per model, so it is not decoded here. */
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SonyMakerNote {
+  pub model_id: Option<u32>,
+  pub creative_style: Option<String>,
+  pub lens_type: Option<u32>,
+  /** the 8 raw bytes of the LensSpec tag */
+  pub lens_spec: Option<Vec<u8>>,
+  /** read from the deciphered tag 0x9050, as written by
+      the SLT, NEX and ILCE models */
+  pub shutter_count: Option<u32>
+}
+
+impl SonyMakerNote {
+  pub fn parse(maker_note: &MakerNote) -> ParseResult<SonyMakerNote> {
+    let mut sony = SonyMakerNote::default();
+    for result in maker_note.iter()? {
+      let (tag, _) = result?;
+      let value = &tag.value;
+      match tag.no {
+        0x9050 => sony.shutter_count = bytes(value).and_then(|b| {
+          let mut data = b.get(0x3A .. 0x3E)?.to_vec();
+          sony_decipher(&mut data);
+          let n = data.iter().rev().fold(0u32, |n, b| n << 8 | *b as u32);
+          Some(n & 0x00FF_FFFF)
+        }),
+        0xB001 => sony.model_id = value.as_u32(),
+        0xB020 => sony.creative_style = text(value),
+        0xB027 => sony.lens_type = value.as_u32(),
+        0xB02A => sony.lens_spec = match *value {
+          ExifVariant::Bytes(b) if b.len() == 8 => Some(b.to_vec()),
+          _ => None
+        },
+        _ => ()
+      }
+    }
+    Ok(sony)
+  }
+
+  /** the shortest and longest focal length of the lens in mm, from the LensSpec */
+  pub fn focal_length_range(&self) -> Option<(f64, f64)> {
+    let spec = self.lens_spec.as_ref()?;
+    let min = bcd(&spec[1 .. 3]);
+    let max = bcd(&spec[3 .. 5]);
+    if min == 0 { None } else { Some((min as f64, max as f64)) }
+  }
+
+  /** the largest aperture at the shortest and longest focal length, from the LensSpec */
+  pub fn aperture_range(&self) -> Option<(f64, f64)> {
+    let spec = self.lens_spec.as_ref()?;
+    let wide = bcd(&spec[5 .. 6]);
+    let tele = bcd(&spec[6 .. 7]);
+    if wide == 0 { None } else { Some((wide as f64 / 10.0, tele as f64 / 10.0)) }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::Endianness;
+  use ::writer::ExifValue;
+  use ::makernote::test_support::{write_ifd, tiff_with_maker_note, read_test_maker_note, TEST_MAKER_NOTE_OFFSET};
+  use super::{SonyMakerNote, sony_decipher};
+
+  /** the inverse of `sony_decipher` */
+  fn encipher(data: &mut [u8]) {
+    for b in data.iter_mut() {
+      if *b < 249 {
+        let n = *b as u32;
+        *b = (n * n * n % 249) as u8;
+      }
+    }
+  }
+
+  #[test]
+  fn test_decipher() {
+    let mut data : Vec<u8> = (0 .. 256).map(|n| n as u8).collect();
+    encipher(&mut data);
+    assert_ne!(data[2], 2);
+    sony_decipher(&mut data);
+    assert_eq!(data, (0 .. 256).map(|n| n as u8).collect::<Vec<u8>>());
+  }
+
+  #[test]
+  fn test_parse_sony() {
+    let mut tag_9050 = vec![0u8; 0x40];
+    tag_9050[0x3A .. 0x3E].copy_from_slice(&[0x39, 0x30, 0x00, 0xFF]);
+    encipher(&mut tag_9050);
+
+    //offsets are relative to the TIFF header, the IFD starts after the 12 byte header
+    let mut maker_note = b"SONY DSC \0\0\0".to_vec();
+    maker_note.extend(write_ifd(&[
+      (0x9050, ExifValue::Undefined(tag_9050)),
+      (0xB001, ExifValue::UShort(vec![311])),
+      (0xB020, ExifValue::Text("Standard".to_string())),
+      (0xB027, ExifValue::UInt(vec![32849])),
+      (0xB02A, ExifValue::Bytes(vec![0, 0, 0x18, 0, 0x55, 0x35, 0x56, 0]))
+    ], TEST_MAKER_NOTE_OFFSET + 12, Endianness::Little));
+
+    let tiff = tiff_with_maker_note(Endianness::Little, &maker_note);
+    let maker_note = read_test_maker_note(&tiff, Endianness::Little, "SONY");
+    let sony = SonyMakerNote::parse(&maker_note).unwrap();
+    assert_eq!(sony.model_id, Some(311));
+    assert_eq!(sony.creative_style, Some("Standard".to_string()));
+    assert_eq!(sony.lens_type, Some(32849));
+    assert_eq!(sony.focal_length_range(), Some((18.0, 55.0)));
+    assert_eq!(sony.aperture_range(), Some((3.5, 5.6)));
+    assert_eq!(sony.shutter_count, Some(12345));
+  }
+}
@@ -0,0 +1,74 @@
+//builders for the MakerNote tests, shared by the vendor modules
+use ::cursor::{Cursor, Endianness};
+use ::tag::read_exif_tag;
+use ::writer::{ExifValue, write_u16, write_u32, write_ifd as write_entries};
+use super::MakerNote;
+
+/** Writes an IFD as it is stored `offset` bytes after its offset base,
+    followed by the values that don't fit in the entries */
+pub fn write_ifd(tags: &[(u16, ExifValue)], offset: usize, endianness: Endianness) -> Vec<u8> {
+  let entries : Vec<(u16, &ExifValue)> = tags.iter().map(|&(no, ref value)| (no, value)).collect();
+  let mut out = Vec::new();
+  write_entries(&mut out, &entries, offset, 0, endianness);
+  out
+}
+
+/** where `tiff_with_maker_note` puts the MakerNote value */
+pub const TEST_MAKER_NOTE_OFFSET : usize = 20;
+
+/** A TIFF structure with a MakerNote entry right after the header,
+    its value stored at `TEST_MAKER_NOTE_OFFSET` */
+pub fn tiff_with_maker_note(endianness: Endianness, maker_note: &[u8]) -> Vec<u8> {
+  let mut tiff = match endianness {
+    Endianness::Little => b"II\x2A\0".to_vec(),
+    Endianness::Big => b"MM\0\x2A".to_vec()
+  };
+  write_u32(&mut tiff, 8, endianness);
+  write_u16(&mut tiff, 0x927C, endianness);
+  write_u16(&mut tiff, 7, endianness);
+  write_u32(&mut tiff, maker_note.len() as u32, endianness);
+  write_u32(&mut tiff, TEST_MAKER_NOTE_OFFSET as u32, endianness);
+  tiff.extend_from_slice(maker_note);
+  tiff
+}
+
+/** reads the MakerNote entry of a TIFF structure made by `tiff_with_maker_note` */
+pub fn read_test_maker_note<'a>(tiff: &'a [u8], endianness: Endianness, make: &str) -> MakerNote<'a> {
+  let tiff_cursor = Cursor::new(tiff, endianness);
+  let entry = tiff_cursor.with_skip_or_fail(8).unwrap();
+  let tag = read_exif_tag(entry, tiff_cursor).unwrap();
+  MakerNote::new(&tag, Some(make)).unwrap().expect("MakerNote should be detected")
+}
+
+/** A binary plist with a single dictionary of integers, like the Apple RunTime tag */
+pub fn int_dict_bplist(entries: &[(&str, i64)]) -> Vec<u8> {
+  let mut objects : Vec<Vec<u8>> = Vec::new();
+  let len = entries.len();
+  let mut dict = vec![0xD0 | len as u8];
+  dict.extend((1 .. 2 * len + 1).map(|i| i as u8));
+  objects.push(dict);
+  for &(key, _) in entries {
+    let mut object = vec![0x50 | key.len() as u8];
+    object.extend_from_slice(key.as_bytes());
+    objects.push(object);
+  }
+  for &(_, value) in entries {
+    let mut object = vec![0x13];
+    object.extend_from_slice(&value.to_be_bytes());
+    objects.push(object);
+  }
+
+  let mut data = b"bplist00".to_vec();
+  let mut offsets = Vec::new();
+  for object in objects {
+    offsets.push(data.len() as u8);
+    data.extend(object);
+  }
+  let table_offset = data.len() as u64;
+  data.extend(&offsets);
+  data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 1, 1]);
+  data.extend_from_slice(&(offsets.len() as u64).to_be_bytes());
+  data.extend_from_slice(&0u64.to_be_bytes());
+  data.extend_from_slice(&table_offset.to_be_bytes());
+  data
+}
@@ -0,0 +1,69 @@
+//the per-round shift amounts
+const SHIFTS : [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+/** The MD5 digest of `data`, as used for the GUID of extended XMP.
+    Not meant for anything security related. */
+pub fn md5(data: &[u8]) -> [u8; 16] {
+  let mut k = [0u32; 64];
+  for (i, k) in k.iter_mut().enumerate() {
+    *k = (((i + 1) as f64).sin().abs() * 4_294_967_296.0) as u32;
+  }
+
+  let mut message = data.to_vec();
+  message.push(0x80);
+  while message.len() % 64 != 56 {
+    message.push(0);
+  }
+  message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+  let mut state : [u32; 4] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476];
+  for block in message.chunks(64) {
+    let mut words = [0u32; 16];
+    for (i, word) in words.iter_mut().enumerate() {
+      *word = u32::from_le_bytes([block[i * 4], block[i * 4 + 1], block[i * 4 + 2], block[i * 4 + 3]]);
+    }
+    let [mut a, mut b, mut c, mut d] = state;
+    for i in 0 .. 64 {
+      let (f, g) = match i / 16 {
+        0 => ((b & c) | (!b & d), i),
+        1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+        2 => (b ^ c ^ d, (3 * i + 5) % 16),
+        _ => (c ^ (b | !d), (7 * i) % 16)
+      };
+      let shift = SHIFTS[(i / 16) * 4 + i % 4];
+      let rotated = a.wrapping_add(f).wrapping_add(k[i]).wrapping_add(words[g]).rotate_left(shift);
+      a = d;
+      d = c;
+      c = b;
+      b = b.wrapping_add(rotated);
+    }
+    state[0] = state[0].wrapping_add(a);
+    state[1] = state[1].wrapping_add(b);
+    state[2] = state[2].wrapping_add(c);
+    state[3] = state[3].wrapping_add(d);
+  }
+
+  let mut digest = [0u8; 16];
+  for (i, word) in state.iter().enumerate() {
+    digest[i * 4 .. i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+  }
+  digest
+}
+
+/** the digest as uppercase hex digits, the form XMP uses */
+pub fn md5_hex(data: &[u8]) -> String {
+  md5(data).iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::md5_hex;
+
+  #[test]
+  fn test_md5() {
+    assert_eq!(md5_hex(b""), "D41D8CD98F00B204E9800998ECF8427E");
+    assert_eq!(md5_hex(b"The quick brown fox jumps over the lazy dog"), "9E107D9D372BB6826BD81D3542A419D6");
+    //more than one block
+    assert_eq!(md5_hex(&[b'a'; 100]), "36A92CC94A9E0FA21F625F8BFB007ADF");
+  }
+}
@@ -0,0 +1,250 @@
+use ::cursor::{Cursor, Endianness};
+use ::error::{ParseError, ParseResult};
+use ::jpeg::{JPEGSegmentIterator, SegmentMarker};
+use ::section::read_section;
+use ::tag::ExifVariant;
+use ::tags::read_tiff_header;
+
+/** the start of an APP2 segment containing the Multi-Picture Format index */
+pub const MPF_SEGMENT_HEADER : &[u8] = b"MPF\0";
+const MP_ENTRY_SIZE : usize = 16;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MpImageType {
+  /** also used for the gain map of Ultra HDR images */
+  Undefined,
+  LargeThumbnailVga,
+  LargeThumbnailFullHd,
+  Panorama,
+  /** one image of a stereo pair */
+  Disparity,
+  MultiAngle,
+  BaselinePrimary,
+  Other(u32)
+}
+
+impl MpImageType {
+  fn from_code(code: u32) -> MpImageType {
+    match code {
+      0x000000 => MpImageType::Undefined,
+      0x010001 => MpImageType::LargeThumbnailVga,
+      0x010002 => MpImageType::LargeThumbnailFullHd,
+      0x020001 => MpImageType::Panorama,
+      0x020002 => MpImageType::Disparity,
+      0x020003 => MpImageType::MultiAngle,
+      0x030000 => MpImageType::BaselinePrimary,
+      _ => MpImageType::Other(code)
+    }
+  }
+}
+
+/** An entry of the MPEntry tag, describing one of the images in the file */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MpEntry {
+  /** the flags, the image data format and the type code */
+  pub attributes: u32,
+  pub image_type: MpImageType,
+  pub size: u32,
+  /** the offset from the TIFF header of the MPF segment, or 0 for the first image */
+  pub offset: u32,
+  /** the 1-based entry numbers of the dependent images, or 0 */
+  pub dependent_images: (u16, u16)
+}
+
+impl MpEntry {
+  pub fn is_dependent_parent(&self) -> bool {
+    self.attributes & 0x8000_0000 != 0
+  }
+
+  pub fn is_dependent_child(&self) -> bool {
+    self.attributes & 0x4000_0000 != 0
+  }
+
+  pub fn is_representative(&self) -> bool {
+    self.attributes & 0x2000_0000 != 0
+  }
+
+  /** a data format of 0 is JPEG, which is the only one defined */
+  pub fn is_jpeg(&self) -> bool {
+    (self.attributes >> 24) & 0x7 == 0
+  }
+}
+
+/** The MP Index IFD of a Multi-Picture Format file */
+#[derive(Debug, PartialEq, Clone)]
+pub struct MpIndex {
+  /** the MPFVersion, like "0100" */
+  pub version: Option<String>,
+  pub number_of_images: Option<u32>,
+  pub entries: Vec<MpEntry>,
+  /** the offset of the TIFF header of the MPF segment in the file, which the entry offsets are relative to */
+  pub offset_base: usize
+}
+
+impl MpIndex {
+  /** The data of the image of an entry, as a slice of `jpeg`,
+      which must be the file the index was read from */
+  pub fn image<'a>(&self, jpeg: &'a [u8], entry: &MpEntry) -> ParseResult<&'a [u8]> {
+    let out_of_bounds = ParseError::ValueOutOfBounds { offset: entry.offset as usize };
+    let start = if entry.offset == 0 {
+      0
+    } else {
+      self.offset_base.checked_add(entry.offset as usize).ok_or(out_of_bounds)?
+    };
+    match start.checked_add(entry.size as usize) {
+      Some(end) if end <= jpeg.len() => Ok(&jpeg[start .. end]),
+      _ => Err(ParseError::ValueOutOfBounds { offset: start })
+    }
+  }
+
+  /** the images after the primary one, like previews, stereo pairs and gain maps */
+  pub fn secondary_images<'a>(&self, jpeg: &'a [u8]) -> ParseResult<Vec<(MpEntry, &'a [u8])>> {
+    self.entries.iter()
+      .filter(|e| e.offset != 0)
+      .map(|e| self.image(jpeg, e).map(|data| (*e, data)))
+      .collect()
+  }
+}
+
+fn read_mp_entry(cursor: &mut Cursor) -> ParseResult<MpEntry> {
+  let attributes : u32 = cursor.read_num_or_fail()?;
+  let size : u32 = cursor.read_num_or_fail()?;
+  let offset : u32 = cursor.read_num_or_fail()?;
+  let dependent_1 : u16 = cursor.read_num_or_fail()?;
+  let dependent_2 : u16 = cursor.read_num_or_fail()?;
+  Ok(MpEntry {
+    attributes,
+    image_type: MpImageType::from_code(attributes & 0x00FF_FFFF),
+    size,
+    offset,
+    dependent_images: (dependent_1, dependent_2)
+  })
+}
+
+/** Returns the MP Index IFD of an APP2 segment, or `None` if the segment holds something else.
+    `offset_base` of the result is relative to `jpeg_cursor`, the file the segment was read from. */
+pub fn read_mp_index<'a>(app2_cursor: Cursor<'a>, jpeg_cursor: &Cursor<'a>) -> ParseResult<Option<MpIndex>> {
+  let mut cursor = app2_cursor;
+  if cursor.read_bytes(MPF_SEGMENT_HEADER.len()) != Some(MPF_SEGMENT_HEADER) {
+    return Ok(None);
+  }
+  let tiff_marker = read_tiff_header(cursor)?;
+  let ifd_offset : u32 = tiff_marker.with_skip_or_fail(2 + 2)?.read_num_or_fail()?;
+  let ifd = tiff_marker.with_skip_or_fail(ifd_offset as usize)?;
+
+  let mut index = MpIndex {
+    version: None,
+    number_of_images: None,
+    entries: Vec::new(),
+    offset_base: tiff_marker.offset_within(jpeg_cursor)
+      .ok_or(ParseError::ValueOutOfBounds { offset: 0 })?
+  };
+  for tag in read_section(ifd, tiff_marker)? {
+    let tag = tag?;
+    match (tag.no, &tag.value) {
+      (0xB000, &ExifVariant::Undefined(version)) =>
+        index.version = Some(String::from_utf8_lossy(version).into_owned()),
+      (0xB001, value) => index.number_of_images = value.as_u32(),
+      (0xB002, &ExifVariant::Undefined(_)) => {
+        let mut entries = tag.value_cursor();
+        for _ in 0 .. entries.len() / MP_ENTRY_SIZE {
+          index.entries.push(read_mp_entry(&mut entries)?);
+        }
+      },
+      _ => ()
+    }
+  }
+  Ok(Some(index))
+}
+
+/** Reads the MP Index of a JPEG file, if it has an MPF segment */
+pub fn read_mpf(jpeg: &[u8]) -> ParseResult<Option<MpIndex>> {
+  let jpeg_cursor = Cursor::new(jpeg, Endianness::Big);
+  for result in JPEGSegmentIterator::new(jpeg_cursor) {
+    let (marker, segment) = result?;
+    if marker != SegmentMarker::APP(2) {
+      continue;
+    }
+    if let Some(index) = read_mp_index(segment, &jpeg_cursor)? {
+      return Ok(Some(index));
+    }
+  }
+  Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::error::ParseError;
+  use ::jpeg::SegmentMarker;
+  use ::jpeg_editor::JPEGEditor;
+  use ::test_fixtures::JPEG_SAMPLE;
+  use super::{read_mpf, read_mp_index, MpIndex, MpImageType, MPF_SEGMENT_HEADER};
+
+  const GAIN_MAP : &[u8] = b"\xFF\xD8\xFF\xD9";
+
+  fn push_entry(out: &mut Vec<u8>, attributes: u32, size: u32, offset: u32) {
+    out.extend_from_slice(&attributes.to_be_bytes());
+    out.extend_from_slice(&size.to_be_bytes());
+    out.extend_from_slice(&offset.to_be_bytes());
+    out.extend_from_slice(&[0, 0, 0, 0]);
+  }
+
+  fn mpf_segment(primary_size: u32, gain_map_offset: u32) -> Vec<u8> {
+    let mut segment = MPF_SEGMENT_HEADER.to_vec();
+    segment.extend_from_slice(b"MM\x00\x2A\x00\x00\x00\x08");
+    segment.extend_from_slice(b"\x00\x03");
+    segment.extend_from_slice(b"\xB0\x00\x00\x07\x00\x00\x00\x040100");
+    segment.extend_from_slice(b"\xB0\x01\x00\x04\x00\x00\x00\x01\x00\x00\x00\x02");
+    //the entries follow the IFD, at 8 + 2 + 3 * 12 + 4 from the TIFF header
+    segment.extend_from_slice(b"\xB0\x02\x00\x07\x00\x00\x00\x20\x00\x00\x00\x32");
+    segment.extend_from_slice(b"\x00\x00\x00\x00");
+    push_entry(&mut segment, 0x2003_0000, primary_size, 0);
+    push_entry(&mut segment, 0x0000_0000, GAIN_MAP.len() as u32, gain_map_offset);
+    segment
+  }
+
+  #[test]
+  fn test_read_mpf() {
+    assert_eq!(read_mpf(JPEG_SAMPLE), Ok(None));
+
+    //write once to find where the segment ends up, then fill in the real offsets
+    let mut editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    editor.insert_segment(2, SegmentMarker::APP(2), mpf_segment(0, 0));
+    let primary_size = editor.write().unwrap().len();
+    let mut jpeg = editor.write().unwrap();
+    let offset_base = jpeg.windows(4).position(|w| w == MPF_SEGMENT_HEADER).unwrap() + 4;
+    editor.replace_segment(2, mpf_segment(primary_size as u32, (primary_size - offset_base) as u32));
+    jpeg = editor.write().unwrap();
+    jpeg.extend_from_slice(GAIN_MAP);
+
+    let index = read_mpf(&jpeg).unwrap().unwrap();
+    assert_eq!(index.version, Some("0100".to_string()));
+    assert_eq!(index.number_of_images, Some(2));
+    assert_eq!(index.offset_base, offset_base);
+    assert_eq!(index.entries.len(), 2);
+    let primary = &index.entries[0];
+    assert_eq!(primary.image_type, MpImageType::BaselinePrimary);
+    assert!(primary.is_representative() && primary.is_jpeg());
+    assert_eq!(index.image(&jpeg, primary).unwrap(), &jpeg[.. primary_size]);
+
+    let secondary = index.secondary_images(&jpeg).unwrap();
+    assert_eq!(secondary.len(), 1);
+    assert_eq!(secondary[0].0.image_type, MpImageType::Undefined);
+    assert_eq!(secondary[0].1, GAIN_MAP);
+
+    //the appended image was cut off
+    assert!(index.secondary_images(&jpeg[.. jpeg.len() - 1]).is_err());
+    let mut huge = secondary[0].0;
+    huge.size = u32::MAX;
+    let far_index = MpIndex { offset_base: usize::MAX, ..index.clone() };
+    assert!(far_index.image(&jpeg, &huge).is_err());
+    assert!(index.image(&jpeg, &huge).is_err());
+
+    //the segment is not in the file passed as the offset base
+    let segment = mpf_segment(0, 0);
+    let other = Cursor::new(GAIN_MAP, Endianness::Big);
+    assert_eq!(read_mp_index(Cursor::new(&segment, Endianness::Big), &other),
+      Err(ParseError::ValueOutOfBounds { offset: 0 }));
+  }
+}
@@ -0,0 +1,134 @@
+use ::cursor::Cursor;
+use ::error::{ParseError, ParseResult};
+use ::tag::RawExifTag;
+use ::writer::ExifValue;
+
+/** Bytes to overwrite in the original data, for changing a tag value
+    without moving anything else around. Offsets stored elsewhere,
+    like in maker notes, stay valid. */
+#[derive(Debug, PartialEq, Clone)]
+pub struct TagPatch {
+  pub offset: usize,
+  pub bytes: Vec<u8>
+}
+
+impl TagPatch {
+  /** Creates a patch replacing the value of `tag` with `value`.
+      The new value needs to have the same format and byte size as the old one.
+      The offset is relative to `base`, see `RawExifTag::value_range`. */
+  pub fn new<'a>(tag: &RawExifTag<'a>, value: &ExifValue, base: &Cursor<'a>) -> ParseResult<TagPatch> {
+    if value.format() != tag.format {
+      return Err(ParseError::InvalidValueFormat { format: value.format().number() });
+    }
+    let (start, end) = tag.value_range(base).ok_or(ParseError::TagNotInData)?;
+    if value.byte_size() != end - start {
+      return Err(ParseError::PatchSizeMismatch {
+        expected: end - start,
+        actual: value.byte_size()
+      });
+    }
+    let mut bytes = Vec::with_capacity(end - start);
+    value.write(&mut bytes, tag.endianness());
+    Ok(TagPatch { offset: start, bytes })
+  }
+
+  /** the offset after the patched bytes, failing if it doesn't fit in a `usize` */
+  fn end(&self) -> ParseResult<usize> {
+    self.offset.checked_add(self.bytes.len()).ok_or(ParseError::TagNotInData)
+  }
+
+  pub fn apply(&self, data: &mut [u8]) -> ParseResult<()> {
+    let end = self.end()?;
+    if end > data.len() {
+      return Err(ParseError::ValueOutOfBounds { offset: self.offset });
+    }
+    data[self.offset .. end].copy_from_slice(&self.bytes);
+    Ok(())
+  }
+}
+
+/** applies all patches, or none if any of them doesn't fit in `data` */
+pub fn apply_patches(data: &mut [u8], patches: &[TagPatch]) -> ParseResult<()> {
+  for patch in patches {
+    if patch.end()? > data.len() {
+      return Err(ParseError::ValueOutOfBounds { offset: patch.offset });
+    }
+  }
+  for patch in patches {
+    patch.apply(data)?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::tags::{read_tags, Section};
+  use ::writer::ExifValue;
+  use ::error::ParseError;
+  use ::test_fixtures::{JPEG_SAMPLE, JPEG_SAMPLE_EXIF_OFFSET};
+  use super::{TagPatch, apply_patches};
+
+  fn sample_patches(edits: Vec<(Section, u16, ExifValue)>) -> Result<Vec<TagPatch>, ParseError> {
+    let file = Cursor::new(JPEG_SAMPLE, Endianness::Big);
+    let cursor = file.with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).unwrap();
+    let tags : Vec<_> = read_tags(cursor).unwrap().map(|r| r.unwrap()).collect();
+    edits.iter().map(|&(section, no, ref value)| {
+      let tag = tags.iter().find(|&&(ref t, s)| s == section && t.no == no).map(|(t, _)| t).unwrap();
+      TagPatch::new(tag, value, &file)
+    }).collect()
+  }
+
+  fn sample_tag_value(data: &[u8], section: Section, no: u16) -> String {
+    let cursor = Cursor::new(data, Endianness::Big)
+      .with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).unwrap();
+    read_tags(cursor).unwrap()
+      .map(|r| r.unwrap())
+      .find(|&(ref tag, s)| s == section && tag.no == no)
+      .map(|(tag, _)| tag.value.to_string())
+      .unwrap()
+  }
+
+  #[test]
+  fn test_patch_in_place() {
+    let patches = sample_patches(vec![
+      (Section::IFD0, 0x0112, ExifValue::UShort(vec![6])),
+      (Section::SubIFD, 0x9003, ExifValue::Text("2014:06:11 16:22:36".to_string())),
+      (Section::GPS, 0x0002, ExifValue::UIntFraction(vec![(0, 1), (0, 1), (0, 1)]))
+    ]).unwrap();
+    //orientation is stored inline in the entry
+    assert_eq!(patches[0], TagPatch { offset: 24 + 6 + 8 + 2 + 2 * 12 + 8, bytes: vec![0, 6] });
+
+    let mut data = JPEG_SAMPLE.to_vec();
+    apply_patches(&mut data, &patches).unwrap();
+    assert_eq!(data.len(), JPEG_SAMPLE.len());
+    assert_eq!(sample_tag_value(&data, Section::IFD0, 0x0112), "6");
+    assert_eq!(sample_tag_value(&data, Section::SubIFD, 0x9003), "2014:06:11 16:22:36");
+    assert_eq!(sample_tag_value(&data, Section::SubIFD, 0x9004), "2013:05:10 15:21:35");
+    assert_eq!(sample_tag_value(&data, Section::GPS, 0x0002), "0/1 0/1 0/1");
+  }
+
+  #[test]
+  fn test_patch_size_must_match() {
+    let result = sample_patches(vec![
+      (Section::SubIFD, 0x9003, ExifValue::Text("2014:06:11".to_string()))
+    ]);
+    assert_eq!(result, Err(ParseError::PatchSizeMismatch { expected: 20, actual: 11 }));
+
+    let result = sample_patches(vec![
+      (Section::IFD0, 0x0112, ExifValue::UInt(vec![6]))
+    ]);
+    assert_eq!(result, Err(ParseError::InvalidValueFormat { format: 4 }));
+  }
+
+  #[test]
+  fn test_patch_out_of_bounds() {
+    let patch = TagPatch { offset: 3, bytes: vec![1, 2] };
+    let mut data = [0u8; 4];
+    assert_eq!(apply_patches(&mut data, &[patch]), Err(ParseError::ValueOutOfBounds { offset: 3 }));
+    assert_eq!(data, [0u8; 4]);
+    let overflowing = TagPatch { offset: usize::MAX, bytes: vec![1, 2] };
+    assert_eq!(overflowing.apply(&mut data), Err(ParseError::TagNotInData));
+    assert_eq!(apply_patches(&mut data, &[overflowing]), Err(ParseError::TagNotInData));
+  }
+}
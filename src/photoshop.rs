@@ -0,0 +1,107 @@
+use ::cursor::{Cursor, Endianness};
+use ::error::{ParseError, ParseResult};
+
+/** the start of an APP13 segment containing Photoshop image resources */
+pub const PHOTOSHOP_SEGMENT_HEADER : &[u8] = b"Photoshop 3.0\0";
+/** the resource holding the IPTC-IIM records */
+pub const IPTC_RESOURCE_ID : u16 = 0x0404;
+/** the resource holding the MD5 digest of the IPTC-IIM records when they were last written */
+pub const IPTC_DIGEST_RESOURCE_ID : u16 = 0x0425;
+
+/** A Photoshop image resource block, usually starting with "8BIM" */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ImageResource<'a> {
+  pub id: u16,
+  /** the name, mostly empty, in no particular encoding */
+  pub name: &'a [u8],
+  pub data: &'a [u8]
+}
+
+pub struct ImageResourceIterator<'a> {
+  cursor: Cursor<'a>,
+  failed: bool
+}
+
+impl<'a> ImageResourceIterator<'a> {
+  fn try_next(&mut self) -> ParseResult<ImageResource<'a>> {
+    let signature = self.cursor.read_bytes_or_fail(4)?;
+    //other signatures than 8BIM are used by some older applications
+    match signature {
+      b"8BIM" | b"MeSa" | b"PHUT" | b"AgHg" | b"DCSR" => (),
+      _ => return Err(ParseError::InvalidImageResource {
+        signature: [signature[0], signature[1], signature[2], signature[3]]
+      })
+    }
+    let id : u16 = self.cursor.read_num_or_fail()?;
+    //the name is a pascal string, padded to an even size including the length byte
+    let name_len : u8 = self.cursor.read_num_or_fail()?;
+    let name = self.cursor.read_bytes_or_fail(name_len as usize)?;
+    if name_len.is_multiple_of(2) {
+      self.cursor = self.cursor.with_skip_or_fail(1)?;
+    }
+    let len : u32 = self.cursor.read_num_or_fail()?;
+    let data = self.cursor.read_bytes_or_fail(len as usize)?;
+    //the data is padded to an even size too, which some writers leave out at the end
+    if !len.is_multiple_of(2) {
+      self.cursor = self.cursor.with_skip(1).unwrap_or(self.cursor);
+    }
+    Ok(ImageResource { id, name, data })
+  }
+}
+
+impl<'a> Iterator for ImageResourceIterator<'a> {
+  type Item = ParseResult<ImageResource<'a>>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.failed || self.cursor.len() == 0 {
+      return None;
+    }
+    let result = self.try_next();
+    self.failed = result.is_err();
+    Some(result)
+  }
+}
+
+/** Iterates the image resources of an APP13 segment,
+    or returns `None` if the segment holds something else */
+pub fn read_image_resources<'a>(app13_cursor: Cursor<'a>) -> Option<ImageResourceIterator<'a>> {
+  let mut cursor = app13_cursor.with_endianness(Endianness::Big);
+  if cursor.read_bytes(PHOTOSHOP_SEGMENT_HEADER.len()) != Some(PHOTOSHOP_SEGMENT_HEADER) {
+    return None;
+  }
+  Some(ImageResourceIterator { cursor, failed: false })
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::error::ParseError;
+  use ::md5::md5;
+  use ::test_fixtures::{JPEG_SAMPLE, expected_segments};
+  use super::{read_image_resources, IPTC_RESOURCE_ID, IPTC_DIGEST_RESOURCE_ID};
+
+  #[test]
+  fn test_sample_resources() {
+    let segment = &expected_segments()[4];
+    let cursor = Cursor::new(&JPEG_SAMPLE[segment.offset .. segment.offset + segment.len], Endianness::Little);
+    let resources : Vec<_> = read_image_resources(cursor).unwrap()
+      .map(|r| r.unwrap())
+      .collect();
+    assert_eq!(resources.len(), 2);
+    assert_eq!(resources[0].id, IPTC_RESOURCE_ID);
+    assert_eq!(resources[0].name, b"");
+    assert_eq!(resources[0].data.len(), 63);
+    assert_eq!(resources[1].id, IPTC_DIGEST_RESOURCE_ID);
+    //the digest shows the IPTC data was not changed since it was written
+    assert_eq!(&md5(resources[0].data)[..], resources[1].data);
+  }
+
+  #[test]
+  fn test_invalid_signature() {
+    let cursor = Cursor::new(b"Photoshop 3.0\08BIX\x04\x04", Endianness::Big);
+    let mut resources = read_image_resources(cursor).unwrap();
+    assert_eq!(resources.next(), Some(Err(ParseError::InvalidImageResource { signature: *b"8BIX" })));
+    assert_eq!(resources.next(), None);
+    assert!(read_image_resources(Cursor::new(b"Adobe_CM", Endianness::Big)).is_none());
+  }
+}
@@ -0,0 +1,338 @@
+use ::adobe::ADOBE_SEGMENT_HEADER;
+use ::cursor::{Cursor, Endianness};
+use ::error::ParseResult;
+use ::icc::ICC_SEGMENT_HEADER;
+use ::jpeg::SegmentMarker;
+use ::jpeg_editor::{JPEGEditor, EditorSegment};
+use ::tags::{read_tags, Section};
+use ::writer::{ExifWriter, ExifValue, is_offset_tag};
+use ::xmp::{read_xmp, read_xmp_packet, Xmp, NS_EXIF, XMP_SEGMENT_HEADER, EXTENDED_XMP_SEGMENT_HEADER};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrubPolicy {
+  /** removes the GPS IFDs, and the XMP if any of its packets has GPS properties too */
+  Gps,
+  /** removes all Exif tags except Orientation and ColorSpace, including the thumbnail */
+  ExifExceptOrientation,
+  /** removes all APPn segments except those needed to decode the colours correctly,
+      ICC profiles and the Adobe segment. Exif and XMP are removed. */
+  AppSegmentsExceptColor,
+  /** removes COM segments */
+  Comments
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Removed {
+  Segment { marker: SegmentMarker, len: usize },
+  Tag { section: Section, no: u16 },
+  Thumbnail { len: usize }
+}
+
+pub struct ScrubResult {
+  pub data: Vec<u8>,
+  pub removed: Vec<Removed>
+}
+
+/** ICC profiles and the Adobe segment, which tells whether the components are RGB, CMYK or transformed */
+fn is_color_info(segment: &EditorSegment) -> bool {
+  match segment.marker() {
+    SegmentMarker::APP(2) => segment.payload().starts_with(ICC_SEGMENT_HEADER),
+    SegmentMarker::APP(14) => segment.payload().starts_with(ADOBE_SEGMENT_HEADER),
+    _ => false
+  }
+}
+
+fn is_xmp(segment: &EditorSegment) -> bool {
+  let payload = segment.payload();
+  segment.marker() == SegmentMarker::APP(1) &&
+    (payload.starts_with(XMP_SEGMENT_HEADER) || payload.starts_with(EXTENDED_XMP_SEGMENT_HEADER))
+}
+
+fn has_gps_properties(xmp: &Xmp) -> bool {
+  xmp.properties.iter().any(|p| p.namespace == NS_EXIF && p.name.starts_with("GPS"))
+}
+
+/** Whether any XMP packet of a JPEG file has GPS properties, like the ones copied from
+    the Exif GPS IFD. XMP that can't be read is assumed to have them. */
+fn xmp_has_gps(data: &[u8], segments: &[EditorSegment]) -> bool {
+  //the extended XMP is only found through the first packet it is merged with
+  let extended_has_gps = match read_xmp(data) {
+    Ok(Some(xmp)) => has_gps_properties(&xmp),
+    Ok(None) => false,
+    Err(_) => true
+  };
+  extended_has_gps || segments.iter()
+    .filter(|s| s.marker() == SegmentMarker::APP(1))
+    .any(|s| match read_xmp_packet(Cursor::new(s.payload(), Endianness::Big)) {
+      Ok(Some(packet)) => Xmp::parse(packet).map(|xmp| has_gps_properties(&xmp)).unwrap_or(true),
+      Ok(None) => false,
+      Err(_) => true
+    })
+}
+
+fn keep_tag(policies: &[ScrubPolicy], section: Section, tag_no: u16) -> bool {
+  if section == Section::GPS && policies.contains(&ScrubPolicy::Gps) {
+    return false;
+  }
+  if policies.contains(&ScrubPolicy::ExifExceptOrientation) {
+    return matches!((section, tag_no),
+      (Section::IFD0, 0x0112) | (Section::SubIFD, 0xA001));
+  }
+  true
+}
+
+/** Rebuilds the Exif segment without the tags the policies remove.
+    Returns `None` if nothing needs to be removed, so the original
+    segment can be kept as is. */
+fn scrub_exif(payload: &[u8], policies: &[ScrubPolicy], removed: &mut Vec<Removed>) -> ParseResult<Option<Vec<u8>>> {
+  let endianness = if payload[6 ..].starts_with(b"II") {
+    Endianness::Little
+  } else {
+    Endianness::Big
+  };
+  let mut writer = ExifWriter::new(endianness);
+  let mut removed_tags = Vec::new();
+  let mut thumbnail_offset = None;
+  let mut thumbnail_len = None;
+
+  for result in read_tags(Cursor::new(payload, endianness))? {
+    let (tag, section) = result?;
+    match (section, tag.no) {
+      (Section::IFD1, 0x0201) => thumbnail_offset = tag.value.as_u32(),
+      (Section::IFD1, 0x0202) => thumbnail_len = tag.value.as_u32(),
+      _ => ()
+    }
+    //offsets are filled in by the writer
+    if is_offset_tag(section, tag.no) {
+      continue;
+    }
+    if keep_tag(policies, section, tag.no) {
+      writer.add_tag(section, tag.no, ExifValue::from_variant(&tag.value));
+    } else {
+      removed_tags.push(Removed::Tag { section, no: tag.no });
+    }
+  }
+
+  let thumbnail = match (thumbnail_offset, thumbnail_len) {
+    (Some(offset), Some(len)) => {
+      let start = 6 + offset as usize;
+      payload.get(start .. start + len as usize)
+    },
+    _ => None
+  };
+  let keep_thumbnail = !policies.contains(&ScrubPolicy::ExifExceptOrientation);
+  if let Some(thumbnail) = thumbnail {
+    if keep_thumbnail {
+      writer.set_thumbnail(thumbnail.to_vec());
+    } else {
+      removed_tags.push(Removed::Thumbnail { len: thumbnail.len() });
+    }
+  }
+
+  if removed_tags.is_empty() {
+    return Ok(None);
+  }
+  removed.extend(removed_tags);
+  Ok(Some(writer.write_app1()))
+}
+
+/** Removes metadata from a JPEG file according to the given policies,
+    without re-encoding the image. Segments that are not affected are copied
+    byte for byte. If tags are removed from an Exif segment, it is rebuilt,
+    which moves the data of the remaining tags: maker notes that use absolute
+    offsets may not be readable afterwards. XMP is removed as a whole, as it
+    isn't rewritten. */
+pub fn scrub(data: &[u8], policies: &[ScrubPolicy]) -> ParseResult<ScrubResult> {
+  let mut editor = JPEGEditor::new(data)?;
+  let mut removed = Vec::new();
+  //every XMP segment is removed if any of them has GPS properties
+  let remove_xmp = policies.contains(&ScrubPolicy::Gps) && xmp_has_gps(data, editor.segments());
+
+  editor.retain(|segment| {
+    let keep = match segment.marker() {
+      SegmentMarker::APP(_) if policies.contains(&ScrubPolicy::AppSegmentsExceptColor) =>
+        is_color_info(segment),
+      SegmentMarker::APP(1) if remove_xmp => !is_xmp(segment),
+      SegmentMarker::COM => !policies.contains(&ScrubPolicy::Comments),
+      _ => true
+    };
+    if !keep {
+      removed.push(Removed::Segment {
+        marker: segment.marker(),
+        len: segment.payload().len()
+      });
+    }
+    keep
+  });
+
+  //files can have more than one Exif segment, each with its own GPS IFD
+  let exif_indices : Vec<usize> = editor.segments().iter()
+    .enumerate()
+    .filter(|&(_, s)| s.is_exif())
+    .map(|(index, _)| index)
+    .collect();
+  for index in exif_indices {
+    let scrubbed = scrub_exif(editor.segments()[index].payload(), policies, &mut removed)?;
+    if let Some(payload) = scrubbed {
+      editor.replace_segment(index, payload);
+    }
+  }
+
+  Ok(ScrubResult {
+    data: editor.write()?,
+    removed
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use ::adobe::{read_color_space, JpegColorSpace};
+  use ::cursor::{Cursor, Endianness};
+  use ::jpeg::{JPEGSegmentIterator, SegmentMarker};
+  use ::jpeg_editor::JPEGEditor;
+  use ::tags::{read_tags, Section};
+  use ::test_fixtures::{JPEG_SAMPLE, JPEG_SAMPLE_EXIF_OFFSET};
+  use ::xmp::{read_xmp, XMP_SEGMENT_HEADER};
+  use super::{scrub, ScrubPolicy, Removed};
+
+  fn sections_and_tags(data: &[u8]) -> Vec<(Section, u16)> {
+    let cursor = Cursor::new(data, Endianness::Big)
+      .with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).unwrap();
+    read_tags(cursor).unwrap()
+      .map(|r| r.unwrap())
+      .map(|(tag, section)| (section, tag.no))
+      .collect()
+  }
+
+  fn markers(data: &[u8]) -> Vec<SegmentMarker> {
+    JPEGSegmentIterator::new(Cursor::new(data, Endianness::Big))
+      .map(|r| r.unwrap().0)
+      .collect()
+  }
+
+  #[test]
+  fn test_scrub_gps() {
+    let result = scrub(JPEG_SAMPLE, &[ScrubPolicy::Gps]).unwrap();
+    let tags = sections_and_tags(&result.data);
+    assert!(!tags.iter().any(|&(section, _)| section == Section::GPS));
+    assert!(read_xmp(&result.data).unwrap().unwrap().properties.iter().all(|p| !p.name.starts_with("GPS")));
+    assert!(tags.contains(&(Section::IFD0, 0x010F)));
+    assert!(tags.contains(&(Section::SubIFD, 0x9003)));
+    assert_eq!(result.removed.len(), 9);
+    assert_eq!(result.removed[0], Removed::Tag { section: Section::GPS, no: 0x0000 });
+    //the image data is untouched
+    let scan = &JPEG_SAMPLE[3865 ..];
+    assert!(result.data.ends_with(scan));
+  }
+
+  #[test]
+  fn test_scrub_gps_in_every_exif_segment() {
+    let mut editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    let exif = editor.segments().iter().find(|s| s.is_exif()).unwrap().payload().to_vec();
+    let last = editor.segments().len();
+    editor.insert_segment(last, SegmentMarker::APP(1), exif);
+    let jpeg = editor.write().unwrap();
+
+    let result = scrub(&jpeg, &[ScrubPolicy::Gps]).unwrap();
+    assert_eq!(result.removed.len(), 2 * 9);
+    let editor = JPEGEditor::new(&result.data).unwrap();
+    let exif_segments : Vec<_> = editor.segments().iter().filter(|s| s.is_exif()).collect();
+    assert_eq!(exif_segments.len(), 2);
+    for segment in exif_segments {
+      let tags : Vec<Section> = read_tags(Cursor::new(segment.payload(), Endianness::Big)).unwrap()
+        .map(|r| r.unwrap().1)
+        .collect();
+      assert!(!tags.contains(&Section::GPS));
+      assert!(tags.contains(&Section::SubIFD));
+    }
+  }
+
+  #[test]
+  fn test_scrub_gps_in_xmp() {
+    let packet = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+      <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+        <rdf:Description rdf:about="" xmlns:exif="http://ns.adobe.com/exif/1.0/"
+            exif:GPSLatitude="38,42.6N" exif:GPSLongitude="9,8.4W"/>
+      </rdf:RDF>
+    </x:xmpmeta>"#;
+    let mut payload = XMP_SEGMENT_HEADER.to_vec();
+    payload.extend_from_slice(packet.as_bytes());
+    let mut editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    let index = editor.segments().iter()
+      .position(|s| s.payload().starts_with(XMP_SEGMENT_HEADER))
+      .unwrap();
+    editor.replace_segment(index, payload);
+    let jpeg = editor.write().unwrap();
+
+    let result = scrub(&jpeg, &[ScrubPolicy::Gps]).unwrap();
+    assert!(!sections_and_tags(&result.data).iter().any(|&(section, _)| section == Section::GPS));
+    assert_eq!(read_xmp(&result.data), Ok(None));
+    assert!(!result.data.windows(3).any(|w| w == b"GPS"));
+    assert!(result.removed.contains(&Removed::Segment { marker: SegmentMarker::APP(1), len: XMP_SEGMENT_HEADER.len() + packet.len() }));
+
+    //a second packet with GPS after the sample packet without
+    let mut editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    let mut payload = XMP_SEGMENT_HEADER.to_vec();
+    payload.extend_from_slice(packet.as_bytes());
+    editor.insert_segment(index + 1, SegmentMarker::APP(1), payload);
+    let jpeg = editor.write().unwrap();
+    let result = scrub(&jpeg, &[ScrubPolicy::Gps]).unwrap();
+    assert_eq!(read_xmp(&result.data), Ok(None));
+    assert!(!result.data.windows(3).any(|w| w == b"GPS"));
+
+    //XMP without GPS is kept
+    let result = scrub(JPEG_SAMPLE, &[ScrubPolicy::Gps]).unwrap();
+    assert!(read_xmp(&result.data).unwrap().is_some());
+  }
+
+  #[test]
+  fn test_scrub_exif_except_orientation() {
+    let result = scrub(JPEG_SAMPLE, &[ScrubPolicy::ExifExceptOrientation]).unwrap();
+    assert_eq!(sections_and_tags(&result.data), vec![
+      (Section::IFD0, 0x0112),
+      (Section::IFD0, 0x8769),
+      (Section::SubIFD, 0xA001)
+    ]);
+    assert_eq!(result.removed.len(), 6 + 12 + 9);
+  }
+
+  #[test]
+  fn test_scrub_app_segments_and_comments() {
+    let result = scrub(JPEG_SAMPLE, &[ScrubPolicy::AppSegmentsExceptColor, ScrubPolicy::Comments]).unwrap();
+    assert_eq!(&markers(&result.data)[0 .. 3], &[
+      SegmentMarker::SOI,
+      SegmentMarker::SOF(0),
+      SegmentMarker::DHT
+    ]);
+    assert_eq!(result.removed, vec![
+      Removed::Segment { marker: SegmentMarker::APP(0), len: 14 },
+      Removed::Segment { marker: SegmentMarker::APP(1), len: 578 },
+      Removed::Segment { marker: SegmentMarker::APP(1), len: 2542 },
+      Removed::Segment { marker: SegmentMarker::APP(13), len: 118 }
+    ]);
+  }
+
+  #[test]
+  fn test_keep_color_info() {
+    let mut editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    editor.insert_segment(1, SegmentMarker::APP(14), b"Adobe\x00\x64\x00\x00\x00\x00\x00".to_vec());
+    editor.insert_segment(1, SegmentMarker::APP(2), b"ICC_PROFILE\0\x01\x01".to_vec());
+    editor.insert_segment(1, SegmentMarker::APP(14), b"Ducky\0".to_vec());
+    let jpeg = editor.write().unwrap();
+    let result = scrub(&jpeg, &[ScrubPolicy::AppSegmentsExceptColor]).unwrap();
+    assert_eq!(&markers(&result.data)[0 .. 4], &[
+      SegmentMarker::SOI,
+      SegmentMarker::APP(2),
+      SegmentMarker::APP(14),
+      SegmentMarker::SOF(0)
+    ]);
+    assert_eq!(read_color_space(&result.data), Ok(Some(JpegColorSpace::Rgb)));
+  }
+
+  #[test]
+  fn test_nothing_to_remove() {
+    let result = scrub(JPEG_SAMPLE, &[ScrubPolicy::Comments]).unwrap();
+    assert!(result.removed.is_empty());
+    assert_eq!(result.data, JPEG_SAMPLE.to_vec());
+  }
+}
@@ -1,4 +1,5 @@
-use ::cursor::{Cursor};
+use std::fmt;
+use ::cursor::{Cursor, Endianness};
 use ::component::TagComponents;
 use ::error::{ParseError, ParseResult};
 
@@ -7,6 +8,7 @@ pub const EXIF_TAG_SIZE : usize = 2 + 2 + 4 + 4;
 pub enum ExifVariant<'a> {
   Text(&'a str),
   Bytes(&'a [u8]),
+  Undefined(&'a [u8]),
   SignedByte(TagComponents<'a, i8>),
   UShort(TagComponents<'a, u16>),
   UInt(TagComponents<'a, u32>),
@@ -18,6 +20,75 @@ pub enum ExifVariant<'a> {
   Double(TagComponents<'a, f64>)
 }
 
+impl<'a> ExifVariant<'a> {
+  /** the first component of an unsigned integer value,
+      which is how most single-valued tags are read */
+  pub fn as_u32(&self) -> Option<u32> {
+    match *self {
+      ExifVariant::Bytes(bytes) => bytes.first().map(|b| *b as u32),
+      ExifVariant::UShort(ref components) => components.iter().next().map(|n| n as u32),
+      ExifVariant::UInt(ref components) => components.iter().next(),
+      _ => None
+    }
+  }
+
+  /** the first component of any numeric value, with fractions divided out */
+  pub fn as_f64(&self) -> Option<f64> {
+    match *self {
+      ExifVariant::Text(_) | ExifVariant::Undefined(_) => None,
+      ExifVariant::Bytes(bytes) => bytes.first().map(|b| *b as f64),
+      ExifVariant::SignedByte(ref c) => c.iter().next().map(|n| n as f64),
+      ExifVariant::UShort(ref c) => c.iter().next().map(|n| n as f64),
+      ExifVariant::UInt(ref c) => c.iter().next().map(|n| n as f64),
+      ExifVariant::UIntFraction(ref c) => c.iter().next().and_then(|(n, d)| {
+        if d == 0 { None } else { Some(n as f64 / d as f64) }
+      }),
+      ExifVariant::Short(ref c) => c.iter().next().map(|n| n as f64),
+      ExifVariant::Int(ref c) => c.iter().next().map(|n| n as f64),
+      ExifVariant::IntFraction(ref c) => c.iter().next().and_then(|(n, d)| {
+        if d == 0 { None } else { Some(n as f64 / d as f64) }
+      }),
+      ExifVariant::Float(ref c) => c.iter().next().map(|n| n as f64),
+      ExifVariant::Double(ref c) => c.iter().next()
+    }
+  }
+}
+
+fn write_components<T, I>(f: &mut fmt::Formatter, components: I) -> fmt::Result
+  where T: fmt::Display, I: Iterator<Item=T>
+{
+  for (i, component) in components.enumerate() {
+    if i != 0 {
+      f.write_str(" ")?;
+    }
+    write!(f, "{}", component)?;
+  }
+  Ok(())
+}
+
+/** Prints the raw value, with components separated by spaces.
+    Text is cut off at the first NUL terminator. */
+impl<'a> fmt::Display for ExifVariant<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      ExifVariant::Text(text) => f.write_str(text.split('\0').next().unwrap_or("")),
+      ExifVariant::Bytes(bytes) => write_components(f, bytes.iter()),
+      ExifVariant::Undefined(bytes) => write!(f, "(Binary data {} bytes)", bytes.len()),
+      ExifVariant::SignedByte(ref c) => write_components(f, c.iter()),
+      ExifVariant::UShort(ref c) => write_components(f, c.iter()),
+      ExifVariant::UInt(ref c) => write_components(f, c.iter()),
+      ExifVariant::UIntFraction(ref c) =>
+        write_components(f, c.iter().map(|(n, d)| format!("{}/{}", n, d))),
+      ExifVariant::Short(ref c) => write_components(f, c.iter()),
+      ExifVariant::Int(ref c) => write_components(f, c.iter()),
+      ExifVariant::IntFraction(ref c) =>
+        write_components(f, c.iter().map(|(n, d)| format!("{}/{}", n, d))),
+      ExifVariant::Float(ref c) => write_components(f, c.iter()),
+      ExifVariant::Double(ref c) => write_components(f, c.iter())
+    }
+  }
+}
+
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum ExifFormat {
   UByte,
@@ -31,7 +102,9 @@ pub enum ExifFormat {
   Int,
   IntFraction,
   Float,
-  Double
+  Double,
+  /** an offset to a sub-IFD, used in some MakerNotes */
+  Ifd
 }
 
 impl ExifFormat {
@@ -49,11 +122,31 @@ impl ExifFormat {
       10 => Ok(ExifFormat::IntFraction),
       11 => Ok(ExifFormat::Float),
       12 => Ok(ExifFormat::Double),
+      13 => Ok(ExifFormat::Ifd),
       _ => Err(ParseError::InvalidValueFormat{ format: format } )
     }
   }
 
-  fn bytes_per_component(self) -> usize {
+  /** the format number as stored in a tag */
+  pub fn number(self) -> u16 {
+    match self {
+      ExifFormat::UByte => 1,
+      ExifFormat::Text => 2,
+      ExifFormat::UShort => 3,
+      ExifFormat::UInt => 4,
+      ExifFormat::UIntFraction => 5,
+      ExifFormat::SignedByte => 6,
+      ExifFormat::Binary => 7,
+      ExifFormat::Short => 8,
+      ExifFormat::Int => 9,
+      ExifFormat::IntFraction => 10,
+      ExifFormat::Float => 11,
+      ExifFormat::Double => 12,
+      ExifFormat::Ifd => 13
+    }
+  }
+
+  pub fn bytes_per_component(self) -> usize {
     match self {
       ExifFormat::UByte |
       ExifFormat::Text |
@@ -65,7 +158,8 @@ impl ExifFormat {
 
       ExifFormat::UInt |
       ExifFormat::Int |
-      ExifFormat::Float => 4,
+      ExifFormat::Float |
+      ExifFormat::Ifd => 4,
 
       ExifFormat::UIntFraction |
       ExifFormat::IntFraction |
@@ -77,13 +171,15 @@ impl ExifFormat {
   -> ParseResult<ExifVariant<'a>>
   {
     let variant = match self {
-      ExifFormat::UByte | ExifFormat::Binary =>
+      ExifFormat::UByte =>
       ExifVariant::Bytes(value_cursor.read_bytes_or_fail(len as usize)?),
+      ExifFormat::Binary =>
+      ExifVariant::Undefined(value_cursor.read_bytes_or_fail(len as usize)?),
       ExifFormat::Text =>
       ExifVariant::Text(value_cursor.read_str_or_fail(len as usize)?),
       ExifFormat::UShort =>
       ExifVariant::UShort(TagComponents::<u16>::new(value_cursor, len)),
-      ExifFormat::UInt =>
+      ExifFormat::UInt | ExifFormat::Ifd =>
       ExifVariant::UInt(TagComponents::<u32>::new(value_cursor, len)),
       ExifFormat::UIntFraction =>
       ExifVariant::UIntFraction(TagComponents::<(u32, u32)>::new(value_cursor, len)),
@@ -104,13 +200,81 @@ impl ExifFormat {
   }
 }
 
+/** A tag as read from an IFD entry. It remembers where the entry and value are stored,
+    so it can only be made by `read_exif_tag` and the iterators built on it. */
 pub struct RawExifTag<'a> {
   pub no: u16,
   pub format: ExifFormat,
-  pub value: ExifVariant<'a>
+  pub value: ExifVariant<'a>,
+  //where the entry and value were read from, for in-place patching
+  entry_cursor: Cursor<'a>,
+  value_cursor: Cursor<'a>,
+  tiff_cursor: Cursor<'a>,
+  inline: bool
+}
+
+/** Where a tag is stored, relative to some base like the TIFF header or the file start */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TagSpan {
+  /** the start of the 12 byte IFD entry */
+  pub entry_offset: usize,
+  pub value_offset: usize,
+  pub value_len: usize,
+  /** whether the value is stored in the entry itself, rather than at an offset */
+  pub inline: bool
+}
+
+impl<'a> RawExifTag<'a> {
+  /** the bytes the value was read from, in the byte order of the TIFF data */
+  pub fn value_bytes(&self) -> &'a [u8] {
+    let mut cursor = self.value_cursor;
+    cursor.read_bytes(cursor.len()).unwrap_or(&[])
+  }
+
+  /** The start and end offset of the value relative to `base`, which needs to be
+      the cursor the tag was read from, or a cursor around it like the whole file.
+      Returns `None` if the value is not within `base`. */
+  pub fn value_range(&self, base: &Cursor<'a>) -> Option<(usize, usize)> {
+    self.value_cursor.offset_within(base)
+      .map(|start| (start, start + self.value_cursor.len()))
+  }
+
+  pub fn endianness(&self) -> Endianness {
+    self.value_cursor.endianness()
+  }
+
+  pub fn value_cursor(&self) -> Cursor<'a> {
+    self.value_cursor
+  }
+
+  /** the start of the TIFF header the tag was read with */
+  pub fn tiff_marker(&self) -> Cursor<'a> {
+    self.tiff_cursor
+  }
+
+  /** The position of the entry and value relative to `base`, like the file start.
+      Returns `None` if they are not within `base`. */
+  pub fn span(&self, base: &Cursor<'a>) -> Option<TagSpan> {
+    let entry_offset = self.entry_cursor.offset_within(base)?;
+    let (value_offset, value_end) = self.value_range(base)?;
+    Some(TagSpan {
+      entry_offset,
+      value_offset,
+      value_len: value_end - value_offset,
+      inline: self.inline
+    })
+  }
+
+  /** The position relative to the cursor the offsets in the IFD are relative to.
+      That is the TIFF header for the Exif IFDs, but can be the start of the MakerNote
+      for MakerNote tags, see `OffsetBase`. */
+  pub fn tiff_span(&self) -> Option<TagSpan> {
+    self.span(&self.tiff_cursor)
+  }
 }
 
 pub fn read_exif_tag<'a>(mut cursor: Cursor<'a>, tiff_cursor: Cursor<'a>) -> ParseResult<RawExifTag<'a>> {
+  let entry_cursor = cursor.with_max_len(EXIF_TAG_SIZE);
   let tag_number : u16 = cursor.read_num_or_fail()?;
   let format_num : u16 = cursor.read_num_or_fail()?;
   let components : u32 = cursor.read_num_or_fail()?;
@@ -118,7 +282,8 @@ pub fn read_exif_tag<'a>(mut cursor: Cursor<'a>, tiff_cursor: Cursor<'a>) -> Par
   let format = ExifFormat::from(format_num)?;
   let total_values_bytes = format.bytes_per_component() * components as usize;
 
-  let value_cursor = if total_values_bytes > 4 {
+  let inline = total_values_bytes <= 4;
+  let value_cursor = if !inline {
     //TODO: have an option to be more forgiving about EOF
     //here since this will inhibit us from discovering
     //the tags to come with embedded values.
@@ -142,7 +307,11 @@ pub fn read_exif_tag<'a>(mut cursor: Cursor<'a>, tiff_cursor: Cursor<'a>) -> Par
   let tag = RawExifTag {
     no: tag_number,
     format: format,
-    value: variant
+    value: variant,
+    entry_cursor,
+    value_cursor: value_cursor.with_max_len(total_values_bytes),
+    tiff_cursor,
+    inline
   };
 
   return Ok(tag);
@@ -154,6 +323,7 @@ mod tests {
 	use ::cursor::{Cursor, Endianness};
 	use super::{
     read_exif_tag,
+    EXIF_TAG_SIZE,
     ExifFormat,
     ExifVariant
   };
@@ -205,6 +375,55 @@ mod tests {
     };
   }
 
+  #[test]
+  fn test_bytes_and_undefined_are_distinct() {
+    const EXIF_TAGS : &'static [u8] = &[
+      0u8, 0u8,//GPSVersionID
+      0u8, 1u8, //byte
+      0u8, 0u8, 0u8, 4u8, //4 values
+      2u8, 2u8, 0u8, 0u8,
+      0x90u8, 0u8,//ExifVersion
+      0u8, 7u8, //undefined
+      0u8, 0u8, 0u8, 4u8, //4 values
+      48u8, 50u8, 50u8, 48u8, //0220
+    ];
+    const EXIF_POINTER_AREA : &'static [u8] = &[];
+    let data_cursor = Cursor::new(EXIF_POINTER_AREA, Endianness::Big);
+
+    let cursor = Cursor::new(EXIF_TAGS, Endianness::Big);
+    let tag = read_exif_tag(cursor, data_cursor)
+      .expect("read should not fail");
+    assert_eq!(tag.format, ExifFormat::UByte);
+    match tag.value {
+      ExifVariant::Bytes(bytes) => assert_eq!(bytes, &[2u8, 2u8, 0u8, 0u8]),
+      _ => panic!("value should be bytes")
+    };
+
+    let cursor = cursor.with_skip_or_fail(EXIF_TAG_SIZE).expect("EOF");
+    let tag = read_exif_tag(cursor, data_cursor)
+      .expect("read should not fail");
+    assert_eq!(tag.format, ExifFormat::Binary);
+    match tag.value {
+      ExifVariant::Undefined(bytes) => assert_eq!(bytes, b"0220"),
+      _ => panic!("value should be undefined")
+    };
+  }
+
+  #[test]
+  fn test_ifd_tag_reads_as_uint() {
+    const EXIF_TAG : &[u8] = &[
+      0x20u8, 0x10u8,//Olympus Equipment
+      0u8, 13u8, //ifd
+      0u8, 0u8, 0u8, 1u8, //1 value
+      0u8, 0u8, 0u8, 0x72u8,
+    ];
+    let cursor = Cursor::new(EXIF_TAG, Endianness::Big);
+    let tag = read_exif_tag(cursor, Cursor::new(&[], Endianness::Big))
+      .expect("read should not fail");
+    assert_eq!(tag.format, ExifFormat::Ifd);
+    assert_eq!(tag.value.as_u32(), Some(0x72));
+  }
+
   #[test]
   fn test_extended_uint_exif_tag() {
     const EXIF_TAG : &'static [u8] = &[
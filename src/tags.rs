@@ -14,6 +14,7 @@ use ::tag::{
   RawExifTag,
   ExifVariant
 };
+use ::makernote::Vendor;
 
 pub struct SectionOffsetIterator {
   ifd0_offset: Option<u32>,
@@ -101,6 +102,11 @@ impl<'a> ExifTagIterator<'a> {
     }
   }
 
+  /** the start of the TIFF header, which offsets in the IFDs are relative to */
+  pub fn tiff_marker(&self) -> Cursor<'a> {
+    self.tiff_marker
+  }
+
   fn open_section(&self, offset: u32) -> ParseResult<SectionIterator<'a>> {
     let cursor = self.tiff_marker.with_skip_or_fail(offset as usize)?;
     read_section(cursor, self.tiff_marker)
@@ -215,7 +221,8 @@ pub enum Section {
   IFD1,
   GPS,
   SubIFD,
-  InteropIFD
+  InteropIFD,
+  MakerNote(Vendor)
 }
 
 pub fn read_tags<'a>(app1_cursor: Cursor<'a>) -> ParseResult<ExifTagIterator<'a>> {
@@ -236,27 +243,31 @@ fn read_exif_header<'a>(mut app1_cursor: Cursor<'a>) -> ParseResult<Cursor<'a>>
     return Err(ParseError::InvalidExifHeader{ header: header_array });
   }
 
-  let tiff_cursor = app1_cursor.clone();
-  let tiff_header : u16 = app1_cursor.read_num_or_fail()?;
+  read_tiff_header(app1_cursor)
+}
 
-  let app1_cursor = match tiff_header {
-    0x4949 => Ok(app1_cursor.with_endianness(Endianness::Little)),
-    0x4D4D => Ok(app1_cursor.with_endianness(Endianness::Big)),
+/** Checks the TIFF header at the start of the cursor,
+    and returns the cursor with the endianness it declares */
+pub fn read_tiff_header<'a>(tiff_cursor: Cursor<'a>) -> ParseResult<Cursor<'a>> {
+  let mut cursor = tiff_cursor;
+  let tiff_header : u16 = cursor.read_num_or_fail()?;
+
+  let cursor = match tiff_header {
+    0x4949 => Ok(cursor.with_endianness(Endianness::Little)),
+    0x4D4D => Ok(cursor.with_endianness(Endianness::Big)),
     _ => Err(ParseError::InvalidTiffHeader{ header: tiff_header })
   };
-  let mut app1_cursor = app1_cursor?;
+  let mut cursor = cursor?;
 
   //this is a marker in the data to check
   //the endianness has been properly detected
   //if not you'd read 0x2A00
-  let tiff_data_marker : u16 = app1_cursor.read_num_or_fail()?;
+  let tiff_data_marker : u16 = cursor.read_num_or_fail()?;
   if tiff_data_marker != 0x002A {
     return Err(ParseError::InvalidTiffData{ data: tiff_data_marker });
   }
 
-  let tiff_cursor = tiff_cursor.with_endianness(app1_cursor.endianness());
-
-  return Ok(tiff_cursor);
+  return Ok(tiff_cursor.with_endianness(cursor.endianness()));
 }
 
 #[cfg(test)]
@@ -270,7 +281,7 @@ mod tests {
     Section
   };
 
-  use ::tag::{ExifVariant};
+  use ::tag::{ExifVariant, TagSpan};
 
   #[test]
   fn test_read_exif_header() {
@@ -305,7 +316,8 @@ mod tests {
             Section::IFD1 => ifd1_count += 1,
             Section::GPS => gps_count += 1,
             Section::SubIFD => subifd_count += 1,
-            Section::InteropIFD => interop_count += 1
+            Section::InteropIFD => interop_count += 1,
+            Section::MakerNote(_) => panic!("MakerNote tags are read separately")
           }
         },
         Err( err ) => {
@@ -320,4 +332,30 @@ mod tests {
     assert_eq!(interop_count, 0);
   }
 
+
+  #[test]
+  fn test_tag_spans() {
+    let file = Cursor::new(JPEG_SAMPLE, Endianness::Little);
+    let cursor = file.with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).expect("EOF");
+    let mut tags_it = read_tags(cursor).unwrap();
+    let tiff_offset = tags_it.tiff_marker().offset_from(&file);
+    assert_eq!(tiff_offset, JPEG_SAMPLE_EXIF_OFFSET + 6);
+
+    //Make is stored at an offset
+    let (make, _) = tags_it.next().unwrap().unwrap();
+    let span = make.tiff_span().unwrap();
+    assert_eq!(span, TagSpan { entry_offset: 10, value_offset: 0x7A, value_len: 6, inline: false });
+    let file_span = make.span(&file).unwrap();
+    assert_eq!(file_span.entry_offset, tiff_offset + 10);
+    assert_eq!(file_span.value_offset, tiff_offset + 0x7A);
+
+    //Orientation is stored in the entry itself
+    let (orientation, _) = tags_it.nth(1).unwrap().unwrap();
+    let span = orientation.tiff_span().unwrap();
+    assert_eq!(span, TagSpan { entry_offset: 34, value_offset: 42, value_len: 2, inline: true });
+
+    let unrelated = Cursor::new(&JPEG_SAMPLE[.. 10], Endianness::Little);
+    assert!(orientation.span(&unrelated).is_none());
+  }
+
 }
@@ -24,7 +24,7 @@ pub fn expected_segments() -> [SegmentFixture; 14] {
 		SegmentFixture {marker: SegmentMarker::DQT,      offset: 3725, len: 65},
 		SegmentFixture {marker: SegmentMarker::DQT,      offset: 3794, len: 65},
 		SegmentFixture {marker: SegmentMarker::DRI,      offset: 3863, len: 2},
-		SegmentFixture {marker: SegmentMarker::SOS,      offset: 3867, len: 0}
+		SegmentFixture {marker: SegmentMarker::SOS,      offset: 3869, len: 10}
 	]
 }
 
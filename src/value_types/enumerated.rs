@@ -0,0 +1,455 @@
+use std::convert::TryFrom;
+use std::fmt;
+use ::tags::Section;
+use ::tag::ExifVariant;
+
+/** Generates an enum for a tag with enumerated values from a table of
+    `value => Variant: "description"` rows. Values not in the table map to
+    `Reserved(value)`, as the specification reserves them for future use. */
+macro_rules! enumerated_value {
+  ($name:ident { $($value:expr => $variant:ident : $description:expr),* }) => {
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    pub enum $name {
+      $($variant,)*
+      Reserved(u16)
+    }
+
+    impl $name {
+      pub fn from_value(value: u16) -> $name {
+        match value {
+          $($value => $name::$variant,)*
+          _ => $name::Reserved(value)
+        }
+      }
+
+      pub fn value(self) -> u16 {
+        match self {
+          $($name::$variant => $value,)*
+          $name::Reserved(value) => value
+        }
+      }
+
+      pub fn description(self) -> &'static str {
+        match self {
+          $($name::$variant => $description,)*
+          $name::Reserved(_) => "Unknown"
+        }
+      }
+    }
+
+    impl fmt::Display for $name {
+      fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+          $name::Reserved(value) => write!(f, "Unknown ({})", value),
+          _ => f.write_str(self.description())
+        }
+      }
+    }
+  }
+}
+
+enumerated_value!(Orientation {
+  1 => Horizontal: "Horizontal (normal)",
+  2 => MirrorHorizontal: "Mirror horizontal",
+  3 => Rotate180: "Rotate 180",
+  4 => MirrorVertical: "Mirror vertical",
+  5 => MirrorHorizontalRotate270: "Mirror horizontal and rotate 270 CW",
+  6 => Rotate90: "Rotate 90 CW",
+  7 => MirrorHorizontalRotate90: "Mirror horizontal and rotate 90 CW",
+  8 => Rotate270: "Rotate 270 CW"
+});
+
+enumerated_value!(Compression {
+  1 => Uncompressed: "Uncompressed",
+  6 => OldJpeg: "JPEG (old-style)",
+  7 => Jpeg: "JPEG"
+});
+
+enumerated_value!(ResolutionUnit {
+  1 => None: "None",
+  2 => Inches: "inches",
+  3 => Centimeters: "cm"
+});
+
+enumerated_value!(FocalPlaneResolutionUnit {
+  1 => None: "None",
+  2 => Inches: "inches",
+  3 => Centimeters: "cm",
+  4 => Millimeters: "mm",
+  5 => Micrometers: "um"
+});
+
+enumerated_value!(YCbCrPositioning {
+  1 => Centered: "Centered",
+  2 => CoSited: "Co-sited"
+});
+
+enumerated_value!(ExposureProgram {
+  0 => NotDefined: "Not Defined",
+  1 => Manual: "Manual",
+  2 => Program: "Program AE",
+  3 => AperturePriority: "Aperture-priority AE",
+  4 => ShutterPriority: "Shutter speed priority AE",
+  5 => Creative: "Creative (Slow speed)",
+  6 => Action: "Action (High speed)",
+  7 => Portrait: "Portrait",
+  8 => Landscape: "Landscape",
+  9 => Bulb: "Bulb"
+});
+
+enumerated_value!(MeteringMode {
+  0 => Unknown: "Unknown",
+  1 => Average: "Average",
+  2 => CenterWeightedAverage: "Center-weighted average",
+  3 => Spot: "Spot",
+  4 => MultiSpot: "Multi-spot",
+  5 => MultiSegment: "Multi-segment",
+  6 => Partial: "Partial",
+  255 => Other: "Other"
+});
+
+enumerated_value!(LightSource {
+  0 => Unknown: "Unknown",
+  1 => Daylight: "Daylight",
+  2 => Fluorescent: "Fluorescent",
+  3 => Tungsten: "Tungsten (Incandescent)",
+  4 => Flash: "Flash",
+  9 => FineWeather: "Fine Weather",
+  10 => Cloudy: "Cloudy",
+  11 => Shade: "Shade",
+  12 => DaylightFluorescent: "Daylight Fluorescent",
+  13 => DayWhiteFluorescent: "Day White Fluorescent",
+  14 => CoolWhiteFluorescent: "Cool White Fluorescent",
+  15 => WhiteFluorescent: "White Fluorescent",
+  16 => WarmWhiteFluorescent: "Warm White Fluorescent",
+  17 => StandardLightA: "Standard Light A",
+  18 => StandardLightB: "Standard Light B",
+  19 => StandardLightC: "Standard Light C",
+  20 => D55: "D55",
+  21 => D65: "D65",
+  22 => D75: "D75",
+  23 => D50: "D50",
+  24 => IsoStudioTungsten: "ISO Studio Tungsten",
+  255 => Other: "Other"
+});
+
+enumerated_value!(ColorSpace {
+  1 => Srgb: "sRGB",
+  2 => AdobeRgb: "Adobe RGB",
+  0xFFFF => Uncalibrated: "Uncalibrated"
+});
+
+enumerated_value!(SensingMethod {
+  1 => NotDefined: "Not defined",
+  2 => OneChipColorArea: "One-chip color area",
+  3 => TwoChipColorArea: "Two-chip color area",
+  4 => ThreeChipColorArea: "Three-chip color area",
+  5 => ColorSequentialArea: "Color sequential area",
+  7 => Trilinear: "Trilinear",
+  8 => ColorSequentialLinear: "Color sequential linear"
+});
+
+enumerated_value!(CustomRendered {
+  0 => Normal: "Normal",
+  1 => Custom: "Custom"
+});
+
+enumerated_value!(ExposureMode {
+  0 => Auto: "Auto",
+  1 => Manual: "Manual",
+  2 => AutoBracket: "Auto bracket"
+});
+
+enumerated_value!(WhiteBalance {
+  0 => Auto: "Auto",
+  1 => Manual: "Manual"
+});
+
+enumerated_value!(SceneCaptureType {
+  0 => Standard: "Standard",
+  1 => Landscape: "Landscape",
+  2 => Portrait: "Portrait",
+  3 => Night: "Night"
+});
+
+enumerated_value!(GainControl {
+  0 => None: "None",
+  1 => LowGainUp: "Low gain up",
+  2 => HighGainUp: "High gain up",
+  3 => LowGainDown: "Low gain down",
+  4 => HighGainDown: "High gain down"
+});
+
+enumerated_value!(Contrast {
+  0 => Normal: "Normal",
+  1 => Low: "Low",
+  2 => High: "High"
+});
+
+enumerated_value!(Saturation {
+  0 => Normal: "Normal",
+  1 => Low: "Low",
+  2 => High: "High"
+});
+
+enumerated_value!(Sharpness {
+  0 => Normal: "Normal",
+  1 => Soft: "Soft",
+  2 => Hard: "Hard"
+});
+
+enumerated_value!(SubjectDistanceRange {
+  0 => Unknown: "Unknown",
+  1 => Macro: "Macro",
+  2 => Close: "Close",
+  3 => Distant: "Distant"
+});
+
+enumerated_value!(GpsAltitudeRef {
+  0 => AboveSeaLevel: "Above Sea Level",
+  1 => BelowSeaLevel: "Below Sea Level"
+});
+
+enumerated_value!(FlashReturn {
+  0 => NoDetection: "No return detection",
+  2 => NotDetected: "Return not detected",
+  3 => Detected: "Return detected"
+});
+
+enumerated_value!(FlashMode {
+  0 => Unknown: "Unknown",
+  1 => On: "On",
+  2 => Off: "Off",
+  3 => Auto: "Auto"
+});
+
+/** The Flash tag is a bit field rather than an enumeration */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Flash {
+  pub fired: bool,
+  pub return_light: FlashReturn,
+  pub mode: FlashMode,
+  pub function_present: bool,
+  pub red_eye_reduction: bool
+}
+
+impl Flash {
+  pub fn from_value(value: u16) -> Flash {
+    Flash {
+      fired: value & 0x01 != 0,
+      return_light: FlashReturn::from_value((value >> 1) & 0x03),
+      mode: FlashMode::from_value((value >> 3) & 0x03),
+      function_present: value & 0x20 == 0,
+      red_eye_reduction: value & 0x40 != 0
+    }
+  }
+}
+
+impl fmt::Display for Flash {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if !self.function_present {
+      return f.write_str("No flash function");
+    }
+    let mut parts = Vec::new();
+    match self.mode {
+      FlashMode::Unknown => if !self.fired {
+        return f.write_str("No Flash");
+      },
+      mode => parts.push(mode.description())
+    };
+    parts.push(if self.fired { "Fired" } else { "Did not fire" });
+    if self.red_eye_reduction {
+      parts.push("Red-eye reduction");
+    }
+    match self.return_light {
+      FlashReturn::NoDetection => (),
+      return_light => parts.push(return_light.description())
+    };
+    f.write_str(&parts.join(", "))
+  }
+}
+
+pub enum EnumeratedValue {
+  Orientation(Orientation),
+  Compression(Compression),
+  ResolutionUnit(ResolutionUnit),
+  YCbCrPositioning(YCbCrPositioning),
+  ExposureProgram(ExposureProgram),
+  MeteringMode(MeteringMode),
+  LightSource(LightSource),
+  Flash(Flash),
+  ColorSpace(ColorSpace),
+  FocalPlaneResolutionUnit(FocalPlaneResolutionUnit),
+  SensingMethod(SensingMethod),
+  CustomRendered(CustomRendered),
+  ExposureMode(ExposureMode),
+  WhiteBalance(WhiteBalance),
+  SceneCaptureType(SceneCaptureType),
+  GainControl(GainControl),
+  Contrast(Contrast),
+  Saturation(Saturation),
+  Sharpness(Sharpness),
+  SubjectDistanceRange(SubjectDistanceRange),
+  GpsAltitudeRef(GpsAltitudeRef)
+}
+
+impl fmt::Display for EnumeratedValue {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      EnumeratedValue::Orientation(v) => v.fmt(f),
+      EnumeratedValue::Compression(v) => v.fmt(f),
+      EnumeratedValue::ResolutionUnit(v) => v.fmt(f),
+      EnumeratedValue::YCbCrPositioning(v) => v.fmt(f),
+      EnumeratedValue::ExposureProgram(v) => v.fmt(f),
+      EnumeratedValue::MeteringMode(v) => v.fmt(f),
+      EnumeratedValue::LightSource(v) => v.fmt(f),
+      EnumeratedValue::Flash(v) => v.fmt(f),
+      EnumeratedValue::ColorSpace(v) => v.fmt(f),
+      EnumeratedValue::FocalPlaneResolutionUnit(v) => v.fmt(f),
+      EnumeratedValue::SensingMethod(v) => v.fmt(f),
+      EnumeratedValue::CustomRendered(v) => v.fmt(f),
+      EnumeratedValue::ExposureMode(v) => v.fmt(f),
+      EnumeratedValue::WhiteBalance(v) => v.fmt(f),
+      EnumeratedValue::SceneCaptureType(v) => v.fmt(f),
+      EnumeratedValue::GainControl(v) => v.fmt(f),
+      EnumeratedValue::Contrast(v) => v.fmt(f),
+      EnumeratedValue::Saturation(v) => v.fmt(f),
+      EnumeratedValue::Sharpness(v) => v.fmt(f),
+      EnumeratedValue::SubjectDistanceRange(v) => v.fmt(f),
+      EnumeratedValue::GpsAltitudeRef(v) => v.fmt(f)
+    }
+  }
+}
+
+/** Looks up the meaning of the value of an enumerated tag.
+    Returns None if the tag is not enumerated or its value is not an unsigned integer
+    of 16 bits or less. */
+pub fn decode_enumerated(section: Section, tag_no: u16, value: &ExifVariant) -> Option<EnumeratedValue> {
+  let n = u16::try_from(value.as_u32()?).ok()?;
+  let decoded = match (section, tag_no) {
+    (Section::IFD0, 0x0112) | (Section::IFD1, 0x0112) =>
+      EnumeratedValue::Orientation(Orientation::from_value(n)),
+    (Section::IFD0, 0x0103) | (Section::IFD1, 0x0103) =>
+      EnumeratedValue::Compression(Compression::from_value(n)),
+    (Section::IFD0, 0x0128) | (Section::IFD1, 0x0128) =>
+      EnumeratedValue::ResolutionUnit(ResolutionUnit::from_value(n)),
+    (Section::IFD0, 0x0213) | (Section::IFD1, 0x0213) =>
+      EnumeratedValue::YCbCrPositioning(YCbCrPositioning::from_value(n)),
+    (Section::SubIFD, 0x8822) =>
+      EnumeratedValue::ExposureProgram(ExposureProgram::from_value(n)),
+    (Section::SubIFD, 0x9207) =>
+      EnumeratedValue::MeteringMode(MeteringMode::from_value(n)),
+    (Section::SubIFD, 0x9208) =>
+      EnumeratedValue::LightSource(LightSource::from_value(n)),
+    (Section::SubIFD, 0x9209) =>
+      EnumeratedValue::Flash(Flash::from_value(n)),
+    (Section::SubIFD, 0xA001) =>
+      EnumeratedValue::ColorSpace(ColorSpace::from_value(n)),
+    (Section::SubIFD, 0xA210) =>
+      EnumeratedValue::FocalPlaneResolutionUnit(FocalPlaneResolutionUnit::from_value(n)),
+    (Section::SubIFD, 0xA217) =>
+      EnumeratedValue::SensingMethod(SensingMethod::from_value(n)),
+    (Section::SubIFD, 0xA401) =>
+      EnumeratedValue::CustomRendered(CustomRendered::from_value(n)),
+    (Section::SubIFD, 0xA402) =>
+      EnumeratedValue::ExposureMode(ExposureMode::from_value(n)),
+    (Section::SubIFD, 0xA403) =>
+      EnumeratedValue::WhiteBalance(WhiteBalance::from_value(n)),
+    (Section::SubIFD, 0xA406) =>
+      EnumeratedValue::SceneCaptureType(SceneCaptureType::from_value(n)),
+    (Section::SubIFD, 0xA407) =>
+      EnumeratedValue::GainControl(GainControl::from_value(n)),
+    (Section::SubIFD, 0xA408) =>
+      EnumeratedValue::Contrast(Contrast::from_value(n)),
+    (Section::SubIFD, 0xA409) =>
+      EnumeratedValue::Saturation(Saturation::from_value(n)),
+    (Section::SubIFD, 0xA40A) =>
+      EnumeratedValue::Sharpness(Sharpness::from_value(n)),
+    (Section::SubIFD, 0xA40C) =>
+      EnumeratedValue::SubjectDistanceRange(SubjectDistanceRange::from_value(n)),
+    (Section::GPS, 0x0005) =>
+      EnumeratedValue::GpsAltitudeRef(GpsAltitudeRef::from_value(n)),
+    _ => return None
+  };
+  Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::tags::{read_tags, Section};
+  use ::component::TagComponents;
+  use ::test_fixtures::{JPEG_SAMPLE, JPEG_SAMPLE_EXIF_OFFSET};
+  use super::*;
+
+  #[test]
+  fn test_enumerated_lookup() {
+    assert_eq!(MeteringMode::from_value(5), MeteringMode::MultiSegment);
+    assert_eq!(MeteringMode::from_value(5).description(), "Multi-segment");
+    assert_eq!(MeteringMode::from_value(255), MeteringMode::Other);
+    assert_eq!(ExposureProgram::Program.value(), 2);
+    assert_eq!(LightSource::from_value(21).to_string(), "D65");
+    assert_eq!(ColorSpace::from_value(0xFFFF), ColorSpace::Uncalibrated);
+  }
+
+  #[test]
+  fn test_unknown_value_falls_back() {
+    let mode = MeteringMode::from_value(42);
+    assert_eq!(mode, MeteringMode::Reserved(42));
+    assert_eq!(mode.value(), 42);
+    assert_eq!(mode.description(), "Unknown");
+    assert_eq!(mode.to_string(), "Unknown (42)");
+  }
+
+  #[test]
+  fn test_flash_bits() {
+    let flash = Flash::from_value(0x5F);
+    assert!(flash.fired);
+    assert!(flash.function_present);
+    assert!(flash.red_eye_reduction);
+    assert_eq!(flash.mode, FlashMode::Auto);
+    assert_eq!(flash.return_light, FlashReturn::Detected);
+    assert_eq!(flash.to_string(), "Auto, Fired, Red-eye reduction, Return detected");
+
+    assert_eq!(Flash::from_value(0x00).to_string(), "No Flash");
+    assert_eq!(Flash::from_value(0x01).to_string(), "Fired");
+    assert_eq!(Flash::from_value(0x05).to_string(), "Fired, Return not detected");
+    assert_eq!(Flash::from_value(0x10).to_string(), "Off, Did not fire");
+    assert_eq!(Flash::from_value(0x20).to_string(), "No flash function");
+  }
+
+  #[test]
+  fn test_decode_sample_tags() {
+    let cursor = Cursor::new(JPEG_SAMPLE, Endianness::Little);
+    let cursor = cursor.with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).expect("EOF");
+    let descriptions : Vec<(u16, String)> = read_tags(cursor).unwrap()
+      .map(|r| r.unwrap())
+      .filter_map(|(tag, section)| {
+        decode_enumerated(section, tag.no, &tag.value).map(|v| (tag.no, v.to_string()))
+      })
+      .collect();
+    assert_eq!(descriptions, vec![
+      (0x0112, "Horizontal (normal)".to_string()),
+      (0x0128, "inches".to_string()),
+      (0x0005, "Above Sea Level".to_string()),
+      (0x9209, "Auto, Did not fire".to_string()),
+      (0xA001, "sRGB".to_string())
+    ]);
+  }
+
+  #[test]
+  fn test_decode_requires_matching_section() {
+    let data = [0u8, 1u8];
+    let value = ExifVariant::Bytes(&data);
+    assert!(decode_enumerated(Section::SubIFD, 0x0112, &value).is_none());
+    assert!(decode_enumerated(Section::IFD0, 0x0112, &value).is_some());
+  }
+
+  #[test]
+  fn test_decode_rejects_values_above_16_bits() {
+    //0x10001 would be Orientation 1 if it were truncated
+    let data = [0u8, 1, 0, 1];
+    let value = ExifVariant::UInt(TagComponents::new(Cursor::new(&data, Endianness::Big), 1));
+    assert!(decode_enumerated(Section::IFD0, 0x0112, &value).is_none());
+  }
+}
@@ -1 +1,45 @@
-mod datetime;
\ No newline at end of file
+mod datetime;
+mod undefined;
+mod enumerated;
+
+pub use self::undefined::{
+  Version,
+  Channel,
+  ComponentsConfiguration,
+  FileSource,
+  SceneType,
+  CfaColor,
+  CfaPattern,
+  RationalTable,
+  DeviceSettings,
+  UndefinedValue,
+  decode_undefined
+};
+
+pub use self::enumerated::{
+  Orientation,
+  Compression,
+  ResolutionUnit,
+  FocalPlaneResolutionUnit,
+  YCbCrPositioning,
+  ExposureProgram,
+  MeteringMode,
+  LightSource,
+  ColorSpace,
+  SensingMethod,
+  CustomRendered,
+  ExposureMode,
+  WhiteBalance,
+  SceneCaptureType,
+  GainControl,
+  Contrast,
+  Saturation,
+  Sharpness,
+  SubjectDistanceRange,
+  GpsAltitudeRef,
+  FlashReturn,
+  FlashMode,
+  Flash,
+  EnumeratedValue,
+  decode_enumerated
+};
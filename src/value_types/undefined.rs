@@ -0,0 +1,451 @@
+use std::char;
+use std::fmt;
+use std::str;
+use ::cursor::{Cursor, Endianness};
+use ::tags::Section;
+
+/** ExifVersion, FlashpixVersion and InteropVersion are stored as
+    four ascii digits, e.g. `0220` for version 2.20 */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Version {
+  pub major: u8,
+  pub minor: u8
+}
+
+impl Version {
+  pub fn parse(bytes: &[u8]) -> Option<Version> {
+    if bytes.len() != 4 || !bytes.iter().all(|b| b.is_ascii_digit()) {
+      return None;
+    }
+    let digit = |i: usize| bytes[i] - b'0';
+    Some(Version {
+      major: digit(0) * 10 + digit(1),
+      minor: digit(2) * 10 + digit(3)
+    })
+  }
+}
+
+impl fmt::Display for Version {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}.{:02}", self.major, self.minor)
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Channel {
+  None,
+  Y,
+  Cb,
+  Cr,
+  R,
+  G,
+  B,
+  Unknown(u8)
+}
+
+impl From<u8> for Channel {
+  fn from(value: u8) -> Channel {
+    match value {
+      0 => Channel::None,
+      1 => Channel::Y,
+      2 => Channel::Cb,
+      3 => Channel::Cr,
+      4 => Channel::R,
+      5 => Channel::G,
+      6 => Channel::B,
+      _ => Channel::Unknown(value)
+    }
+  }
+}
+
+impl fmt::Display for Channel {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Channel::None => f.write_str("-"),
+      Channel::Y => f.write_str("Y"),
+      Channel::Cb => f.write_str("Cb"),
+      Channel::Cr => f.write_str("Cr"),
+      Channel::R => f.write_str("R"),
+      Channel::G => f.write_str("G"),
+      Channel::B => f.write_str("B"),
+      Channel::Unknown(value) => write!(f, "Unknown ({})", value)
+    }
+  }
+}
+
+/** maps each of the (up to) four channels of the compressed data to a color component,
+    e.g. `Y Cb Cr -` */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ComponentsConfiguration {
+  pub channels: [Channel; 4]
+}
+
+impl ComponentsConfiguration {
+  pub fn parse(bytes: &[u8]) -> Option<ComponentsConfiguration> {
+    if bytes.len() != 4 {
+      return None;
+    }
+    let mut channels = [Channel::None; 4];
+    for (channel, byte) in channels.iter_mut().zip(bytes) {
+      *channel = Channel::from(*byte);
+    }
+    Some(ComponentsConfiguration { channels })
+  }
+}
+
+impl fmt::Display for ComponentsConfiguration {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let channels : Vec<String> = self.channels.iter().map(|c| c.to_string()).collect();
+    f.write_str(&channels.join(", "))
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FileSource {
+  FilmScanner,
+  ReflectionPrintScanner,
+  DigitalStillCamera,
+  Other(u8)
+}
+
+impl FileSource {
+  //some cameras write this tag as 4 bytes, only the first one is meaningful
+  pub fn parse(bytes: &[u8]) -> Option<FileSource> {
+    bytes.first().map(|b| match *b {
+      1 => FileSource::FilmScanner,
+      2 => FileSource::ReflectionPrintScanner,
+      3 => FileSource::DigitalStillCamera,
+      other => FileSource::Other(other)
+    })
+  }
+}
+
+impl fmt::Display for FileSource {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      FileSource::FilmScanner => f.write_str("Film Scanner"),
+      FileSource::ReflectionPrintScanner => f.write_str("Reflection Print Scanner"),
+      FileSource::DigitalStillCamera => f.write_str("Digital Camera"),
+      FileSource::Other(value) => write!(f, "Unknown ({})", value)
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SceneType {
+  DirectlyPhotographed,
+  Other(u8)
+}
+
+impl SceneType {
+  pub fn parse(bytes: &[u8]) -> Option<SceneType> {
+    bytes.first().map(|b| match *b {
+      1 => SceneType::DirectlyPhotographed,
+      other => SceneType::Other(other)
+    })
+  }
+}
+
+impl fmt::Display for SceneType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      SceneType::DirectlyPhotographed => f.write_str("Directly photographed"),
+      SceneType::Other(value) => write!(f, "Unknown ({})", value)
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CfaColor {
+  Red,
+  Green,
+  Blue,
+  Cyan,
+  Magenta,
+  Yellow,
+  White,
+  Unknown(u8)
+}
+
+impl From<u8> for CfaColor {
+  fn from(value: u8) -> CfaColor {
+    match value {
+      0 => CfaColor::Red,
+      1 => CfaColor::Green,
+      2 => CfaColor::Blue,
+      3 => CfaColor::Cyan,
+      4 => CfaColor::Magenta,
+      5 => CfaColor::Yellow,
+      6 => CfaColor::White,
+      _ => CfaColor::Unknown(value)
+    }
+  }
+}
+
+/** The color filter array of the sensor, as a matrix of `rows` x `columns` colors */
+#[derive(Debug, PartialEq, Clone)]
+pub struct CfaPattern {
+  pub columns: u16,
+  pub rows: u16,
+  pub colors: Vec<CfaColor>
+}
+
+impl CfaPattern {
+  /** The dimensions are stored as shorts in the tiff byte order,
+      but some cameras always write them big endian. If the dimensions
+      don't match the data length, we retry with the other byte order. */
+  pub fn parse(bytes: &[u8], endianness: Endianness) -> Option<CfaPattern> {
+    CfaPattern::parse_with_endianness(bytes, endianness).or_else(|| {
+      let swapped = match endianness {
+        Endianness::Little => Endianness::Big,
+        Endianness::Big => Endianness::Little
+      };
+      CfaPattern::parse_with_endianness(bytes, swapped)
+    })
+  }
+
+  fn parse_with_endianness(bytes: &[u8], endianness: Endianness) -> Option<CfaPattern> {
+    let (columns, rows) = read_dimensions(bytes, endianness)?;
+    let colors = bytes.get(4 ..)?;
+    if colors.len() != columns as usize * rows as usize {
+      return None;
+    }
+    Some(CfaPattern {
+      columns,
+      rows,
+      colors: colors.iter().map(|c| CfaColor::from(*c)).collect()
+    })
+  }
+
+  pub fn color_at(&self, row: u16, column: u16) -> Option<CfaColor> {
+    if row >= self.rows || column >= self.columns {
+      return None;
+    }
+    self.colors.get(row as usize * self.columns as usize + column as usize).cloned()
+  }
+}
+
+/** The layout shared by OECF and SpatialFrequencyResponse:
+    a row of column names followed by `rows` x `columns` signed rationals */
+#[derive(Debug, PartialEq, Clone)]
+pub struct RationalTable<'a> {
+  pub columns: u16,
+  pub rows: u16,
+  pub column_names: Vec<&'a str>,
+  pub values: Vec<(i32, i32)>
+}
+
+impl<'a> RationalTable<'a> {
+  pub fn parse(bytes: &'a [u8], endianness: Endianness) -> Option<RationalTable<'a>> {
+    let (columns, rows) = read_dimensions(bytes, endianness)?;
+    let mut remaining = bytes.get(4 ..)?;
+
+    let mut column_names = Vec::with_capacity(columns as usize);
+    for _ in 0 .. columns {
+      let end = remaining.iter().position(|b| *b == 0)?;
+      column_names.push(str::from_utf8(&remaining[0 .. end]).ok()?);
+      remaining = &remaining[end + 1 ..];
+    }
+
+    let value_count = columns as usize * rows as usize;
+    let mut cursor = Cursor::new(remaining, endianness);
+    let mut values = Vec::with_capacity(value_count);
+    for _ in 0 .. value_count {
+      let numerator : i32 = cursor.read_num()?;
+      let denominator : i32 = cursor.read_num()?;
+      values.push((numerator, denominator));
+    }
+
+    Some(RationalTable { columns, rows, column_names, values })
+  }
+
+  pub fn value(&self, row: u16, column: u16) -> Option<(i32, i32)> {
+    if row >= self.rows || column >= self.columns {
+      return None;
+    }
+    self.values.get(row as usize * self.columns as usize + column as usize).cloned()
+  }
+}
+
+/** DeviceSettingDescription: one UCS-2 string per camera setting */
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeviceSettings {
+  pub columns: u16,
+  pub rows: u16,
+  pub settings: Vec<String>
+}
+
+impl DeviceSettings {
+  pub fn parse(bytes: &[u8], endianness: Endianness) -> Option<DeviceSettings> {
+    let (columns, rows) = read_dimensions(bytes, endianness)?;
+    let mut cursor = Cursor::new(bytes.get(4 ..)?, endianness);
+
+    let mut settings = Vec::new();
+    let mut units = Vec::new();
+    while let Some(unit) = cursor.read_num::<u16>() {
+      if unit == 0 {
+        settings.push(decode_ucs2(&units));
+        units.clear();
+      } else {
+        units.push(unit);
+      }
+    }
+    //tolerate a missing terminator on the last setting
+    if !units.is_empty() {
+      settings.push(decode_ucs2(&units));
+    }
+
+    Some(DeviceSettings { columns, rows, settings })
+  }
+}
+
+fn decode_ucs2(units: &[u16]) -> String {
+  char::decode_utf16(units.iter().cloned())
+    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+    .collect()
+}
+
+fn read_dimensions(bytes: &[u8], endianness: Endianness) -> Option<(u16, u16)> {
+  let mut cursor = Cursor::new(bytes, endianness);
+  let columns : u16 = cursor.read_num()?;
+  let rows : u16 = cursor.read_num()?;
+  Some((columns, rows))
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum UndefinedValue<'a> {
+  ExifVersion(Version),
+  FlashpixVersion(Version),
+  InteropVersion(Version),
+  ComponentsConfiguration(ComponentsConfiguration),
+  FileSource(FileSource),
+  SceneType(SceneType),
+  CfaPattern(CfaPattern),
+  Oecf(RationalTable<'a>),
+  SpatialFrequencyResponse(RationalTable<'a>),
+  DeviceSettingDescription(DeviceSettings)
+}
+
+/** Decodes the value of an UNDEFINED tag with a known internal structure.
+    Returns None for other tags, or if the value is malformed. */
+pub fn decode_undefined<'a>(section: Section, tag_no: u16, bytes: &'a [u8], endianness: Endianness)
+-> Option<UndefinedValue<'a>>
+{
+  match (section, tag_no) {
+    (Section::SubIFD, 0x9000) =>
+      Version::parse(bytes).map(UndefinedValue::ExifVersion),
+    (Section::SubIFD, 0xA000) =>
+      Version::parse(bytes).map(UndefinedValue::FlashpixVersion),
+    (Section::InteropIFD, 0x0002) =>
+      Version::parse(bytes).map(UndefinedValue::InteropVersion),
+    (Section::SubIFD, 0x9101) =>
+      ComponentsConfiguration::parse(bytes).map(UndefinedValue::ComponentsConfiguration),
+    (Section::SubIFD, 0xA300) =>
+      FileSource::parse(bytes).map(UndefinedValue::FileSource),
+    (Section::SubIFD, 0xA301) =>
+      SceneType::parse(bytes).map(UndefinedValue::SceneType),
+    (Section::SubIFD, 0xA302) =>
+      CfaPattern::parse(bytes, endianness).map(UndefinedValue::CfaPattern),
+    (Section::SubIFD, 0x8828) =>
+      RationalTable::parse(bytes, endianness).map(UndefinedValue::Oecf),
+    (Section::SubIFD, 0xA20C) =>
+      RationalTable::parse(bytes, endianness).map(UndefinedValue::SpatialFrequencyResponse),
+    (Section::SubIFD, 0xA40B) =>
+      DeviceSettings::parse(bytes, endianness).map(UndefinedValue::DeviceSettingDescription),
+    _ => None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::tags::{read_tags, Section};
+  use ::tag::ExifVariant;
+  use ::test_fixtures::{JPEG_SAMPLE, JPEG_SAMPLE_EXIF_OFFSET};
+  use super::*;
+
+  #[test]
+  fn test_version() {
+    assert_eq!(Version::parse(b"0220"), Some(Version { major: 2, minor: 20 }));
+    assert_eq!(Version::parse(b"0231"), Some(Version { major: 2, minor: 31 }));
+    assert_eq!(Version::parse(b"02a0"), None);
+    assert_eq!(Version::parse(b"022"), None);
+  }
+
+  #[test]
+  fn test_components_configuration() {
+    let config = ComponentsConfiguration::parse(b"\x01\x02\x03\x00").unwrap();
+    assert_eq!(config.channels, [Channel::Y, Channel::Cb, Channel::Cr, Channel::None]);
+    assert!(ComponentsConfiguration::parse(b"\x01\x02").is_none());
+  }
+
+  #[test]
+  fn test_file_source_and_scene_type() {
+    assert_eq!(FileSource::parse(b"\x03"), Some(FileSource::DigitalStillCamera));
+    assert_eq!(FileSource::parse(b"\x03\x00\x00\x00"), Some(FileSource::DigitalStillCamera));
+    assert_eq!(FileSource::parse(b"\x09"), Some(FileSource::Other(9)));
+    assert_eq!(SceneType::parse(b"\x01"), Some(SceneType::DirectlyPhotographed));
+    assert_eq!(SceneType::parse(b""), None);
+  }
+
+  #[test]
+  fn test_cfa_pattern() {
+    let bytes = b"\x02\x00\x02\x00\x00\x01\x01\x02";
+    let pattern = CfaPattern::parse(bytes, Endianness::Little).unwrap();
+    assert_eq!(pattern.columns, 2);
+    assert_eq!(pattern.rows, 2);
+    assert_eq!(pattern.color_at(0, 0), Some(CfaColor::Red));
+    assert_eq!(pattern.color_at(1, 1), Some(CfaColor::Blue));
+    assert_eq!(pattern.color_at(2, 0), None);
+    //dimensions written big endian in a little endian file
+    let bytes = b"\x00\x02\x00\x02\x00\x01\x01\x02";
+    let pattern = CfaPattern::parse(bytes, Endianness::Little).unwrap();
+    assert_eq!(pattern.color_at(0, 1), Some(CfaColor::Green));
+  }
+
+  #[test]
+  fn test_rational_table() {
+    let bytes = b"\x00\x02\x00\x01ISO\0EV\0\x00\x00\x00\x64\x00\x00\x00\x01\xFF\xFF\xFF\xFF\x00\x00\x00\x03";
+    let table = RationalTable::parse(bytes, Endianness::Big).unwrap();
+    assert_eq!(table.column_names, vec!["ISO", "EV"]);
+    assert_eq!(table.value(0, 0), Some((100, 1)));
+    assert_eq!(table.value(0, 1), Some((-1, 3)));
+    assert_eq!(table.value(1, 0), None);
+    assert!(RationalTable::parse(&bytes[0 .. 20], Endianness::Big).is_none());
+  }
+
+  #[test]
+  fn test_device_settings() {
+    let bytes = b"\x00\x00\x00\x00\x00A\x00=\x001\x00\x00\x00B\x00\x00";
+    let settings = DeviceSettings::parse(bytes, Endianness::Big).unwrap();
+    assert_eq!(settings.settings, vec!["A=1".to_string(), "B".to_string()]);
+  }
+
+  #[test]
+  fn test_decode_sample_tags() {
+    let cursor = Cursor::new(JPEG_SAMPLE, Endianness::Little);
+    let cursor = cursor.with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).expect("EOF");
+    let mut decoded = 0;
+    for result in read_tags(cursor).unwrap() {
+      let (tag, section) = result.unwrap();
+      if let ExifVariant::Undefined(bytes) = tag.value {
+        match decode_undefined(section, tag.no, bytes, Endianness::Big) {
+          Some(UndefinedValue::ExifVersion(v)) =>
+            assert_eq!(v, Version { major: 2, minor: 20 }),
+          Some(UndefinedValue::FlashpixVersion(v)) =>
+            assert_eq!(v, Version { major: 1, minor: 0 }),
+          Some(UndefinedValue::ComponentsConfiguration(c)) =>
+            assert_eq!(c.channels[0], Channel::Y),
+          _ => panic!("unexpected undefined tag {:X}", tag.no)
+        }
+        decoded += 1;
+      }
+    }
+    assert_eq!(decoded, 3);
+  }
+
+  #[test]
+  fn test_decode_unknown_tag() {
+    assert!(decode_undefined(Section::IFD0, 0x9000, b"0220", Endianness::Big).is_none());
+  }
+}
@@ -0,0 +1,494 @@
+use std::collections::BTreeMap;
+use ::cursor::Endianness;
+use ::tags::Section;
+use ::tag::{ExifFormat, ExifVariant, EXIF_TAG_SIZE};
+
+/** An owned tag value, for writing */
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExifValue {
+  Text(String),
+  Bytes(Vec<u8>),
+  Undefined(Vec<u8>),
+  SignedByte(Vec<i8>),
+  UShort(Vec<u16>),
+  UInt(Vec<u32>),
+  UIntFraction(Vec<(u32, u32)>),
+  Short(Vec<i16>),
+  Int(Vec<i32>),
+  IntFraction(Vec<(i32, i32)>),
+  Float(Vec<f32>),
+  Double(Vec<f64>)
+}
+
+impl ExifValue {
+  pub fn from_variant(variant: &ExifVariant) -> ExifValue {
+    match *variant {
+      ExifVariant::Text(text) => ExifValue::Text(text.to_string()),
+      ExifVariant::Bytes(bytes) => ExifValue::Bytes(bytes.to_vec()),
+      ExifVariant::Undefined(bytes) => ExifValue::Undefined(bytes.to_vec()),
+      ExifVariant::SignedByte(ref c) => ExifValue::SignedByte(c.iter().collect()),
+      ExifVariant::UShort(ref c) => ExifValue::UShort(c.iter().collect()),
+      ExifVariant::UInt(ref c) => ExifValue::UInt(c.iter().collect()),
+      ExifVariant::UIntFraction(ref c) => ExifValue::UIntFraction(c.iter().collect()),
+      ExifVariant::Short(ref c) => ExifValue::Short(c.iter().collect()),
+      ExifVariant::Int(ref c) => ExifValue::Int(c.iter().collect()),
+      ExifVariant::IntFraction(ref c) => ExifValue::IntFraction(c.iter().collect()),
+      ExifVariant::Float(ref c) => ExifValue::Float(c.iter().collect()),
+      ExifVariant::Double(ref c) => ExifValue::Double(c.iter().collect())
+    }
+  }
+
+  pub fn format(&self) -> ExifFormat {
+    match *self {
+      ExifValue::Text(_) => ExifFormat::Text,
+      ExifValue::Bytes(_) => ExifFormat::UByte,
+      ExifValue::Undefined(_) => ExifFormat::Binary,
+      ExifValue::SignedByte(_) => ExifFormat::SignedByte,
+      ExifValue::UShort(_) => ExifFormat::UShort,
+      ExifValue::UInt(_) => ExifFormat::UInt,
+      ExifValue::UIntFraction(_) => ExifFormat::UIntFraction,
+      ExifValue::Short(_) => ExifFormat::Short,
+      ExifValue::Int(_) => ExifFormat::Int,
+      ExifValue::IntFraction(_) => ExifFormat::IntFraction,
+      ExifValue::Float(_) => ExifFormat::Float,
+      ExifValue::Double(_) => ExifFormat::Double
+    }
+  }
+
+  /** Text is always written with a NUL terminator, which counts as a component */
+  fn component_count(&self) -> usize {
+    match *self {
+      ExifValue::Text(ref s) if s.ends_with('\0') => s.len(),
+      ExifValue::Text(ref s) => s.len() + 1,
+      ExifValue::Bytes(ref v) | ExifValue::Undefined(ref v) => v.len(),
+      ExifValue::SignedByte(ref v) => v.len(),
+      ExifValue::UShort(ref v) => v.len(),
+      ExifValue::UInt(ref v) => v.len(),
+      ExifValue::UIntFraction(ref v) => v.len(),
+      ExifValue::Short(ref v) => v.len(),
+      ExifValue::Int(ref v) => v.len(),
+      ExifValue::IntFraction(ref v) => v.len(),
+      ExifValue::Float(ref v) => v.len(),
+      ExifValue::Double(ref v) => v.len()
+    }
+  }
+
+  pub fn byte_size(&self) -> usize {
+    self.component_count() * self.format().bytes_per_component()
+  }
+
+  pub fn write(&self, out: &mut Vec<u8>, endianness: Endianness) {
+    match *self {
+      ExifValue::Text(ref s) => {
+        out.extend_from_slice(s.as_bytes());
+        if !s.ends_with('\0') {
+          out.push(0);
+        }
+      },
+      ExifValue::Bytes(ref v) | ExifValue::Undefined(ref v) => out.extend_from_slice(v),
+      ExifValue::SignedByte(ref v) => out.extend(v.iter().map(|n| *n as u8)),
+      ExifValue::UShort(ref v) => for n in v {
+        write_u16(out, *n, endianness);
+      },
+      ExifValue::UInt(ref v) => for n in v {
+        write_u32(out, *n, endianness);
+      },
+      ExifValue::UIntFraction(ref v) => for &(n, d) in v {
+        write_u32(out, n, endianness);
+        write_u32(out, d, endianness);
+      },
+      ExifValue::Short(ref v) => for n in v {
+        write_u16(out, *n as u16, endianness);
+      },
+      ExifValue::Int(ref v) => for n in v {
+        write_u32(out, *n as u32, endianness);
+      },
+      ExifValue::IntFraction(ref v) => for &(n, d) in v {
+        write_u32(out, n as u32, endianness);
+        write_u32(out, d as u32, endianness);
+      },
+      ExifValue::Float(ref v) => for n in v {
+        write_u32(out, n.to_bits(), endianness);
+      },
+      ExifValue::Double(ref v) => for n in v {
+        let bits = n.to_bits();
+        let (high, low) = ((bits >> 32) as u32, bits as u32);
+        match endianness {
+          Endianness::Little => { write_u32(out, low, endianness); write_u32(out, high, endianness); },
+          Endianness::Big => { write_u32(out, high, endianness); write_u32(out, low, endianness); }
+        }
+      }
+    }
+  }
+}
+
+pub fn write_u16(out: &mut Vec<u8>, n: u16, endianness: Endianness) {
+  match endianness {
+    Endianness::Little => out.extend_from_slice(&n.to_le_bytes()),
+    Endianness::Big => out.extend_from_slice(&n.to_be_bytes())
+  }
+}
+
+pub fn write_u32(out: &mut Vec<u8>, n: u32, endianness: Endianness) {
+  match endianness {
+    Endianness::Little => out.extend_from_slice(&n.to_le_bytes()),
+    Endianness::Big => out.extend_from_slice(&n.to_be_bytes())
+  }
+}
+
+/** tags that hold offsets, which the writer fills in itself */
+pub fn is_offset_tag(section: Section, tag_no: u16) -> bool {
+  matches!((section, tag_no),
+    (Section::IFD0, 0x8769) |
+    (Section::IFD0, 0x8825) |
+    (Section::SubIFD, 0xA005) |
+    (Section::IFD1, 0x0201) |
+    (Section::IFD1, 0x0202))
+}
+
+/** Builds the TIFF structure of an Exif APP1 segment from tags grouped per section.
+    The offsets between the IFDs and to the thumbnail are filled in by the writer,
+    any tags that hold them are ignored. */
+pub struct ExifWriter {
+  endianness: Endianness,
+  ifd0: BTreeMap<u16, ExifValue>,
+  sub_ifd: BTreeMap<u16, ExifValue>,
+  interop: BTreeMap<u16, ExifValue>,
+  gps: BTreeMap<u16, ExifValue>,
+  ifd1: BTreeMap<u16, ExifValue>,
+  thumbnail: Option<Vec<u8>>
+}
+
+struct DirectoryLayout<'w> {
+  section: Section,
+  tags: Vec<(u16, ExifValue)>,
+  borrowed: &'w BTreeMap<u16, ExifValue>,
+  offset: usize,
+  size: usize
+}
+
+impl ExifWriter {
+  pub fn new(endianness: Endianness) -> ExifWriter {
+    ExifWriter {
+      endianness,
+      ifd0: BTreeMap::new(),
+      sub_ifd: BTreeMap::new(),
+      interop: BTreeMap::new(),
+      gps: BTreeMap::new(),
+      ifd1: BTreeMap::new(),
+      thumbnail: None
+    }
+  }
+
+  /** MakerNote tags can't be written, the MakerNote is kept as a whole */
+  fn section_tags(&self, section: Section) -> Option<&BTreeMap<u16, ExifValue>> {
+    match section {
+      Section::IFD0 => Some(&self.ifd0),
+      Section::SubIFD => Some(&self.sub_ifd),
+      Section::InteropIFD => Some(&self.interop),
+      Section::GPS => Some(&self.gps),
+      Section::IFD1 => Some(&self.ifd1),
+      Section::MakerNote(_) => None
+    }
+  }
+
+  fn section_tags_mut(&mut self, section: Section) -> Option<&mut BTreeMap<u16, ExifValue>> {
+    match section {
+      Section::IFD0 => Some(&mut self.ifd0),
+      Section::SubIFD => Some(&mut self.sub_ifd),
+      Section::InteropIFD => Some(&mut self.interop),
+      Section::GPS => Some(&mut self.gps),
+      Section::IFD1 => Some(&mut self.ifd1),
+      Section::MakerNote(_) => None
+    }
+  }
+
+  /** Adds a tag, replacing any tag with the same number in the section.
+      Tags in `Section::MakerNote` are ignored. */
+  pub fn add_tag(&mut self, section: Section, tag_no: u16, value: ExifValue) -> &mut ExifWriter {
+    if !is_offset_tag(section, tag_no) {
+      if let Some(tags) = self.section_tags_mut(section) {
+        tags.insert(tag_no, value);
+      }
+    }
+    self
+  }
+
+  pub fn remove_tag(&mut self, section: Section, tag_no: u16) -> Option<ExifValue> {
+    self.section_tags_mut(section)?.remove(&tag_no)
+  }
+
+  pub fn tag(&self, section: Section, tag_no: u16) -> Option<&ExifValue> {
+    self.section_tags(section)?.get(&tag_no)
+  }
+
+  /** the JPEG thumbnail, stored after IFD1 */
+  pub fn set_thumbnail(&mut self, jpeg: Vec<u8>) -> &mut ExifWriter {
+    self.thumbnail = Some(jpeg);
+    self
+  }
+
+  /** Lays out the directories in the order they will be written.
+      IFD0 is always written, the other IFDs only if they contain tags
+      or, for the Exif IFD, if it is needed to point to the Interop IFD. */
+  fn layout(&self) -> Vec<DirectoryLayout<'_>> {
+    let has_interop = !self.interop.is_empty();
+    let has_sub_ifd = !self.sub_ifd.is_empty() || has_interop;
+    let has_gps = !self.gps.is_empty();
+    let has_ifd1 = !self.ifd1.is_empty() || self.thumbnail.is_some();
+
+    let mut sections = vec![Section::IFD0];
+    if has_sub_ifd {
+      sections.push(Section::SubIFD);
+    }
+    if has_interop {
+      sections.push(Section::InteropIFD);
+    }
+    if has_gps {
+      sections.push(Section::GPS);
+    }
+    if has_ifd1 {
+      sections.push(Section::IFD1);
+    }
+
+    //the tiff header takes the first 8 bytes
+    let mut offset = 8;
+    sections.into_iter().filter_map(|section| {
+      self.section_tags(section).map(|borrowed| (section, borrowed))
+    }).map(|(section, borrowed)| {
+      //placeholders for the offset tags, so they are counted and sorted in
+      let mut tags = Vec::new();
+      match section {
+        Section::IFD0 => {
+          if has_sub_ifd {
+            tags.push((0x8769, ExifValue::UInt(vec![0])));
+          }
+          if has_gps {
+            tags.push((0x8825, ExifValue::UInt(vec![0])));
+          }
+        },
+        Section::SubIFD if has_interop => {
+          tags.push((0xA005, ExifValue::UInt(vec![0])));
+        },
+        Section::IFD1 => if let Some(ref thumbnail) = self.thumbnail {
+          tags.push((0x0201, ExifValue::UInt(vec![0])));
+          tags.push((0x0202, ExifValue::UInt(vec![thumbnail.len() as u32])));
+        },
+        _ => ()
+      };
+      let entry_count = borrowed.len() + tags.len();
+      let data_size : usize = borrowed.values()
+        .map(|v| v.byte_size())
+        .filter(|size| *size > 4)
+        .map(word_align)
+        .sum();
+      let size = 2 + entry_count * EXIF_TAG_SIZE + 4 + data_size;
+      let layout = DirectoryLayout { section, tags, borrowed, offset, size };
+      offset += size;
+      layout
+    }).collect()
+  }
+
+  /** writes the TIFF header and all IFDs */
+  pub fn write_tiff(&self) -> Vec<u8> {
+    let endianness = self.endianness;
+    let mut layouts = self.layout();
+
+    let offset_of = |layouts: &[DirectoryLayout], section: Section| {
+      layouts.iter().find(|l| l.section == section).map(|l| l.offset as u32)
+    };
+    let thumbnail_offset = layouts.last()
+      .map(|l| (l.offset + l.size) as u32)
+      .unwrap_or(0);
+    let ifd1_offset = offset_of(&layouts, Section::IFD1).unwrap_or(0);
+    let sub_ifd_offset = offset_of(&layouts, Section::SubIFD).unwrap_or(0);
+    let gps_offset = offset_of(&layouts, Section::GPS).unwrap_or(0);
+    let interop_offset = offset_of(&layouts, Section::InteropIFD).unwrap_or(0);
+
+    //now all offsets are known, fill in the placeholders
+    for layout in layouts.iter_mut() {
+      for &mut (tag_no, ref mut value) in layout.tags.iter_mut() {
+        let offset = match tag_no {
+          0x8769 => sub_ifd_offset,
+          0x8825 => gps_offset,
+          0xA005 => interop_offset,
+          0x0201 => thumbnail_offset,
+          _ => continue
+        };
+        *value = ExifValue::UInt(vec![offset]);
+      }
+    }
+
+    let mut out = Vec::new();
+    match endianness {
+      Endianness::Little => out.extend_from_slice(b"II"),
+      Endianness::Big => out.extend_from_slice(b"MM")
+    };
+    write_u16(&mut out, 0x002A, endianness);
+    write_u32(&mut out, 8, endianness);
+
+    for layout in layouts.iter() {
+      debug_assert_eq!(out.len(), layout.offset);
+      let mut entries : Vec<(u16, &ExifValue)> = layout.borrowed.iter()
+        .map(|(no, value)| (*no, value))
+        .chain(layout.tags.iter().map(|&(no, ref value)| (no, value)))
+        .collect();
+      entries.sort_by_key(|&(no, _)| no);
+
+      let next_ifd = if layout.section == Section::IFD0 { ifd1_offset } else { 0 };
+      write_ifd(&mut out, &entries, layout.offset, next_ifd, endianness);
+    }
+
+    if let Some(ref thumbnail) = self.thumbnail {
+      out.extend_from_slice(thumbnail);
+    }
+    out
+  }
+
+  /** the payload of an APP1 segment: the Exif header followed by the TIFF structure */
+  pub fn write_app1(&self) -> Vec<u8> {
+    let mut out = b"Exif\0\0".to_vec();
+    out.extend_from_slice(&self.write_tiff());
+    out
+  }
+}
+
+/** Writes an IFD with the entries in the given order, followed by the values that
+    don't fit in the entries. `offset` is where the IFD ends up, relative to what
+    the value offsets are relative to, like the TIFF header. */
+pub(crate) fn write_ifd(out: &mut Vec<u8>, entries: &[(u16, &ExifValue)], offset: usize, next_ifd: u32, endianness: Endianness) {
+  let directory_size = 2 + entries.len() * EXIF_TAG_SIZE + 4;
+  let mut data = Vec::new();
+
+  write_u16(out, entries.len() as u16, endianness);
+  for &(tag_no, value) in entries {
+    write_u16(out, tag_no, endianness);
+    write_u16(out, value.format().number(), endianness);
+    write_u32(out, value.component_count() as u32, endianness);
+    if value.byte_size() > 4 {
+      write_u32(out, (offset + directory_size + data.len()) as u32, endianness);
+      value.write(&mut data, endianness);
+      if data.len() % 2 != 0 {
+        data.push(0);
+      }
+    } else {
+      //values of 4 bytes or less are stored inline, left aligned
+      let start = out.len();
+      value.write(out, endianness);
+      out.resize(start + 4, 0);
+    }
+  }
+  write_u32(out, next_ifd, endianness);
+  out.extend_from_slice(&data);
+}
+
+fn word_align(size: usize) -> usize {
+  size + size % 2
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::tags::{read_tags, Section};
+  use ::test_fixtures::{JPEG_SAMPLE, JPEG_SAMPLE_EXIF_OFFSET, expected_segments};
+  use super::{ExifWriter, ExifValue};
+
+  fn read_back(app1: &[u8]) -> Vec<(Section, u16, ExifValue)> {
+    let cursor = Cursor::new(app1, Endianness::Big);
+    read_tags(cursor).unwrap()
+      .map(|r| r.unwrap())
+      .map(|(tag, section)| (section, tag.no, ExifValue::from_variant(&tag.value)))
+      .collect()
+  }
+
+  fn sample_writer(endianness: Endianness) -> ExifWriter {
+    let mut writer = ExifWriter::new(endianness);
+    writer
+      .add_tag(Section::IFD0, 0x010F, ExifValue::Text("Canon".to_string()))
+      .add_tag(Section::IFD0, 0x0112, ExifValue::UShort(vec![6]))
+      .add_tag(Section::SubIFD, 0x829A, ExifValue::UIntFraction(vec![(1, 250)]))
+      .add_tag(Section::SubIFD, 0x9204, ExifValue::IntFraction(vec![(-1, 3)]))
+      .add_tag(Section::SubIFD, 0x9000, ExifValue::Undefined(b"0231".to_vec()))
+      .add_tag(Section::InteropIFD, 0x0001, ExifValue::Text("R98".to_string()))
+      .add_tag(Section::GPS, 0x0000, ExifValue::Bytes(vec![2, 3, 0, 0]))
+      .add_tag(Section::GPS, 0x0002, ExifValue::UIntFraction(vec![(47, 1), (36, 1), (2280, 100)]))
+      .add_tag(Section::IFD1, 0x0103, ExifValue::UShort(vec![6]))
+      .set_thumbnail(vec![0xFF, 0xD8, 0xFF, 0xD9]);
+    writer
+  }
+
+  #[test]
+  fn test_round_trip_both_endiannesses() {
+    for endianness in [Endianness::Little, Endianness::Big].iter() {
+      let app1 = sample_writer(*endianness).write_app1();
+      let tags = read_back(&app1);
+      assert_eq!(tags, vec![
+        (Section::IFD0, 0x010F, ExifValue::Text("Canon\0".to_string())),
+        (Section::IFD0, 0x0112, ExifValue::UShort(vec![6])),
+        (Section::IFD0, 0x8769, ExifValue::UInt(vec![68])),
+        (Section::IFD0, 0x8825, ExifValue::UInt(vec![156])),
+        (Section::IFD1, 0x0103, ExifValue::UShort(vec![6])),
+        (Section::IFD1, 0x0201, ExifValue::UInt(vec![252])),
+        (Section::IFD1, 0x0202, ExifValue::UInt(vec![4])),
+        (Section::GPS, 0x0000, ExifValue::Bytes(vec![2, 3, 0, 0])),
+        (Section::GPS, 0x0002, ExifValue::UIntFraction(vec![(47, 1), (36, 1), (2280, 100)])),
+        (Section::SubIFD, 0x829A, ExifValue::UIntFraction(vec![(1, 250)])),
+        (Section::SubIFD, 0x9000, ExifValue::Undefined(b"0231".to_vec())),
+        (Section::SubIFD, 0x9204, ExifValue::IntFraction(vec![(-1, 3)])),
+        (Section::SubIFD, 0xA005, ExifValue::UInt(vec![138])),
+        (Section::InteropIFD, 0x0001, ExifValue::Text("R98\0".to_string()))
+      ]);
+      //the thumbnail comes last
+      assert_eq!(&app1[6 + 252 ..], &[0xFF, 0xD8, 0xFF, 0xD9]);
+    }
+  }
+
+  #[test]
+  fn test_out_of_line_values_are_word_aligned() {
+    let mut writer = ExifWriter::new(Endianness::Big);
+    writer
+      .add_tag(Section::IFD0, 0x010F, ExifValue::Text("Nokia".to_string()))
+      .add_tag(Section::IFD0, 0x0110, ExifValue::Text("Lumia".to_string()));
+    let tiff = writer.write_tiff();
+    //header + 2 entries + next ifd link
+    let data_start = 8 + 2 + 2 * 12 + 4;
+    assert_eq!(&tiff[data_start ..], b"Nokia\0Lumia\0");
+    //offset of the second value
+    assert_eq!(&tiff[8 + 2 + 12 + 8 .. 8 + 2 + 12 + 12], &[0, 0, 0, 44]);
+
+    let mut writer = ExifWriter::new(Endianness::Big);
+    writer
+      .add_tag(Section::IFD0, 0x010F, ExifValue::Text("Sony".to_string()))
+      .add_tag(Section::IFD0, 0x0110, ExifValue::Text("A7".to_string()));
+    let tiff = writer.write_tiff();
+    assert_eq!(&tiff[8 + 2 + 12 ..], b"\x01\x10\x00\x02\x00\x00\x00\x03A7\0\0\0\0\0\0Sony\0\0");
+  }
+
+  #[test]
+  fn test_offset_tags_are_ignored() {
+    let mut writer = ExifWriter::new(Endianness::Little);
+    writer.add_tag(Section::IFD0, 0x8769, ExifValue::UInt(vec![1234]));
+    let tags = read_back(&writer.write_app1());
+    assert!(tags.is_empty());
+  }
+
+  #[test]
+  fn test_rewrite_sample() {
+    let cursor = Cursor::new(JPEG_SAMPLE, Endianness::Big);
+    let cursor = cursor.with_skip_or_fail(JPEG_SAMPLE_EXIF_OFFSET).expect("EOF");
+    let segment = &expected_segments()[2];
+    let original = read_back(&JPEG_SAMPLE[segment.offset .. segment.offset + segment.len]);
+
+    let mut writer = ExifWriter::new(Endianness::Little);
+    for result in read_tags(cursor).unwrap() {
+      let (tag, section) = result.unwrap();
+      writer.add_tag(section, tag.no, ExifValue::from_variant(&tag.value));
+    }
+    let rewritten = read_back(&writer.write_app1());
+
+    let without_offsets = |tags: Vec<(Section, u16, ExifValue)>| -> Vec<(Section, u16, ExifValue)> {
+      tags.into_iter().filter(|&(_, no, _)| no != 0x8769 && no != 0x8825).collect()
+    };
+    assert_eq!(without_offsets(original), without_offsets(rewritten));
+  }
+}
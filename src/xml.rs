@@ -0,0 +1,346 @@
+use ::error::{ParseError, ParseResult};
+
+pub const XML_NAMESPACE : &str = "http://www.w3.org/XML/1998/namespace";
+//untrusted documents could nest deep enough to overflow the stack
+const MAX_DEPTH : usize = 128;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Attribute {
+  pub namespace: String,
+  pub name: String,
+  pub value: String
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Node {
+  Element(Element),
+  Text(String)
+}
+
+/** An XML element with its namespace prefix resolved.
+    Whitespace-only text between elements is dropped. */
+#[derive(Debug, PartialEq, Clone)]
+pub struct Element {
+  pub namespace: String,
+  pub name: String,
+  /** the attributes, without the namespace declarations */
+  pub attributes: Vec<Attribute>,
+  pub children: Vec<Node>
+}
+
+impl Element {
+  pub fn is(&self, namespace: &str, name: &str) -> bool {
+    self.namespace == namespace && self.name == name
+  }
+
+  pub fn attribute(&self, namespace: &str, name: &str) -> Option<&str> {
+    self.attributes.iter()
+      .find(|a| a.namespace == namespace && a.name == name)
+      .map(|a| a.value.as_str())
+  }
+
+  /** the child elements */
+  pub fn elements(&self) -> impl Iterator<Item=&Element> {
+    self.children.iter().filter_map(|node| match *node {
+      Node::Element(ref element) => Some(element),
+      Node::Text(_) => None
+    })
+  }
+
+  /** the text of the element, without the text of its child elements */
+  pub fn text(&self) -> String {
+    self.children.iter().filter_map(|node| match *node {
+      Node::Text(ref text) => Some(text.as_str()),
+      Node::Element(_) => None
+    }).collect()
+  }
+
+  /** the first descendant, or this element itself, with the given name */
+  pub fn find(&self, namespace: &str, name: &str) -> Option<&Element> {
+    if self.is(namespace, name) {
+      return Some(self);
+    }
+    self.elements().filter_map(|e| e.find(namespace, name)).next()
+  }
+}
+
+struct Parser<'a> {
+  data: &'a str,
+  pos: usize,
+  /** the namespace declarations in scope, as (prefix, uri) */
+  namespaces: Vec<(String, String)>
+}
+
+fn is_name_end(b: u8) -> bool {
+  matches!(b, b' ' | b'\t' | b'\r' | b'\n' | b'/' | b'>' | b'=')
+}
+
+impl<'a> Parser<'a> {
+  fn error(&self) -> ParseError {
+    ParseError::InvalidXml { offset: self.pos }
+  }
+
+  fn rest(&self) -> &'a str {
+    &self.data[self.pos ..]
+  }
+
+  fn peek(&self) -> Option<u8> {
+    self.data.as_bytes().get(self.pos).cloned()
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n') = self.peek() {
+      self.pos += 1;
+    }
+  }
+
+  fn expect(&mut self, token: &str) -> ParseResult<()> {
+    if self.rest().starts_with(token) {
+      self.pos += token.len();
+      Ok(())
+    } else {
+      Err(self.error())
+    }
+  }
+
+  /** skips past the next occurrence of `end` */
+  fn skip_past(&mut self, end: &str) -> ParseResult<&'a str> {
+    match self.rest().find(end) {
+      Some(index) => {
+        let skipped = &self.rest()[.. index];
+        self.pos += index + end.len();
+        Ok(skipped)
+      },
+      None => Err(self.error())
+    }
+  }
+
+  fn read_name(&mut self) -> ParseResult<&'a str> {
+    let start = self.pos;
+    while let Some(b) = self.peek() {
+      if is_name_end(b) {
+        break;
+      }
+      self.pos += 1;
+    }
+    if self.pos == start {
+      Err(self.error())
+    } else {
+      Ok(&self.data[start .. self.pos])
+    }
+  }
+
+  /** skips comments, processing instructions and the doctype */
+  fn skip_misc(&mut self) -> ParseResult<()> {
+    loop {
+      self.skip_whitespace();
+      let rest = self.rest();
+      if rest.starts_with("<!--") {
+        self.skip_past("-->")?;
+      } else if rest.starts_with("<?") {
+        self.skip_past("?>")?;
+      } else if rest.starts_with("<!DOCTYPE") {
+        self.skip_past(">")?;
+      } else {
+        return Ok(());
+      }
+    }
+  }
+
+  fn resolve(&self, qname: &str, is_attribute: bool) -> ParseResult<(String, String)> {
+    let (prefix, name) = match qname.find(':') {
+      Some(index) => (&qname[.. index], &qname[index + 1 ..]),
+      //unprefixed attributes have no namespace, unprefixed elements the default one
+      None if is_attribute => return Ok((String::new(), qname.to_string())),
+      None => ("", qname)
+    };
+    if prefix == "xml" {
+      return Ok((XML_NAMESPACE.to_string(), name.to_string()));
+    }
+    let uri = self.namespaces.iter().rev()
+      .find(|(p, _)| p == prefix)
+      .map(|(_, uri)| uri.clone());
+    match uri {
+      Some(uri) => Ok((uri, name.to_string())),
+      None if prefix.is_empty() => Ok((String::new(), name.to_string())),
+      None => Err(self.error())
+    }
+  }
+
+  fn parse_element(&mut self, depth: usize) -> ParseResult<Element> {
+    if depth > MAX_DEPTH {
+      return Err(self.error());
+    }
+    self.expect("<")?;
+    let qname = self.read_name()?;
+
+    let mut raw_attributes = Vec::new();
+    let is_empty = loop {
+      self.skip_whitespace();
+      if self.rest().starts_with("/>") {
+        self.pos += 2;
+        break true;
+      }
+      if self.rest().starts_with('>') {
+        self.pos += 1;
+        break false;
+      }
+      let name = self.read_name()?;
+      self.skip_whitespace();
+      self.expect("=")?;
+      self.skip_whitespace();
+      let quote = match self.peek() {
+        Some(b'"') => "\"",
+        Some(b'\'') => "'",
+        _ => return Err(self.error())
+      };
+      self.pos += 1;
+      let value_offset = self.pos;
+      let value = decode_entities(self.skip_past(quote)?, value_offset)?;
+      raw_attributes.push((name, value));
+    };
+
+    let scope = self.namespaces.len();
+    for &(name, ref value) in raw_attributes.iter() {
+      if name == "xmlns" {
+        self.namespaces.push((String::new(), value.clone()));
+      } else if let Some(prefix) = name.strip_prefix("xmlns:") {
+        self.namespaces.push((prefix.to_string(), value.clone()));
+      }
+    }
+
+    let (namespace, name) = self.resolve(qname, false)?;
+    let mut attributes = Vec::new();
+    for (attribute_name, value) in raw_attributes {
+      if attribute_name == "xmlns" || attribute_name.starts_with("xmlns:") {
+        continue;
+      }
+      let (namespace, name) = self.resolve(attribute_name, true)?;
+      attributes.push(Attribute { namespace, name, value });
+    }
+
+    let mut children = Vec::new();
+    if !is_empty {
+      loop {
+        let rest = self.rest();
+        if rest.starts_with("</") {
+          self.pos += 2;
+          if self.read_name()? != qname {
+            return Err(self.error());
+          }
+          self.skip_whitespace();
+          self.expect(">")?;
+          break;
+        } else if rest.starts_with("<!--") {
+          self.skip_past("-->")?;
+        } else if rest.starts_with("<![CDATA[") {
+          self.pos += 9;
+          let text = self.skip_past("]]>")?;
+          children.push(Node::Text(text.to_string()));
+        } else if rest.starts_with("<?") {
+          self.skip_past("?>")?;
+        } else if rest.starts_with('<') {
+          children.push(Node::Element(self.parse_element(depth + 1)?));
+        } else if rest.is_empty() {
+          return Err(self.error());
+        } else {
+          let text_offset = self.pos;
+          let end = rest.find('<').unwrap_or(rest.len());
+          self.pos += end;
+          let text = &rest[.. end];
+          if !text.trim().is_empty() {
+            children.push(Node::Text(decode_entities(text, text_offset)?));
+          }
+        }
+      }
+    }
+
+    self.namespaces.truncate(scope);
+    Ok(Element { namespace, name, attributes, children })
+  }
+}
+
+/** replaces the predefined and numeric character references */
+fn decode_entities(text: &str, offset: usize) -> ParseResult<String> {
+  if !text.contains('&') {
+    return Ok(text.to_string());
+  }
+  let mut decoded = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find('&') {
+    decoded.push_str(&rest[.. start]);
+    let error_offset = offset + text.len() - rest.len() + start;
+    let error = || ParseError::InvalidXml { offset: error_offset };
+    let end = rest[start ..].find(';').ok_or_else(error)? + start;
+    let entity = &rest[start + 1 .. end];
+    let c = match entity {
+      "lt" => '<',
+      "gt" => '>',
+      "amp" => '&',
+      "quot" => '"',
+      "apos" => '\'',
+      _ => {
+        let code = if let Some(hex) = entity.strip_prefix("#x") {
+          u32::from_str_radix(hex, 16).ok()
+        } else if let Some(decimal) = entity.strip_prefix('#') {
+          decimal.parse().ok()
+        } else {
+          None
+        };
+        code.and_then(::std::char::from_u32).ok_or_else(error)?
+      }
+    };
+    decoded.push(c);
+    rest = &rest[end + 1 ..];
+  }
+  decoded.push_str(rest);
+  Ok(decoded)
+}
+
+/** Parses an XML document into its root element. Comments, processing instructions
+    and the doctype are skipped, DTDs and custom entities are not supported. */
+pub fn parse_xml(data: &str) -> ParseResult<Element> {
+  let mut parser = Parser { data, pos: 0, namespaces: Vec::new() };
+  parser.skip_misc()?;
+  let root = parser.parse_element(0)?;
+  parser.skip_misc()?;
+  Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+  use ::error::ParseError;
+  use super::{parse_xml, XML_NAMESPACE};
+
+  #[test]
+  fn test_namespaces() {
+    let root = parse_xml(r#"<?xml version="1.0"?>
+      <!-- a comment -->
+      <a:root xmlns:a="urn:a" xmlns="urn:default" a:attr='1' plain="2">
+        <child xml:lang="en">x &amp; y &#x41;&#66;</child>
+        <b:child xmlns:b="urn:b"><![CDATA[<raw>]]></b:child>
+        <a:empty/>
+      </a:root>"#).unwrap();
+
+    assert!(root.is("urn:a", "root"));
+    assert_eq!(root.attribute("urn:a", "attr"), Some("1"));
+    assert_eq!(root.attribute("", "plain"), Some("2"));
+    let children : Vec<_> = root.elements().collect();
+    assert_eq!(children.len(), 3);
+    assert!(children[0].is("urn:default", "child"));
+    assert_eq!(children[0].attribute(XML_NAMESPACE, "lang"), Some("en"));
+    assert_eq!(children[0].text(), "x & y AB");
+    assert!(children[1].is("urn:b", "child"));
+    assert_eq!(children[1].text(), "<raw>");
+    assert!(children[2].children.is_empty());
+    assert!(root.find("urn:a", "empty").is_some());
+  }
+
+  #[test]
+  fn test_invalid_xml() {
+    assert_eq!(parse_xml("<a><b></a>"), Err(ParseError::InvalidXml { offset: 9 }));
+    assert_eq!(parse_xml("<p:a/>"), Err(ParseError::InvalidXml { offset: 6 }));
+    assert!(parse_xml("<a>&unknown;</a>").is_err());
+    assert!(parse_xml(&"<a>".repeat(1000)).is_err());
+  }
+}
@@ -0,0 +1,464 @@
+use std::str;
+use ::cursor::{Cursor, Endianness};
+use ::error::{ParseError, ParseResult};
+use ::jpeg::{JPEGSegmentIterator, SegmentMarker};
+use ::md5::md5_hex;
+use ::xml::{parse_xml, Element, XML_NAMESPACE};
+
+/** the start of an APP1 segment containing an XMP packet */
+pub const XMP_SEGMENT_HEADER : &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/** the start of an APP1 segment containing a chunk of extended XMP */
+pub const EXTENDED_XMP_SEGMENT_HEADER : &[u8] = b"http://ns.adobe.com/xmp/extension/\0";
+const GUID_LEN : usize = 32;
+
+pub const NS_RDF : &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+pub const NS_XMP : &str = "http://ns.adobe.com/xap/1.0/";
+pub const NS_EXIF : &str = "http://ns.adobe.com/exif/1.0/";
+pub const NS_DC : &str = "http://purl.org/dc/elements/1.1/";
+pub const NS_PHOTOSHOP : &str = "http://ns.adobe.com/photoshop/1.0/";
+pub const NS_LIGHTROOM : &str = "http://ns.adobe.com/lightroom/1.0/";
+pub const NS_XMP_NOTE : &str = "http://ns.adobe.com/xmp/note/";
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum XmpValue {
+  Simple(String),
+  /** an unordered array, `rdf:Bag` */
+  Bag(Vec<XmpValue>),
+  /** an ordered array, `rdf:Seq` */
+  Seq(Vec<XmpValue>),
+  /** alternatives with their `xml:lang`, `rdf:Alt` */
+  Alt(Vec<(Option<String>, XmpValue)>),
+  Struct(Vec<XmpProperty>)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct XmpProperty {
+  pub namespace: String,
+  pub name: String,
+  pub value: XmpValue
+}
+
+impl XmpValue {
+  pub fn as_str(&self) -> Option<&str> {
+    match *self {
+      XmpValue::Simple(ref s) => Some(s),
+      _ => None
+    }
+  }
+
+  /** the items of a Bag or Seq */
+  pub fn items(&self) -> &[XmpValue] {
+    match *self {
+      XmpValue::Bag(ref items) | XmpValue::Seq(ref items) => items,
+      _ => &[]
+    }
+  }
+
+  /** the `x-default` alternative, or the first one if there is none */
+  pub fn default_alt(&self) -> Option<&XmpValue> {
+    match *self {
+      XmpValue::Alt(ref alts) => alts.iter()
+        .find(|(lang, _)| lang.as_ref().map(|l| l.as_str()) == Some("x-default"))
+        .or_else(|| alts.first())
+        .map(|(_, value)| value),
+      _ => None
+    }
+  }
+
+  /** a field of a struct */
+  pub fn field(&self, namespace: &str, name: &str) -> Option<&XmpValue> {
+    match *self {
+      XmpValue::Struct(ref fields) => find_property(fields, namespace, name),
+      _ => None
+    }
+  }
+}
+
+fn find_property<'a>(properties: &'a [XmpProperty], namespace: &str, name: &str) -> Option<&'a XmpValue> {
+  properties.iter()
+    .find(|p| p.namespace == namespace && p.name == name)
+    .map(|p| &p.value)
+}
+
+/** attributes that are part of the RDF syntax rather than properties */
+fn is_syntax_attribute(namespace: &str) -> bool {
+  namespace == NS_RDF || namespace == XML_NAMESPACE || namespace.is_empty()
+}
+
+fn attribute_properties(element: &Element) -> Vec<XmpProperty> {
+  element.attributes.iter()
+    .filter(|a| !is_syntax_attribute(&a.namespace))
+    .map(|a| XmpProperty {
+      namespace: a.namespace.clone(),
+      name: a.name.clone(),
+      value: XmpValue::Simple(a.value.clone())
+    })
+    .collect()
+}
+
+/** the properties of an `rdf:Description`, written as attributes or child elements */
+fn description_properties(description: &Element) -> Vec<XmpProperty> {
+  let mut properties = attribute_properties(description);
+  properties.extend(description.elements().map(property));
+  properties
+}
+
+fn property(element: &Element) -> XmpProperty {
+  XmpProperty {
+    namespace: element.namespace.clone(),
+    name: element.name.clone(),
+    value: property_value(element)
+  }
+}
+
+fn list_items(container: &Element) -> impl Iterator<Item=&Element> {
+  container.elements().filter(|e| e.is(NS_RDF, "li"))
+}
+
+fn property_value(element: &Element) -> XmpValue {
+  if let Some(resource) = element.attribute(NS_RDF, "resource") {
+    return XmpValue::Simple(resource.to_string());
+  }
+  if element.attribute(NS_RDF, "parseType") == Some("Resource") {
+    return XmpValue::Struct(element.elements().map(property).collect());
+  }
+  if let Some(child) = element.elements().next() {
+    return if child.is(NS_RDF, "Bag") {
+      XmpValue::Bag(list_items(child).map(property_value).collect())
+    } else if child.is(NS_RDF, "Seq") {
+      XmpValue::Seq(list_items(child).map(property_value).collect())
+    } else if child.is(NS_RDF, "Alt") {
+      XmpValue::Alt(list_items(child)
+        .map(|li| (li.attribute(XML_NAMESPACE, "lang").map(|l| l.to_string()), property_value(li)))
+        .collect())
+    } else if child.is(NS_RDF, "Description") {
+      XmpValue::Struct(description_properties(child))
+    } else {
+      XmpValue::Struct(element.elements().map(property).collect())
+    };
+  }
+  //a struct can be written as the attributes of an empty element
+  let fields = attribute_properties(element);
+  if !fields.is_empty() {
+    return XmpValue::Struct(fields);
+  }
+  XmpValue::Simple(element.text())
+}
+
+/** The properties of an XMP packet, from all of its `rdf:Description` elements */
+#[derive(Debug, PartialEq, Clone)]
+pub struct Xmp {
+  pub properties: Vec<XmpProperty>
+}
+
+impl Xmp {
+  pub fn parse(packet: &str) -> ParseResult<Xmp> {
+    let root = parse_xml(packet)?;
+    let rdf = root.find(NS_RDF, "RDF").ok_or(ParseError::InvalidXml { offset: 0 })?;
+    let properties = rdf.elements()
+      .filter(|e| e.is(NS_RDF, "Description"))
+      .flat_map(description_properties)
+      .collect();
+    Ok(Xmp { properties })
+  }
+
+  pub fn get(&self, namespace: &str, name: &str) -> Option<&XmpValue> {
+    find_property(&self.properties, namespace, name)
+  }
+
+  /** the star rating, `xmp:Rating`, with -1 for rejected images */
+  pub fn rating(&self) -> Option<i32> {
+    let rating = self.get(NS_XMP, "Rating")?.as_str()?.trim();
+    rating.parse::<i32>().ok()
+      .or_else(|| rating.parse::<f64>().ok().map(|r| r as i32))
+  }
+
+  /** the color label, `xmp:Label` */
+  pub fn label(&self) -> Option<&str> {
+    self.get(NS_XMP, "Label")?.as_str()
+  }
+
+  /** the keywords, `dc:subject` */
+  pub fn keywords(&self) -> Vec<&str> {
+    self.get(NS_DC, "subject")
+      .map(|v| v.items().iter().filter_map(|k| k.as_str()).collect())
+      .unwrap_or_default()
+  }
+
+  /** the keywords with their parents separated by `|`, `lr:hierarchicalSubject` */
+  pub fn hierarchical_keywords(&self) -> Vec<&str> {
+    self.get(NS_LIGHTROOM, "hierarchicalSubject")
+      .map(|v| v.items().iter().filter_map(|k| k.as_str()).collect())
+      .unwrap_or_default()
+  }
+
+  /** the GUID of the extended XMP, `xmpNote:HasExtendedXMP` */
+  pub fn extended_xmp_guid(&self) -> Option<&str> {
+    self.get(NS_XMP_NOTE, "HasExtendedXMP")?.as_str()
+  }
+
+  /** adds the properties of the extended XMP, which replace the `xmpNote:HasExtendedXMP` reference */
+  pub fn merge(&mut self, extended: Xmp) {
+    self.properties.retain(|p| !(p.namespace == NS_XMP_NOTE && p.name == "HasExtendedXMP"));
+    self.properties.extend(extended.properties);
+  }
+}
+
+/** Returns the XMP packet of an APP1 segment, or `None` if the segment holds something else */
+pub fn read_xmp_packet<'a>(app1_cursor: Cursor<'a>) -> ParseResult<Option<&'a str>> {
+  let mut cursor = app1_cursor;
+  let data = cursor.read_bytes_or_fail(app1_cursor.len())?;
+  if !data.starts_with(XMP_SEGMENT_HEADER) {
+    return Ok(None);
+  }
+  let packet = str::from_utf8(&data[XMP_SEGMENT_HEADER.len() ..])
+    .map_err(|e| ParseError::InvalidXml { offset: e.valid_up_to() })?;
+  Ok(Some(packet.trim_end_matches('\0')))
+}
+
+/** A part of an extended XMP packet, which is split over APP1 segments
+    as it doesn't fit into one */
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ExtendedXmpChunk<'a> {
+  /** the MD5 digest of the whole extended packet, as 32 uppercase hex digits */
+  pub guid: &'a str,
+  pub full_length: u32,
+  pub offset: u32,
+  pub data: &'a [u8]
+}
+
+/** Returns the extended XMP chunk of an APP1 segment, or `None` if the segment holds something else */
+pub fn read_extended_xmp_chunk<'a>(app1_cursor: Cursor<'a>) -> ParseResult<Option<ExtendedXmpChunk<'a>>> {
+  let mut cursor = app1_cursor.with_endianness(Endianness::Big);
+  let header = EXTENDED_XMP_SEGMENT_HEADER;
+  if cursor.read_bytes(header.len()) != Some(header) {
+    return Ok(None);
+  }
+  let guid = cursor.read_str_or_fail(GUID_LEN)?;
+  let full_length : u32 = cursor.read_num_or_fail()?;
+  let offset : u32 = cursor.read_num_or_fail()?;
+  let data = cursor.read_bytes_or_fail(cursor.len())?;
+  Ok(Some(ExtendedXmpChunk { guid, full_length, offset, data }))
+}
+
+/** Joins the chunks of the extended XMP packet with the given GUID, which may be in any order.
+    Chunks of other packets are ignored. The packet must be complete and match its MD5 digest. */
+pub fn assemble_extended_xmp(guid: &str, chunks: &[ExtendedXmpChunk]) -> ParseResult<String> {
+  let mut chunks : Vec<&ExtendedXmpChunk> = chunks.iter().filter(|c| c.guid == guid).collect();
+  chunks.sort_by_key(|c| c.offset);
+  let full_length = chunks.first().map(|c| c.full_length as usize).unwrap_or(0);
+
+  //the full length is untrusted, so the packet only grows by the chunks actually stored
+  let mut packet = Vec::new();
+  for chunk in chunks {
+    let is_next = chunk.full_length as usize == full_length && chunk.offset as usize == packet.len();
+    if !is_next || packet.len() + chunk.data.len() > full_length {
+      return Err(ParseError::InvalidExtendedXmp { offset: chunk.offset as usize });
+    }
+    packet.extend_from_slice(chunk.data);
+  }
+  if packet.is_empty() || packet.len() != full_length {
+    return Err(ParseError::InvalidExtendedXmp { offset: packet.len() });
+  }
+  if md5_hex(&packet) != guid.to_uppercase() {
+    return Err(ParseError::ExtendedXmpChecksumMismatch);
+  }
+  String::from_utf8(packet)
+    .map_err(|e| ParseError::InvalidXml { offset: e.utf8_error().valid_up_to() })
+}
+
+/** Reads the XMP of a JPEG file, merged with its extended XMP if it has any.
+    If the extended XMP was stripped from the file or is corrupt, only the main packet is returned. */
+pub fn read_xmp(jpeg: &[u8]) -> ParseResult<Option<Xmp>> {
+  let mut packet = None;
+  let mut chunks = Vec::new();
+  for result in JPEGSegmentIterator::new(Cursor::new(jpeg, Endianness::Big)) {
+    let (marker, segment) = result?;
+    if marker != SegmentMarker::APP(1) {
+      continue;
+    }
+    //a corrupt chunk only means the extended XMP can't be assembled
+    match read_extended_xmp_chunk(segment) {
+      Ok(Some(chunk)) => chunks.push(chunk),
+      Ok(None) if packet.is_none() => packet = read_xmp_packet(segment)?,
+      _ => ()
+    }
+  }
+
+  let mut xmp = match packet {
+    Some(packet) => Xmp::parse(packet)?,
+    None => return Ok(None)
+  };
+  let guid = xmp.extended_xmp_guid().map(|g| g.to_string());
+  if let Some(guid) = guid {
+    let extended = assemble_extended_xmp(&guid, &chunks)
+      .and_then(|extended| Xmp::parse(&extended));
+    if let Ok(extended) = extended {
+      xmp.merge(extended);
+    }
+  }
+  Ok(Some(xmp))
+}
+
+#[cfg(test)]
+mod tests {
+  use ::cursor::{Cursor, Endianness};
+  use ::jpeg::{JPEGSegmentIterator, SegmentMarker};
+  use ::test_fixtures::JPEG_SAMPLE;
+  use ::error::ParseError;
+  use ::jpeg_editor::JPEGEditor;
+  use ::md5::md5_hex;
+  use super::{read_extended_xmp_chunk, assemble_extended_xmp, ExtendedXmpChunk};
+  use super::{read_xmp_packet, read_xmp, Xmp, XmpValue, NS_XMP, NS_PHOTOSHOP, NS_DC};
+  use super::{EXTENDED_XMP_SEGMENT_HEADER, XMP_SEGMENT_HEADER};
+
+  const EXTENDED_PACKET : &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+    <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+      <rdf:Description rdf:about="" xmlns:GDepth="http://ns.google.com/photos/1.0/depthmap/"
+          GDepth:Mime="image/jpeg" GDepth:Data="/9j/4AAQSkZJRgABAQAAAQABAAD"/>
+    </rdf:RDF>
+  </x:xmpmeta>"#;
+
+  /** the sample JPEG with its XMP replaced by one referring to extended XMP,
+      whose chunks are stored in reverse order */
+  fn jpeg_with_extended_xmp(extended: &str, guid: &str) -> Vec<u8> {
+    let main = format!(r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+      <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+        <rdf:Description rdf:about="" xmlns:xmpNote="http://ns.adobe.com/xmp/note/"
+            xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:Rating="5" xmpNote:HasExtendedXMP="{}"/>
+      </rdf:RDF>
+    </x:xmpmeta>"#, guid);
+    let mut main_payload = XMP_SEGMENT_HEADER.to_vec();
+    main_payload.extend_from_slice(main.as_bytes());
+
+    let mut editor = JPEGEditor::new(JPEG_SAMPLE).unwrap();
+    let index = editor.segments().iter()
+      .position(|s| s.payload().starts_with(XMP_SEGMENT_HEADER))
+      .unwrap();
+    editor.replace_segment(index, main_payload);
+
+    let split = extended.len() / 2;
+    for &(offset, data) in &[(0, &extended[.. split]), (split, &extended[split ..])] {
+      let mut chunk = EXTENDED_XMP_SEGMENT_HEADER.to_vec();
+      chunk.extend_from_slice(guid.as_bytes());
+      chunk.extend_from_slice(&(extended.len() as u32).to_be_bytes());
+      chunk.extend_from_slice(&(offset as u32).to_be_bytes());
+      chunk.extend_from_slice(data.as_bytes());
+      editor.insert_segment(index + 1, SegmentMarker::APP(1), chunk);
+    }
+    editor.write().unwrap()
+  }
+
+  #[test]
+  fn test_sample_xmp() {
+    let cursor = Cursor::new(JPEG_SAMPLE, Endianness::Big);
+    let packets : Vec<&str> = JPEGSegmentIterator::new(cursor)
+      .map(|s| s.unwrap())
+      .filter(|&(marker, _)| marker == SegmentMarker::APP(1))
+      .filter_map(|(_, segment)| read_xmp_packet(segment).unwrap())
+      .collect();
+    assert_eq!(packets.len(), 1);
+    assert!(packets[0].starts_with("<?xpacket begin"));
+
+    let xmp = Xmp::parse(packets[0]).unwrap();
+    assert_eq!(xmp.get(NS_XMP, "CreatorTool"), Some(&XmpValue::Simple("Windows Phone".to_string())));
+    assert_eq!(xmp.get(NS_XMP, "CreateDate").and_then(|v| v.as_str()), Some("2013-05-10T15:21:35"));
+    assert_eq!(xmp.get(NS_PHOTOSHOP, "DateCreated").and_then(|v| v.as_str()), Some("2013-05-10T15:21:35"));
+    assert_eq!(xmp.rating(), None);
+  }
+
+  #[test]
+  fn test_lightroom_xmp() {
+    let xmp = Xmp::parse(r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+      <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+        <rdf:Description rdf:about=""
+            xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+            xmlns:dc="http://purl.org/dc/elements/1.1/"
+            xmlns:lr="http://ns.adobe.com/lightroom/1.0/"
+            xmlns:Iptc4xmpCore="http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/"
+            xmp:Rating="4" xmp:Label="Red">
+          <dc:subject><rdf:Bag><rdf:li>beach</rdf:li><rdf:li>sunset</rdf:li></rdf:Bag></dc:subject>
+          <lr:hierarchicalSubject><rdf:Bag><rdf:li>places|beach</rdf:li></rdf:Bag></lr:hierarchicalSubject>
+          <dc:creator><rdf:Seq><rdf:li>Jane Doe</rdf:li></rdf:Seq></dc:creator>
+          <dc:title><rdf:Alt>
+            <rdf:li xml:lang="de">Strand</rdf:li>
+            <rdf:li xml:lang="x-default">Beach</rdf:li>
+          </rdf:Alt></dc:title>
+          <Iptc4xmpCore:CreatorContactInfo rdf:parseType="Resource">
+            <Iptc4xmpCore:CiAdrCity>Lisbon</Iptc4xmpCore:CiAdrCity>
+          </Iptc4xmpCore:CreatorContactInfo>
+          <Iptc4xmpCore:Location Iptc4xmpCore:CountryCode="PT"/>
+        </rdf:Description>
+      </rdf:RDF>
+    </x:xmpmeta>"#).unwrap();
+    let iptc = "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/";
+
+    assert_eq!(xmp.rating(), Some(4));
+    assert_eq!(xmp.label(), Some("Red"));
+    assert_eq!(xmp.keywords(), vec!["beach", "sunset"]);
+    assert_eq!(xmp.hierarchical_keywords(), vec!["places|beach"]);
+    assert_eq!(xmp.get(NS_DC, "creator"), Some(&XmpValue::Seq(vec![XmpValue::Simple("Jane Doe".to_string())])));
+    let title = xmp.get(NS_DC, "title").unwrap();
+    assert_eq!(title.default_alt().and_then(|v| v.as_str()), Some("Beach"));
+    let contact = xmp.get(iptc, "CreatorContactInfo").unwrap();
+    assert_eq!(contact.field(iptc, "CiAdrCity").and_then(|v| v.as_str()), Some("Lisbon"));
+    let location = xmp.get(iptc, "Location").unwrap();
+    assert_eq!(location.field(iptc, "CountryCode").and_then(|v| v.as_str()), Some("PT"));
+  }
+
+  #[test]
+  fn test_extended_xmp() {
+    let guid = md5_hex(EXTENDED_PACKET.as_bytes());
+    let jpeg = jpeg_with_extended_xmp(EXTENDED_PACKET, &guid);
+    let xmp = read_xmp(&jpeg).unwrap().unwrap();
+    let depth = "http://ns.google.com/photos/1.0/depthmap/";
+    assert_eq!(xmp.rating(), Some(5));
+    assert_eq!(xmp.get(depth, "Mime").and_then(|v| v.as_str()), Some("image/jpeg"));
+    assert_eq!(xmp.extended_xmp_guid(), None);
+  }
+
+  #[test]
+  fn test_extended_xmp_checksum() {
+    let guid = md5_hex(b"something else");
+    let jpeg = jpeg_with_extended_xmp(EXTENDED_PACKET, &guid);
+    let chunks : Vec<ExtendedXmpChunk> = JPEGSegmentIterator::new(Cursor::new(&jpeg, Endianness::Big))
+      .filter_map(|s| read_extended_xmp_chunk(s.unwrap().1).unwrap())
+      .collect();
+    assert_eq!(assemble_extended_xmp(&guid, &chunks), Err(ParseError::ExtendedXmpChecksumMismatch));
+    //the main packet is still returned
+    let xmp = read_xmp(&jpeg).unwrap().unwrap();
+    assert_eq!(xmp.rating(), Some(5));
+    assert_eq!(xmp.get("http://ns.google.com/photos/1.0/depthmap/", "Mime"), None);
+  }
+
+  #[test]
+  fn test_truncated_extended_chunk() {
+    let guid = md5_hex(EXTENDED_PACKET.as_bytes());
+    let jpeg = jpeg_with_extended_xmp(EXTENDED_PACKET, &guid);
+    let mut editor = JPEGEditor::new(&jpeg).unwrap();
+    let mut chunk = EXTENDED_XMP_SEGMENT_HEADER.to_vec();
+    chunk.extend_from_slice(&guid.as_bytes()[.. 10]);
+    editor.insert_segment(1, SegmentMarker::APP(1), chunk);
+    let jpeg = editor.write().unwrap();
+
+    let xmp = read_xmp(&jpeg).unwrap().unwrap();
+    assert_eq!(xmp.rating(), Some(5));
+    assert_eq!(xmp.get("http://ns.google.com/photos/1.0/depthmap/", "Mime").and_then(|v| v.as_str()), Some("image/jpeg"));
+  }
+
+  #[test]
+  fn test_extended_xmp_length() {
+    let guid = md5_hex(EXTENDED_PACKET.as_bytes());
+    let chunk = ExtendedXmpChunk { guid: &guid, full_length: u32::MAX, offset: 0, data: b"<x:xmpmeta" };
+    assert_eq!(assemble_extended_xmp(&guid, &[chunk]), Err(ParseError::InvalidExtendedXmp { offset: 10 }));
+    //a chunk longer than the full length
+    let chunk = ExtendedXmpChunk { guid: &guid, full_length: 4, offset: 0, data: b"<x:xmpmeta" };
+    assert_eq!(assemble_extended_xmp(&guid, &[chunk]), Err(ParseError::InvalidExtendedXmp { offset: 0 }));
+  }
+
+  #[test]
+  fn test_not_xmp() {
+    let cursor = Cursor::new(b"Exif\0\0MM", Endianness::Big);
+    assert_eq!(read_xmp_packet(cursor), Ok(None));
+  }
+}